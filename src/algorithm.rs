@@ -1,4 +1,5 @@
 use rustdct::algorithm::type2and3_butterflies::{Type2And3Butterfly4, Type2And3Butterfly8};
+use std::str::FromStr;
 
 #[cfg(test)]
 #[rustfmt::skip]
@@ -312,3 +313,131 @@ pub fn similarity(f: &[f32; 10], f2: &[f32; 10]) -> f32 {
 //     }
 //     return ans;
 // }
+
+/// `similarity` with each feature weighted.
+pub fn weighted_similarity(f: &[f32], f2: &[f32], w: &[f32]) -> f32 {
+    return f
+        .iter()
+        .zip(f2)
+        .zip(w)
+        .map(|((a, b), w)| (a - b).abs() * w)
+        .sum();
+}
+
+/// How dissimilar two features are, lower is more similar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Sum of absolute differences, i.e. `similarity`
+    L1,
+    /// Euclidean distance, which penalizes large differences (often brightness offsets) more
+    L2,
+    /// One minus the cosine similarity, which ignores the scale of features
+    Cosine,
+    /// `L1` with each feature weighted
+    #[default]
+    Weighted,
+}
+
+impl Metric {
+    pub const NAMES: &'static [&'static str] = &["l1", "l2", "cosine", "weighted"];
+
+    /// Weights are used only by `Weighted`; Features may be truncated, see `charset::Charset::coeffs`.
+    pub fn distance(self, f: &[f32], f2: &[f32], w: &[f32]) -> f32 {
+        return match self {
+            Metric::L1 => f.iter().zip(f2).map(|(a, b)| (a - b).abs()).sum(),
+            Metric::L2 => f
+                .iter()
+                .zip(f2)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f32>()
+                .sqrt(),
+            Metric::Cosine => {
+                let dot = f.iter().zip(f2).map(|(a, b)| a * b).sum::<f32>();
+                let norm = |f: &[f32]| f.iter().map(|a| a * a).sum::<f32>().sqrt();
+                match norm(f) * norm(f2) {
+                    n if n > 0. => 1. - dot / n,
+                    _ => 1.,
+                }
+            }
+            Metric::Weighted => weighted_similarity(f, f2, w),
+        };
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "l1" => Ok(Metric::L1),
+            "l2" => Ok(Metric::L2),
+            "cosine" => Ok(Metric::Cosine),
+            "weighted" => Ok(Metric::Weighted),
+            _ => Err(format!("Unknown metric \"{}\"", s)),
+        };
+    }
+}
+
+/// Bins of gradient orientations within `0..π`, i.e. `0°, 45°, 90°, 135°` of gradients,
+/// so strokes of `|`, `/`, `_` and `\` respectively.
+pub const ORIENT_BINS: usize = 4;
+
+/// Histogram of gradient orientations of the left `4x8` of the block, weighted by magnitudes.
+pub fn orient_4x8_feature(b: &[[f32; 8]; 8]) -> [f32; ORIENT_BINS] {
+    return orient(b, 4);
+}
+
+/// Histogram of gradient orientations of the block, weighted by magnitudes.
+pub fn orient_8x8_feature(b: &[[f32; 8]; 8]) -> [f32; ORIENT_BINS] {
+    return orient(b, 8);
+}
+
+fn orient(b: &[[f32; 8]; 8], w: usize) -> [f32; ORIENT_BINS] {
+    let mut hist = [0f32; ORIENT_BINS];
+    for y in 0..8 {
+        for x in 0..w {
+            // Central differences, one-sided on borders.
+            let gx = b[y][(x + 1).min(w - 1)] - b[y][x.max(1) - 1];
+            let gy = b[(y + 1).min(7)][x] - b[y.max(1) - 1][x];
+            let m = gx.hypot(gy);
+            if m == 0. {
+                continue;
+            }
+            // Flip to `[0, π)`, then split linearly between the two nearest bins.
+            let a = gy.atan2(gx).rem_euclid(std::f32::consts::PI);
+            let pos = a / std::f32::consts::PI * ORIENT_BINS as f32;
+            let i = pos.floor() as usize % ORIENT_BINS;
+            let frac = pos - pos.floor();
+            hist[i] += m * (1. - frac);
+            hist[(i + 1) % ORIENT_BINS] += m * frac;
+        }
+    }
+    return hist;
+}
+
+/// Approximate the block from its features, the inverse of `extract` after DCT,
+/// for deriving orientations of glyphs whose pixels are unknown.
+pub fn reconstruct(f: &[f32; 10], wide: bool) -> [[f32; 8]; 8] {
+    #[rustfmt::skip]
+    const POS: [(usize, usize); 10] = [(0, 0), (1, 0), (0, 1), (0, 2), (1, 1), (2, 0), (3, 0), (2, 1), (1, 2), (0, 3)];
+    let pi = std::f32::consts::PI;
+    let w = if wide { 8 } else { 4 };
+    let norm = |k: usize, n: usize| if k == 0 { 1. / n as f32 } else { 2. / n as f32 };
+    let mut b = [[0f32; 8]; 8];
+    for y in 0..8 {
+        for x in 0..w {
+            for (&(v, u), n) in POS.iter().zip(f) {
+                b[y][x] += n
+                    * norm(v, 8)
+                    * norm(u, w)
+                    * (pi / 8. * (y as f32 + 0.5) * v as f32).cos()
+                    * (pi / w as f32 * (x as f32 + 0.5) * u as f32).cos();
+            }
+        }
+    }
+    return b;
+}
+
+pub fn orient_similarity(h: &[f32; ORIENT_BINS], h2: &[f32; ORIENT_BINS]) -> f32 {
+    return h.iter().zip(h2).map(|(a, b)| (a - b).abs()).sum();
+}
@@ -0,0 +1,241 @@
+//! Classic ANSI art (`.ans`) of the BBS scene: CP437 text with SGR escape sequences.
+
+use crate::*;
+use art::{Cell, Frame};
+use cluster::Glyph;
+
+pub const ANS_SUFFIX: &str = "ans";
+/// Columns of ANSI art without a SAUCE record telling otherwise.
+pub const ANS_COLUMNS: usize = 80;
+
+/// Colors of VGA text mode, which ANSI art is drawn for.
+#[rustfmt::skip]
+pub const VGA16: [[u8; 3]; 16] = [
+    [  0,   0,   0], [170,   0,   0], [  0, 170,   0], [170,  85,   0],
+    [  0,   0, 170], [170,   0, 170], [  0, 170, 170], [170, 170, 170],
+    [ 85,  85,  85], [255,  85,  85], [ 85, 255,  85], [255, 255,  85],
+    [ 85,  85, 255], [255,  85, 255], [ 85, 255, 255], [255, 255, 255],
+];
+
+/// Colors of the xterm-256 palette, whose first 16 are taken as VGA ones.
+fn xterm256(n: usize) -> [u8; 3] {
+    const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    return match n {
+        0..=15 => VGA16[n],
+        16..=231 => [
+            CUBE[(n - 16) / 36],
+            CUBE[(n - 16) / 6 % 6],
+            CUBE[(n - 16) % 6],
+        ],
+        _ => [(8 + 10 * (n.min(255) - 232)) as u8; 3],
+    };
+}
+
+/// Glyphs of CP437 in Unicode; Control bytes are shown as their glyphs too.
+#[rustfmt::skip]
+const CP437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', ' ',
+];
+
+pub fn is_ans<P: AsRef<Path>>(p: P) -> bool {
+    return p
+        .as_ref()
+        .extension()
+        .map_or(false, |e| e.eq_ignore_ascii_case(ANS_SUFFIX));
+}
+
+/// Columns told by the SAUCE record at the end, if any.
+fn sauce_columns(bytes: &[u8]) -> Option<usize> {
+    let sauce = bytes.len().checked_sub(128).map(|i| &bytes[i..])?;
+    if !sauce.starts_with(b"SAUCE") || sauce[94] != 1 {
+        return None; // Not of characters.
+    }
+    let cols = u16::from_le_bytes([sauce[96], sauce[97]]) as usize;
+    return Some(cols).filter(|c| *c > 0);
+}
+
+/// Graphics state set by SGR.
+#[derive(Clone, Copy)]
+struct Pen {
+    fg: [u8; 3],
+    bg: [u8; 3],
+    bold: bool,
+    blink: bool,
+    reverse: bool,
+    /// Indices into `VGA16`, if not set by 24-bit colors
+    index: (Option<usize>, Option<usize>),
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        return Pen {
+            fg: VGA16[7],
+            bg: VGA16[0],
+            bold: false,
+            blink: false,
+            reverse: false,
+            index: (Some(7), Some(0)),
+        };
+    }
+}
+
+impl Pen {
+    fn sgr(&mut self, params: &[usize]) {
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Pen::default(),
+                1 => self.bold = true,
+                5 => self.blink = true,
+                7 => self.reverse = true,
+                22 => self.bold = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                30..=37 => self.index.0 = Some(p - 30),
+                40..=47 => self.index.1 = Some(p - 40),
+                39 => self.index.0 = Some(7),
+                49 => self.index.1 = Some(0),
+                90..=97 => self.index.0 = Some(p - 90 + 8),
+                100..=107 => self.index.1 = Some(p - 100 + 8),
+                38 | 48 => {
+                    let rgb = match params.next() {
+                        Some(2) => {
+                            let mut n = || params.next().unwrap_or(0).min(255) as u8;
+                            [n(), n(), n()]
+                        }
+                        Some(5) => xterm256(params.next().unwrap_or(0)),
+                        _ => continue,
+                    };
+                    match p {
+                        38 => (self.fg, self.index.0) = (rgb, None),
+                        _ => (self.bg, self.index.1) = (rgb, None),
+                    }
+                }
+                _ => (),
+            }
+        }
+        // Bold brightens the foreground, and blink the background as in iCE colors.
+        if let Some(i) = self.index.0 {
+            self.fg = VGA16[i | (self.bold as usize) << 3];
+        }
+        if let Some(i) = self.index.1 {
+            self.bg = VGA16[i | (self.blink as usize) << 3];
+        }
+    }
+
+    fn cell(&self, glyph: char) -> Cell {
+        let (fg, bg) = match self.reverse {
+            true => (self.bg, self.fg),
+            false => (self.fg, self.bg),
+        };
+        return Cell {
+            color: fg,
+            // Black backgrounds are left to terminals, which are mostly dark.
+            bg: Some(bg).filter(|bg| *bg != VGA16[0]),
+            glyph: Glyph::new(glyph),
+            attrs: 0,
+        };
+    }
+}
+
+/// Frames drawn by the ANSI art, a new one begins whenever the screen is cleared;
+/// Cursor movements are followed, lines are wrapped at the columns told by SAUCE or 80.
+pub fn import_ans(bytes: &[u8]) -> Vec<Frame> {
+    let cols = sauce_columns(bytes).unwrap_or(ANS_COLUMNS);
+    let blank = Pen::default().cell(' ');
+    let mut frames = Vec::new();
+    let mut lines = Vec::<Vec<Cell>>::new();
+    let (mut pen, mut x, mut y, mut saved) = (Pen::default(), 0usize, 0usize, (0, 0));
+    let mut bytes = bytes.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            0x1a => break, // SAUCE and comments follow.
+            b'\r' => x = 0,
+            b'\n' => (x, y) = (0, y + 1),
+            b'\t' => x = (x / 8 + 1) * 8,
+            0x1b if bytes.peek() == Some(&b'[') => {
+                bytes.next();
+                let mut params = vec![0usize];
+                let cmd = loop {
+                    match bytes.next() {
+                        Some(d @ b'0'..=b'9') => {
+                            let p = params.last_mut().unwrap();
+                            *p = p.saturating_mul(10).saturating_add((d - b'0') as usize);
+                        }
+                        Some(b';') => params.push(0),
+                        Some(b'?' | b'=') => (),
+                        Some(c) => break c,
+                        None => break 0,
+                    }
+                };
+                let n = params[0].max(1);
+                match cmd {
+                    b'm' => pen.sgr(&params),
+                    b'A' => y = y.saturating_sub(n),
+                    b'B' => y += n,
+                    b'C' => x = (x + n).min(cols - 1),
+                    b'D' => x = x.saturating_sub(n),
+                    b'H' | b'f' => {
+                        y = params[0].max(1) - 1;
+                        x = params.get(1).copied().unwrap_or(1).max(1) - 1;
+                    }
+                    b'J' if params[0] == 2 => {
+                        if lines.iter().any(|l| !l.is_empty()) {
+                            frames.push(Frame {
+                                lines: std::mem::take(&mut lines),
+                            });
+                        }
+                        lines.clear();
+                        (x, y) = (0, 0);
+                    }
+                    b'K' => {
+                        if let Some(line) = lines.get_mut(y) {
+                            line.truncate(x);
+                        }
+                    }
+                    b's' => saved = (x, y),
+                    b'u' => (x, y) = saved,
+                    _ => (),
+                }
+            }
+            b => {
+                if x >= cols {
+                    (x, y) = (0, y + 1);
+                }
+                if lines.len() <= y {
+                    lines.resize(y + 1, Vec::new());
+                }
+                let line = &mut lines[y];
+                if line.len() <= x {
+                    line.resize(x + 1, blank);
+                }
+                line[x] = pen.cell(CP437[b as usize]);
+                x += 1;
+            }
+        }
+    }
+    if lines.iter().any(|l| !l.is_empty()) || frames.is_empty() {
+        frames.push(Frame { lines });
+    }
+    // Lines of the same width, as on screens.
+    for frame in &mut frames {
+        let width = frame.lines.iter().map(Vec::len).max().unwrap_or(0);
+        frame.lines.iter_mut().for_each(|l| l.resize(width, blank));
+    }
+    return frames;
+}
@@ -0,0 +1,2748 @@
+//! The `.shoal` and `.shoala` formats, conversion from images, and playing on terminals.
+
+use crate::*;
+use charset::Charset;
+use cluster::{read_glyph, write_glyph, Glyph};
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+};
+use image::{
+    imageops::{self, Triangle},
+    GrayImage, Luma, Rgb, RgbImage,
+};
+use plugin::Plugins;
+use std::{
+    cmp::Reverse,
+    fs::File,
+    io::{self, Read, Write},
+    str::FromStr,
+    time::Duration,
+};
+use util::Codec;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(glyph: char, color: [u8; 3]) -> Cell {
+        return Cell {
+            color,
+            bg: None,
+            glyph: Glyph::new(glyph),
+            attrs: 0,
+        };
+    }
+
+    fn frame(rows: &[&str], color: [u8; 3]) -> Frame {
+        let lines = rows
+            .iter()
+            .map(|r| r.chars().map(|c| cell(c, color)).collect());
+        return Frame {
+            lines: lines.collect(),
+        };
+    }
+
+    /// Written into the temp dir, unique to the process.
+    fn temp(name: &str, content: &[u8]) -> PathBuf {
+        let p = std::env::temp_dir().join(format!("shoalart-{}-{}", std::process::id(), name));
+        std::fs::write(&p, content).unwrap();
+        return p;
+    }
+
+    #[test]
+    fn test_anim_deltas() {
+        let mut art = Art::default();
+        let key = frame(&["abcd", "efgh", "ijkl"], [255, 0, 0]);
+        let mut delta = key.clone();
+        delta.lines[1][2] = cell('x', [0, 255, 0]);
+        art.push(key.clone(), Duration::from_millis(40));
+        art.push(delta, Duration::from_millis(80));
+        art.push(
+            frame(&["mnop", "qrst", "uvwx"], [0, 0, 255]),
+            Duration::ZERO,
+        );
+        let mut buf = Vec::new();
+        encode_anim(&mut buf, &art).unwrap();
+        // Dimensions and count, delay and kind of the keyframe, delay of the delta.
+        let mut lines = Vec::new();
+        encode_lines(&mut lines, &key, write_cell).unwrap();
+        assert_eq!(buf[8 + 5 + lines.len() + 4], 1);
+        assert_eq!(decode_anim(&buf[..]).unwrap(), art);
+    }
+    #[test]
+    fn test_art_v0() {
+        let mut buf = vec![0, 2, 0, 2];
+        buf.extend([1, 2, 3, 0, 0, 0, b'a', 4, 5, 6, 0, 0, 0, b'b']);
+        buf.extend([0, 1, 7, 8, 9, 0, 0, 0, b'c']);
+        let frame = decode_art_v0(&buf[..]).unwrap();
+        assert_eq!(frame.lines[0], [cell('a', [1, 2, 3]), cell('b', [4, 5, 6])]);
+        assert_eq!(frame.lines[1], [cell('c', [7, 8, 9])]);
+        assert!(decode_art_v0(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_read_timing() {
+        let p = temp("test.timing", b"# ms\n40\n\n 12.5 \n0\n");
+        let delays = read_timing(&p).unwrap();
+        assert_eq!(delays, [40_000, 12_500, 0].map(Duration::from_micros));
+        let q = temp("test.timing.out", b"");
+        write_timing(&q, &delays).unwrap();
+        assert_eq!(read_timing(&q).unwrap(), delays);
+        std::fs::write(&p, "40\n-1\n").unwrap();
+        assert!(read_timing(&p).is_err());
+        std::fs::write(&p, "40\nabc\n").unwrap();
+        assert!(read_timing(&p).is_err());
+        [p, q].iter().for_each(|p| std::fs::remove_file(p).unwrap());
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        let key = frame(&["○ab", "字"], [1, 2, 3]);
+        assert_eq!(key.dimensions(), (3, 2));
+        assert_eq!(key.dimensions_as(Ambiguous::Wide), (4, 2));
+        // The wide `○` crosses the left edge.
+        let cropped = key.crop((2, 1, 1, 0), Ambiguous::Wide);
+        assert_eq!(
+            cropped.lines[0],
+            [cell(' ', [1, 2, 3]), cell('a', [1, 2, 3])]
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let choice = |c: char, score: f32| Choice {
+            glyph: Glyph::new(c),
+            score,
+            mean: 0.,
+            dc: 0.,
+            energy: 0.,
+        };
+        let units = |row: Option<Vec<(usize, bool, Choice)>>| {
+            let row = row.unwrap().into_iter();
+            return row
+                .map(|(u, w, c)| (u, w, c.glyph.first()))
+                .collect::<Vec<_>>();
+        };
+        // A greedy pass would take `a` and `B` for being cheaper, then be left with `d`.
+        let halves = [
+            choice('a', 1.),
+            choice('b', 1.),
+            choice('c', 1.),
+            choice('d', 5.),
+        ];
+        let fulls = [
+            choice('A', 1.5),
+            choice('B', 0.5),
+            choice('C', 1.5),
+            choice('D', 0.),
+        ];
+        let row = layout(&halves.map(Some), &fulls.map(Some));
+        assert_eq!(units(row), [(0, true, 'A'), (2, true, 'C')]);
+        let halves = [Some(choice('a', 1.)), None, Some(choice('c', 1.))];
+        let fulls = [None, Some(choice('B', 9.)), None];
+        let row = layout(&halves, &fulls);
+        assert_eq!(units(row), [(0, false, 'a'), (1, true, 'B')]);
+        assert!(layout(&[None, None], &[None, None]).is_none());
+        assert_eq!(layout(&[], &[]).map(|r| r.len()), Some(0));
+    }
+
+    #[test]
+    fn test_read_palette() {
+        let gpl = "GIMP Palette\nName: Test\nColumns: 2\n#\n  0   0   0\tBlack\n255 128 1 Orange\n";
+        let p = temp("test.gpl", gpl.as_bytes());
+        assert_eq!(read_palette(&p).unwrap(), [[0, 0, 0], [255, 128, 1]]);
+        std::fs::write(&p, "#FF8001\n\n00ff00\n").unwrap();
+        assert_eq!(read_palette(&p).unwrap(), [[255, 128, 1], [0, 255, 0]]);
+        std::fs::write(&p, "GIMP Palette\n256 0 0\n").unwrap();
+        assert!(read_palette(&p).is_err());
+        std::fs::write(&p, "#FF80\n").unwrap();
+        assert!(read_palette(&p).is_err());
+        std::fs::write(&p, "\n").unwrap();
+        assert!(read_palette(&p).is_err());
+        std::fs::remove_file(&p).unwrap();
+    }
+
+    #[test]
+    fn test_indexed() {
+        let mut key = frame(&["ab", "cd"], [1, 2, 3]);
+        key.lines[0][1].bg = Some([4, 5, 6]);
+        key.lines[1][0].color = [4, 5, 6];
+        let mut buf = Vec::new();
+        encode_art_as(&mut buf, &key, Channels::Indexed).unwrap();
+        // Two colors, with `u8` indices.
+        assert_eq!(buf[..4], [0, 0, 0, 2]);
+        assert_eq!(decode_art_as(&buf[..], Channels::Indexed).unwrap(), key);
+        // More than 256 colors, with `u16` indices.
+        let mut many = Frame::default();
+        many.lines.push(
+            (0..300)
+                .map(|i| cell('x', [i as u8, (i >> 8) as u8, 0]))
+                .collect(),
+        );
+        buf.clear();
+        encode_art_as(&mut buf, &many, Channels::Indexed).unwrap();
+        assert_eq!(decode_art_as(&buf[..], Channels::Indexed).unwrap(), many);
+        // Palettes of deltas are of their changes only.
+        let mut delta = key.clone();
+        delta.lines[1][1] = cell('e', [7, 8, 9]);
+        let art = Art {
+            frames: vec![key, delta, many],
+            delays: vec![Duration::from_millis(10); 3],
+        };
+        buf.clear();
+        encode_anim_as(&mut buf, &art, Channels::Indexed).unwrap();
+        assert_eq!(decode_anim_as(&buf[..], Channels::Indexed).unwrap(), art);
+    }
+
+    #[test]
+    fn test_runs() {
+        let key = frame(&["aaaab", "", "cddd"], [1, 2, 3]);
+        let line = &key.lines[0];
+        let runs = runs(line).map(|(c, n)| (c.glyph.first(), n));
+        assert_eq!(runs.collect::<Vec<_>>(), [('a', 4), ('b', 1)]);
+        let mut buf = Vec::new();
+        encode_art(&mut buf, &key).unwrap();
+        // Lines, then cells, flags, repeats of runs, colors and glyphs of each line.
+        assert_eq!(buf.len(), 2 + (2 + 10 + 8) + 2 + (2 + 8 + 10));
+        assert_eq!(decode_art(&buf[..]).unwrap(), key);
+        // Runs beyond the end of the line.
+        let buf = [0, 1, 0, 2, CELL_RUN, 0, 3, 1, 2, 3, 0, 0, 0, b'a'];
+        assert!(decode_art(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_footer() {
+        let key = frame(&["ab"], [1, 2, 3]);
+        let p = temp("test.shoal", b"");
+        write_art(&p, &key, Codec::None).unwrap();
+        assert_eq!(read_art(&p).unwrap(), key);
+        let buf = std::fs::read(&p).unwrap();
+        // Header, codec, flags, lines, cells and flags of the first cell, then its color.
+        let mut bad = buf.clone();
+        bad[ART_HEADER_LEN + 1 + 1 + 2 + 2 + 1] ^= 1;
+        std::fs::write(&p, &bad).unwrap();
+        assert!(read_art(&p).unwrap_err().contains("Checksum mismatch"));
+        // Files without the footer are still readable, but not those cut within it.
+        std::fs::write(&p, &buf[..buf.len() - FOOTER_LEN]).unwrap();
+        assert_eq!(read_art(&p).unwrap(), key);
+        std::fs::write(&p, &buf[..buf.len() - 3]).unwrap();
+        assert!(read_art(&p).is_err());
+        std::fs::remove_file(&p).unwrap();
+    }
+
+    #[test]
+    fn test_make_art_rows() {
+        let mut cs = Charset::builtin();
+        cs.full.push((Glyph::new('字'), [0.; 10], [0.; 4]));
+        let (draft, color) = (GrayImage::new(20, 21), RgbImage::new(20, 21));
+        // The partial bottom row of blocks is left out, in either direction.
+        for (cell, vertical, dims) in [
+            ((4, 8), false, (5, 2)),
+            ((4, 6), false, (5, 3)),
+            ((4, 8), true, (4, 2)),
+        ] {
+            let sampling = Sampling {
+                cell,
+                ..Default::default()
+            };
+            let matching = Matching {
+                vertical,
+                ..Default::default()
+            };
+            let frame = make_art(
+                &draft,
+                &color,
+                &cs,
+                matching,
+                sampling,
+                &mut Plugins::none(),
+            );
+            assert_eq!(frame.unwrap().dimensions(), dims);
+        }
+    }
+    #[test]
+    fn test_quadtree_dither() {
+        let cs = Charset::builtin();
+        // Gray within the range of the built-in charset, whose glyphs are mostly dark.
+        let (draft, color) = (
+            GrayImage::from_pixel(64, 64, Luma([40])),
+            RgbImage::new(64, 64),
+        );
+        let make = |quadtree| {
+            let matching = Matching {
+                dither: true,
+                quadtree,
+                ..Default::default()
+            };
+            let sampling = Sampling::default();
+            make_art(
+                &draft,
+                &color,
+                &cs,
+                matching,
+                sampling,
+                &mut Plugins::none(),
+            )
+            .unwrap()
+        };
+        // Flat regions are not decided once, as errors diffused into their blocks differ.
+        assert_eq!(make(Some((64, 1.))), make(None));
+    }
+}
+
+pub const ART_HEADER: &str = "Shoalart.v1 ART";
+pub const ART_HEADER_LEN: usize = ART_HEADER.len();
+/// Payloads of `Shoalart.v1 ART` and `Shoalart.v1 ANM` begin with `flags/u8`, whose bits of
+/// `ART_CHANNELS` are how colors of cells are stored, see `Channels`.
+pub const ART_CHANNELS: u8 = 0b11;
+/// Without flags, cells are `rgb/u8*3` and `char/u32` only; Still readable.
+pub const ART_HEADER_V0: &str = "Shoalart.v0 ART";
+pub const ANM_HEADER: &str = "Shoalart.v1 ANM";
+pub const ANM_HEADER_LEN: usize = ANM_HEADER.len();
+/// Foreground of cells read from files without colors.
+pub const MONO_COLOR: [u8; 3] = [255; 3];
+pub const ANM_SUFFIX: &str = "shoala";
+/// Suffix of the sidecar of a dir of `.shoal` files, see `timing_path`.
+pub const TIMING_SUFFIX: &str = "timing";
+/// Length of the footer after payloads: `u32` frames (`1` in `.shoal`) and `u32` CRC-32 of the
+/// payload before it; Files without it are still readable.
+pub const FOOTER_LEN: usize = 8;
+/// Tag of the optional chunk after the footer, see `CharsetStamp`.
+pub const STAMP_TAG: &[u8; 4] = b"CHRS";
+/// At most so many frames between two keyframes in `.shoala`.
+pub const ANM_KEYFRAME_INTERVAL: usize = 100;
+
+/// Begin of synchronized update (DEC mode 2026), terminals hold drawing until the end.
+pub const SYNC_BEGIN: &str = "\x1b[?2026h";
+/// End of synchronized update.
+pub const SYNC_END: &str = "\x1b[?2026l";
+
+/// Cell flag: A background color follows the foreground.
+pub const CELL_BG: u8 = 1;
+/// Cell flag and attribute: Bold, which thickens strokes on terminals that change weight.
+pub const CELL_BOLD: u8 = 1 << 1;
+/// Cell flag and attribute: Dim.
+pub const CELL_DIM: u8 = 1 << 2;
+/// Cell flag and attribute: Italic.
+pub const CELL_ITALIC: u8 = 1 << 3;
+/// Cell flag: The cell repeats, `u16` times in total follow the flags.
+pub const CELL_RUN: u8 = 1 << 4;
+/// All cell flags which are attributes.
+pub const CELL_ATTRS: u8 = CELL_BOLD | CELL_DIM | CELL_ITALIC;
+/// Glyph of cells made in `Mode::HalfBlock`.
+pub const HALF_BLOCK: Glyph = Glyph::new('▀');
+/// Size of the block of a half-width cell in px, `(width, height)`; Full-width ones are twice as wide.
+pub const DEFAULT_CELL: (u32, u32) = (4, 8);
+
+/// A colored character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub color: [u8; 3],
+    /// Terminal default if `None`.
+    pub bg: Option<[u8; 3]>,
+    pub glyph: Glyph,
+    /// Text attributes, any of `CELL_ATTRS`.
+    pub attrs: u8,
+}
+
+/// How images are converted into cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Match blocks of the draft against the charset
+    Glyph,
+    /// `▀` colored by the upper half, on the background of the lower half
+    HalfBlock,
+}
+
+impl Mode {
+    pub const NAMES: &'static [&'static str] = &["glyph", "halfblock"];
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "glyph" => Ok(Mode::Glyph),
+            "halfblock" => Ok(Mode::HalfBlock),
+            _ => Err(format!("Unknown mode \"{}\"", s)),
+        };
+    }
+}
+
+/// How blocks are matched against the charset.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Matching<'a> {
+    /// Diffuse the brightness error of each chosen glyph into neighboring blocks,
+    /// Floyd-Steinberg style, for smoother gradients
+    pub dither: bool,
+    /// Keep glyphs of `prev` in place, unless the best candidate scores lower by this fraction;
+    /// Suppresses shimmer of animations, zero to disable
+    pub stabilize: f32,
+    /// Weight of gradient orientations against DCT features, so that strokes of glyphs
+    /// follow local directions; Zero to disable
+    pub orient: f32,
+    /// How features of blocks and glyphs are compared
+    pub metric: algorithm::Metric,
+    /// Weights of DCT features, used by the `Weighted` metric
+    pub weights: Weights,
+    /// Maximum size of square regions in px (`16` or `32`), and the standard deviation of luma
+    /// below which they are flat; Flat regions, i.e. those with little DCT energy, repeat
+    /// the glyph of their first block, skipping matching of the rest
+    pub quadtree: Option<(u32, f32)>,
+    /// Multipliers of scores of glyphs, smaller favors them, see `read_bias`
+    pub bias: Option<&'a AHashMap<Glyph, f32>>,
+    /// Half-width glyph for blocks matched as spaces, or darker than the mean luma within `0..=255`,
+    /// so that empty regions have a consistent texture; Wide ones become two
+    pub blank: Option<(Glyph, f32)>,
+    /// Blocks whose AC energy of DCT exceeds this are made bold (e.g. `0.3`), so that strokes pop;
+    /// The energy is the magnitude of features but the DC term, divided by the area
+    pub bold: Option<f32>,
+    /// The previous frame of an animation, made from an image of the same size
+    pub prev: Option<&'a Frame>,
+    /// Only full-width glyphs, matched column by column from right to left, each from top
+    /// to bottom, as vertical CJK text is set; Frames are still made of lines
+    pub vertical: bool,
+}
+
+/// How images are divided into blocks, and how the color of each cell is taken from its block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sampling {
+    /// Size of blocks of half-width cells, see `DEFAULT_CELL`;
+    /// Blocks are resized to `4x8` (or `8x8`) before matching
+    pub cell: (u32, u32),
+    pub cell_color: CellColor,
+    /// Average in linear light instead of sRGB, which darkens saturated regions less
+    pub linear: bool,
+    /// Width of ambiguous chars on the terminal, which blocks of glyphs are laid out by
+    pub ambiguous: Ambiguous,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        return Sampling {
+            cell: DEFAULT_CELL,
+            cell_color: CellColor::default(),
+            linear: false,
+            ambiguous: Ambiguous::default(),
+        };
+    }
+}
+
+/// Which color of a block its cell takes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellColor {
+    /// Average of all pixels
+    #[default]
+    Average,
+    /// Average of the most common colors, which keeps high-contrast blocks vivid
+    Dominant,
+}
+
+impl CellColor {
+    pub const NAMES: &'static [&'static str] = &["average", "dominant"];
+}
+
+impl FromStr for CellColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "average" => Ok(CellColor::Average),
+            "dominant" => Ok(CellColor::Dominant),
+            _ => Err(format!("Unknown cell color \"{}\"", s)),
+        };
+    }
+}
+
+/// Weights of the 10 DCT features, from the mean brightness to finer structures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights(pub [f32; 10]);
+
+impl Weights {
+    pub const NAMES: &'static [&'static str] = &["uniform", "contrast", "structure"];
+    pub const UNIFORM: Weights = Weights([1.; 10]);
+    /// Favors the mean brightness, for smoother shading.
+    pub const CONTRAST: Weights = Weights([2.5, 1., 1., 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5]);
+    /// Favors lower frequencies other than the mean brightness, for sharper outlines.
+    pub const STRUCTURE: Weights = Weights([0.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1., 1., 1., 1.]);
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        return Weights::UNIFORM;
+    }
+}
+
+/// Either a preset name, or 10 numbers separated by commas.
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "uniform" => Ok(Weights::UNIFORM),
+            "contrast" => Ok(Weights::CONTRAST),
+            "structure" => Ok(Weights::STRUCTURE),
+            _ => {
+                let ws = s
+                    .split(',')
+                    .map(|w| w.trim().parse::<f32>().ok().filter(|w| *w >= 0.))
+                    .collect::<Option<Vec<_>>>();
+                match ws.map(|ws| ws.try_into()) {
+                    Some(Ok(ws)) => Ok(Weights(ws)),
+                    _ => Err(format!(
+                        "Invalid weights \"{}\"; Expected one of [{}], or 10 non-negative numbers",
+                        s,
+                        Weights::NAMES.join(", ")
+                    )),
+                }
+            }
+        };
+    }
+}
+
+/// How colors of cells are stored in files, as bits of `ART_CHANNELS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Channels {
+    /// `rgb/u8*3`
+    #[default]
+    Rgb,
+    /// `luma/u8`, about half of the size, for black-and-white footage
+    Gray,
+    /// Indices into a palette of each frame, mostly `u8`
+    Indexed,
+    /// No colors at all
+    None,
+}
+
+impl Channels {
+    /// Of the bits of `ART_CHANNELS` in flags; `None` if other bits are set.
+    pub fn from_byte(b: u8) -> Option<Channels> {
+        return match b {
+            0 => Some(Channels::Rgb),
+            1 => Some(Channels::Gray),
+            2 => Some(Channels::Indexed),
+            3 => Some(Channels::None),
+            _ => None,
+        };
+    }
+}
+
+/// Colors supported by terminals during play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colors {
+    /// 24-bit RGB
+    TrueColor,
+    /// The xterm-256 palette
+    Ansi256,
+    /// The 16 basic ANSI colors
+    Ansi16,
+    /// Guess from `COLORTERM` and `TERM`
+    Auto,
+}
+
+impl Colors {
+    pub const NAMES: &'static [&'static str] = &["truecolor", "256", "16", "auto"];
+
+    /// Resolve `Auto` by environment variables, as terminfo rarely knows truecolor.
+    pub fn detect(self) -> Colors {
+        if self != Colors::Auto {
+            return self;
+        }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        return if colorterm == "truecolor" || colorterm == "24bit" {
+            Colors::TrueColor
+        } else if term.is_empty() && cfg!(windows) {
+            Colors::TrueColor // Windows Terminal and conhost since Windows 10.
+        } else {
+            Colors::from_term(&term)
+        };
+    }
+
+    /// Guess by a terminal type, e.g. `xterm-256color`.
+    pub fn from_term(term: &str) -> Colors {
+        let term = term.to_ascii_lowercase();
+        return if term.contains("truecolor") || term.contains("direct") {
+            Colors::TrueColor
+        } else if term.contains("256") {
+            Colors::Ansi256
+        } else {
+            Colors::Ansi16
+        };
+    }
+
+    /// The nearest color available; `Auto` is treated as `TrueColor`.
+    pub fn quantize(self, [r, g, b]: [u8; 3]) -> Color {
+        return match self {
+            Colors::TrueColor | Colors::Auto => Color::Rgb { r, g, b },
+            Colors::Ansi256 => Color::AnsiValue(ansi256([r, g, b])),
+            Colors::Ansi16 => Color::AnsiValue(nearest(&ANSI16, [r, g, b]) as u8),
+        };
+    }
+}
+
+impl FromStr for Colors {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "truecolor" | "24bit" => Ok(Colors::TrueColor),
+            "256" => Ok(Colors::Ansi256),
+            "16" => Ok(Colors::Ansi16),
+            "auto" => Ok(Colors::Auto),
+            _ => Err(format!("Unknown colors \"{}\"", s)),
+        };
+    }
+}
+
+/// Width of East Asian ambiguous chars, e.g. `○` and `─`, which CJK terminals may draw as wide.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Ambiguous {
+    #[default]
+    Narrow,
+    Wide,
+}
+
+impl Ambiguous {
+    pub const NAMES: &'static [&'static str] = &["narrow", "wide"];
+}
+
+impl FromStr for Ambiguous {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "narrow" => Ok(Ambiguous::Narrow),
+            "wide" => Ok(Ambiguous::Wide),
+            _ => Err(format!("Unknown width \"{}\"", s)),
+        };
+    }
+}
+
+/// Columns the glyph takes on terminals, where ambiguous chars are `ambiguous`;
+/// `None` for control chars.
+pub fn glyph_width(g: Glyph, ambiguous: Ambiguous) -> Option<usize> {
+    return g.width(ambiguous == Ambiguous::Wide);
+}
+
+/// Default colors of xterm.
+#[rustfmt::skip]
+const ANSI16: [[u8; 3]; 16] = [
+    [  0,   0,   0], [205,   0,   0], [  0, 205,   0], [205, 205,   0],
+    [  0,   0, 238], [205,   0, 205], [  0, 205, 205], [229, 229, 229],
+    [127, 127, 127], [255,   0,   0], [  0, 255,   0], [255, 255,   0],
+    [ 92,  92, 255], [255,   0, 255], [  0, 255, 255], [255, 255, 255],
+];
+
+/// Levels of the `6x6x6` cube in xterm-256.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    return a
+        .iter()
+        .zip(b)
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum();
+}
+
+fn nearest(palette: &[[u8; 3]], rgb: [u8; 3]) -> usize {
+    return (0..palette.len())
+        .min_by_key(|&i| distance(palette[i], rgb))
+        .unwrap_or(0);
+}
+
+/// Either in the color cube or the grayscale ramp, whichever is closer.
+fn ansi256(rgb: [u8; 3]) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+            .unwrap_or(0)
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = [CUBE[r], CUBE[g], CUBE[b]];
+    let avg = rgb.iter().map(|&v| v as i32).sum::<i32>() / 3;
+    let i = ((avg - 8 + 5) / 10).clamp(0, 23);
+    let gray = [(8 + 10 * i) as u8; 3];
+    return match distance(gray, rgb) < distance(cube, rgb) {
+        true => 232 + i as u8,
+        false => (16 + 36 * r + 6 * g + b) as u8,
+    };
+}
+
+/// Lines of cells, i.e. the content of a `.shoal` file.
+///
+/// Lines may differ in length, since glyphs are either half or full width.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub lines: Vec<Vec<Cell>>,
+}
+
+impl Frame {
+    /// `(columns, rows)` on terminals where ambiguous chars are narrow.
+    pub fn dimensions(&self) -> (usize, usize) {
+        return self.dimensions_as(Ambiguous::Narrow);
+    }
+
+    /// Like `dimensions`, but ambiguous chars are `ambiguous`.
+    pub fn dimensions_as(&self, ambiguous: Ambiguous) -> (usize, usize) {
+        let cols = self.lines.iter().map(|l| {
+            l.iter()
+                .map(|c| glyph_width(c.glyph, ambiguous).unwrap_or(1))
+                .sum::<usize>()
+        });
+        return (cols.max().unwrap_or(0), self.lines.len());
+    }
+
+    /// Changed cells `(line, index, cell)` since `prev`; `None` if the shapes differ.
+    pub fn diff(&self, prev: &Frame) -> Option<Vec<(usize, usize, Cell)>> {
+        if self.lines.len() != prev.lines.len()
+            || self
+                .lines
+                .iter()
+                .zip(&prev.lines)
+                .any(|(a, b)| a.len() != b.len())
+        {
+            return None;
+        }
+        let mut changes = Vec::new();
+        for (y, (line, prev)) in self.lines.iter().zip(&prev.lines).enumerate() {
+            for (x, (cell, prev)) in line.iter().zip(prev).enumerate() {
+                if cell != prev {
+                    changes.push((y, x, *cell));
+                }
+            }
+        }
+        return Some(changes);
+    }
+
+    /// Crop or pad with `blank` to exactly `(columns, rows)`; Full-width glyphs crossing the right
+    /// edge are cropped too.
+    pub fn fit(&mut self, (cols, rows): (usize, usize), blank: Cell, ambiguous: Ambiguous) {
+        self.lines.resize(rows, Vec::new());
+        for line in &mut self.lines {
+            let mut w = 0;
+            let n = line
+                .iter()
+                .take_while(|c| {
+                    w += glyph_width(c.glyph, ambiguous).unwrap_or(1);
+                    return w <= cols;
+                })
+                .count();
+            line.truncate(n);
+            let w = line
+                .iter()
+                .map(|c| glyph_width(c.glyph, ambiguous).unwrap_or(1))
+                .sum::<usize>();
+            line.extend(std::iter::repeat(blank).take(cols - w));
+        }
+    }
+
+    /// Cells within `width x height` columns and rows from `(left, top)`; Full-width glyphs
+    /// crossing the edges become spaces.
+    pub fn crop(&self, (w, h, x, y): (usize, usize, usize, usize), ambiguous: Ambiguous) -> Frame {
+        let lines = self.lines.iter().skip(y).take(h).map(|line| {
+            let (mut cells, mut col) = (Vec::with_capacity(w), 0);
+            for cell in line {
+                let (l, r) = (col, col + glyph_width(cell.glyph, ambiguous).unwrap_or(1));
+                col = r;
+                if r <= x || l >= x + w {
+                    continue;
+                } else if l < x || r > x + w {
+                    let space = Cell {
+                        glyph: Glyph::new(' '),
+                        ..*cell
+                    };
+                    cells.extend(std::iter::repeat(space).take(r.min(x + w) - l.max(x)));
+                } else {
+                    cells.push(*cell);
+                }
+            }
+            return cells;
+        });
+        return Frame {
+            lines: lines.collect(),
+        };
+    }
+
+    /// Drop or duplicate lines, and cells in each line, to about `factor` times as many.
+    pub fn scale(&self, factor: f32) -> Frame {
+        let pick = |n: usize| {
+            (0..(n as f32 * factor).round() as usize)
+                .map(move |i| (((i as f32 + 0.5) / factor) as usize).min(n.saturating_sub(1)))
+        };
+        let lines = pick(self.lines.len()).map(|y| {
+            let line = &self.lines[y];
+            return pick(line.len()).map(|x| line[x]).collect();
+        });
+        return Frame {
+            lines: lines.collect(),
+        };
+    }
+
+    /// Apply changes from `diff`, out of range ones are ignored.
+    pub fn patch(&mut self, changes: &[(usize, usize, Cell)]) {
+        for &(y, x, cell) in changes {
+            if let Some(c) = self.lines.get_mut(y).and_then(|l| l.get_mut(x)) {
+                *c = cell;
+            }
+        }
+    }
+}
+
+/// Frames in order, i.e. an animation, the content of a `.shoala` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Art {
+    pub frames: Vec<Frame>,
+    /// How long each frame lasts, same length as `frames`; Zero if unspecified.
+    pub delays: Vec<Duration>,
+}
+
+impl Art {
+    pub fn push(&mut self, frame: Frame, delay: Duration) {
+        self.frames.push(frame);
+        self.delays.push(delay);
+    }
+
+    /// Maximum `(columns, rows)` of all frames.
+    pub fn dimensions(&self) -> (usize, usize) {
+        return self
+            .frames
+            .iter()
+            .map(Frame::dimensions)
+            .fold((0, 0), |(w, h), (w2, h2)| (w.max(w2), h.max(h2)));
+    }
+}
+
+/// The charset an art was made with, so that players can check whether the font covers
+/// all glyphs before playing.
+///
+/// Written after the footer as `STAMP_TAG`; `hash/u32`; `len/u16`; `source/utf8*len`;
+/// `count/u32`; `glyph/char*count`, see `cluster::read_glyph`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CharsetStamp {
+    /// CRC-32 of the charset file, zero for the built-in one
+    pub hash: u32,
+    /// E.g. the file name of the charset
+    pub source: String,
+    /// In ascending order
+    pub glyphs: Vec<Glyph>,
+}
+
+impl CharsetStamp {
+    pub fn new(cs: &Charset, source: String, hash: u32) -> Self {
+        let mut glyphs = cs.iter().map(|(c, ..)| c).collect::<Vec<_>>();
+        glyphs.sort_unstable();
+        glyphs.dedup();
+        return CharsetStamp {
+            hash,
+            source,
+            glyphs,
+        };
+    }
+
+    /// Glyphs for which `has` returns `false`, e.g. those not in a font.
+    pub fn missing<F: Fn(Glyph) -> bool>(&self, has: F) -> Vec<Glyph> {
+        return self.glyphs.iter().copied().filter(|c| !has(*c)).collect();
+    }
+
+    /// Right after `STAMP_TAG`.
+    fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; 4];
+        r.read_exact(&mut buf)?;
+        let hash = u32::from_be_bytes(buf);
+        r.read_exact(&mut buf[..2])?;
+        let mut source = vec![0u8; u16::from_be_bytes([buf[0], buf[1]]) as usize];
+        r.read_exact(&mut source)?;
+        r.read_exact(&mut buf)?;
+        let count = u32::from_be_bytes(buf) as usize;
+        let mut glyphs = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            glyphs.push(read_glyph(r)?);
+        }
+        return Ok(CharsetStamp {
+            hash,
+            source: String::from_utf8_lossy(&source).into_owned(),
+            glyphs,
+        });
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let source = &self.source.as_bytes()[..self.source.len().min(u16::MAX as usize)];
+        w.write_all(STAMP_TAG)?;
+        w.write_all(&self.hash.to_be_bytes())?;
+        w.write_all(&(source.len() as u16).to_be_bytes())?;
+        w.write_all(source)?;
+        w.write_all(&(self.glyphs.len() as u32).to_be_bytes())?;
+        for c in &self.glyphs {
+            write_glyph(w, *c)?;
+        }
+        return Ok(());
+    }
+}
+
+////////////////////////////////////////
+
+pub fn read_art<P: AsRef<Path>>(p: P) -> Result<Frame, String> {
+    return read_art_stamped(p).map(|(f, _)| f);
+}
+
+/// Like `read_art`, with the charset stamped if any.
+pub fn read_art_stamped<P: AsRef<Path>>(p: P) -> Result<(Frame, Option<CharsetStamp>), String> {
+    let mut file = match File::open(p.as_ref()) {
+        Ok(f) => f,
+        Err(e) => Err(format!("Failed to open art: {:?}", e))?,
+    };
+    let mut buf = [0u8; ART_HEADER_LEN];
+    if let Err(e) = file.read_exact(&mut buf) {
+        Err(format!("Failed to read art: {:?}", e))?;
+    }
+    let version = match &buf {
+        h if h == ART_HEADER.as_bytes() => 1,
+        h if h == ART_HEADER_V0.as_bytes() => 0,
+        _ => Err(format!("Failed to parsing art: Invalid header"))?,
+    };
+    let mut r = match util::decompress(file) {
+        Ok(r) => util::Crc32::new(r),
+        Err(e) => Err(format!("Failed to parsing art: {:?}", e))?,
+    };
+    let decode = |r: &mut util::Crc32<_>| match version {
+        1 => read_channels(r).and_then(|c| decode_art_as(r, c)),
+        _ => decode_art_v0(r),
+    };
+    return match decode(&mut r).and_then(|a| check_footer(&mut r, 1).map(|s| (a, s))) {
+        Ok(a) => Ok(a),
+        Err(e) => Err(format!(
+            "Failed to parsing art at byte {} of payload: {}",
+            r.count, e
+        )),
+    };
+}
+
+pub fn write_art<P: AsRef<Path>>(p: P, frame: &Frame, codec: Codec) -> io::Result<()> {
+    return write_art_as(p, frame, codec, Channels::Rgb);
+}
+
+/// Like `write_art`, but colors are stored as `channels`; Without colors, files are a third
+/// to half of the size.
+pub fn write_art_as<P: AsRef<Path>>(
+    p: P,
+    frame: &Frame,
+    codec: Codec,
+    channels: Channels,
+) -> io::Result<()> {
+    return write_art_stamped(p, frame, codec, channels, None);
+}
+
+/// Like `write_art_as`, with the charset stamped if any, see `CharsetStamp`.
+pub fn write_art_stamped<P: AsRef<Path>>(
+    p: P,
+    frame: &Frame,
+    codec: Codec,
+    channels: Channels,
+    stamp: Option<&CharsetStamp>,
+) -> io::Result<()> {
+    let mut file = File::create(p.as_ref())?;
+    file.write_all(ART_HEADER.as_bytes())?;
+    let mut comp = util::Crc32::new(util::compress(file, codec)?);
+    comp.write_all(&[channels as u8])?;
+    encode_art_as(&mut comp, frame, channels)?;
+    write_footer(&mut comp, 1)?;
+    if let Some(stamp) = stamp {
+        stamp.write(&mut comp)?;
+    }
+    comp.inner.finish()?;
+    return Ok(());
+}
+
+fn write_footer<W: Write>(w: &mut util::Crc32<W>, frames: usize) -> io::Result<()> {
+    let crc = w.crc();
+    w.write_all(&(frames as u32).to_be_bytes())?;
+    return w.write_all(&crc.to_be_bytes());
+}
+
+/// Check the footer if any, right after the payload of `frames` was read,
+/// then read the charset stamped after it if any.
+fn check_footer<R: Read>(
+    r: &mut util::Crc32<R>,
+    frames: usize,
+) -> io::Result<Option<CharsetStamp>> {
+    let crc = r.crc();
+    let mut buf = Vec::with_capacity(FOOTER_LEN + STAMP_TAG.len());
+    r.by_ref()
+        .take((FOOTER_LEN + STAMP_TAG.len()) as u64)
+        .read_to_end(&mut buf)?;
+    let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    let stamp = match buf.len() {
+        0 => return Ok(None),
+        FOOTER_LEN => None,
+        _ if buf.get(FOOTER_LEN..) == Some(&STAMP_TAG[..]) => {
+            let stamp = CharsetStamp::read(r)?;
+            match r.read(&mut [0u8])? {
+                0 => Some(stamp),
+                _ => return invalid(format!("Trailing data after the charset")),
+            }
+        }
+        _ => return invalid(format!("Truncated footer or trailing data")),
+    };
+    let n = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+    let crc2 = u32::from_be_bytes(buf[4..].try_into().unwrap());
+    return if n != frames {
+        invalid(format!(
+            "{} frames were read, but {} were written",
+            frames, n
+        ))
+    } else if crc2 != crc {
+        invalid(format!(
+            "Checksum mismatch, {:08x} instead of {:08x}",
+            crc, crc2
+        ))
+    } else {
+        Ok(stamp)
+    };
+}
+
+/// Read `flags/u8` at the beginning of payloads, see `ART_CHANNELS`.
+fn read_channels<R: Read>(r: &mut R) -> io::Result<Channels> {
+    let mut buf = [0u8];
+    r.read_exact(&mut buf)?;
+    return match Channels::from_byte(buf[0]) {
+        Some(c) => Ok(c),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid flags")),
+    };
+}
+
+fn is_anim_header(h: &[u8]) -> bool {
+    return h == ANM_HEADER.as_bytes();
+}
+
+/// Summary of a `.shoal` or `.shoala` file, see `inspect`.
+pub struct Info {
+    /// E.g. `Shoalart.v1 ART`
+    pub header: String,
+    /// `Channels::Rgb` if the file has no flags
+    pub channels: Channels,
+    /// `None` if the file has no codec byte, which means LZ4
+    pub codec: Option<Codec>,
+    /// Of the file, and of the payload after decompression
+    pub sizes: (u64, u64),
+    /// A single frame if `.shoal`
+    pub art: Art,
+    pub charset: Option<CharsetStamp>,
+}
+
+pub fn inspect<P: AsRef<Path>>(p: P) -> Result<Info, String> {
+    let mut buf = [0u8; ANM_HEADER_LEN + 1];
+    let mut file = match File::open(p.as_ref()) {
+        Ok(f) => f,
+        Err(e) => Err(format!("Failed to open art: {:?}", e))?,
+    };
+    if let Err(e) = file.read_exact(&mut buf) {
+        Err(format!("Failed to read header: {:?}", e))?;
+    }
+    let (header, codec) = (&buf[..ANM_HEADER_LEN], buf[ANM_HEADER_LEN]);
+    let (art, charset) = match is_anim_header(header) {
+        true => read_anim_stamped(p.as_ref())?,
+        false => {
+            let (frame, charset) = read_art_stamped(p.as_ref())?;
+            let mut art = Art::default();
+            art.push(frame, Duration::ZERO);
+            (art, charset)
+        }
+    };
+    let flagged = header != ART_HEADER_V0.as_bytes();
+    let sizes = File::open(p.as_ref()).and_then(|mut f| {
+        f.read_exact(&mut [0u8; ANM_HEADER_LEN])?;
+        let size = f.metadata()?.len();
+        let mut r = util::decompress(f)?;
+        let channels = match flagged {
+            true => read_channels(&mut r)?,
+            false => Channels::Rgb,
+        };
+        let payload = flagged as u64 + io::copy(&mut r, &mut io::sink())?;
+        return Ok(((size, payload), channels));
+    });
+    let (sizes, channels) = match sizes {
+        Ok(s) => s,
+        Err(e) => Err(format!("Failed to read art: {:?}", e))?,
+    };
+    return Ok(Info {
+        header: String::from_utf8_lossy(header).into_owned(),
+        channels,
+        codec: util::Codec::from_byte(codec),
+        sizes,
+        art,
+        charset,
+    });
+}
+
+/// Read the `.shoal` or `.shoala` file, then return problems which make it render incorrectly,
+/// e.g. control chars, and lines of different widths in a frame, where ambiguous chars are
+/// `ambiguous`; `Err` if it is corrupt.
+pub fn validate<P: AsRef<Path>>(p: P, ambiguous: Ambiguous) -> Result<Vec<String>, String> {
+    let mut buf = [0u8; ANM_HEADER_LEN];
+    if let Err(e) = File::open(p.as_ref()).and_then(|mut f| f.read_exact(&mut buf)) {
+        Err(format!("Failed to read header: {:?}", e))?;
+    }
+    let anim = is_anim_header(&buf);
+    let frames = match anim {
+        true => read_anim(p)?.frames,
+        false => vec![read_art(p)?],
+    };
+    let mut problems = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let at = |y: usize| match anim {
+            true => format!("Frame {}, line {}", i + 1, y + 1),
+            false => format!("Line {}", y + 1),
+        };
+        let mut widths = frame.lines.iter().map(|l| {
+            l.iter()
+                .map(|c| glyph_width(c.glyph, ambiguous).unwrap_or(0))
+                .sum::<usize>()
+        });
+        let first = widths.next();
+        for (y, w) in widths.enumerate() {
+            if Some(w) != first {
+                problems.push(format!(
+                    "{}: {} columns wide, but the first line is {}",
+                    at(y + 1),
+                    w,
+                    first.unwrap()
+                ));
+            }
+        }
+        for (y, line) in frame.lines.iter().enumerate() {
+            for (x, cell) in line.iter().enumerate() {
+                if cell.glyph.as_str().chars().any(char::is_control) {
+                    problems.push(format!(
+                        "{}, cell {}: Control char {:?}",
+                        at(y),
+                        x + 1,
+                        cell.glyph
+                    ));
+                }
+            }
+        }
+    }
+    return Ok(problems);
+}
+
+/// Files of the dir in the order of frames, i.e. sorted by names.
+pub fn art_dir_paths<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, String> {
+    let mut paths = match std::fs::read_dir(dir.as_ref()) {
+        Ok(d) => d
+            .filter_map(|d| d.ok().map(|d| d.path()))
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>(),
+        Err(e) => Err(format!("Failed to access shoals: {:?}", e))?,
+    };
+    paths.sort_unstable();
+    return Ok(paths);
+}
+
+/// Read all `.shoal` files in the dir, sorted in ascending order.
+pub fn read_art_dir<P: AsRef<Path>>(p: P) -> Result<Art, String> {
+    let paths = art_dir_paths(p.as_ref())?;
+    return Ok(Art {
+        frames: paths.iter().map(read_art).collect::<Result<_, _>>()?,
+        delays: dir_delays(p, paths.len())?,
+    });
+}
+
+/// Sidecar of the dir, holding how long each frame lasts; E.g. `frames.timing` for `frames/`.
+pub fn timing_path<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+    let dir = dir.as_ref();
+    return dir
+        .file_name()
+        .map(|n| dir.with_file_name(format!("{}.{}", n.to_string_lossy(), TIMING_SUFFIX)));
+}
+
+/// One duration per line in ms, fractions allowed; Empty lines and lines starting with `#` are skipped.
+pub fn read_timing<P: AsRef<Path>>(p: P) -> Result<Vec<Duration>, String> {
+    let text = match std::fs::read_to_string(p.as_ref()) {
+        Ok(t) => t,
+        Err(e) => Err(format!("Failed to read timing: {:?}", e))?,
+    };
+    return text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| match l.parse::<f64>() {
+            Ok(ms) if ms >= 0. && ms.is_finite() => Ok(Duration::from_secs_f64(ms / 1000.)),
+            _ => Err(format!("Invalid duration \"{}\" in timing", l)),
+        })
+        .collect();
+}
+
+/// As read by `read_timing`.
+pub fn write_timing<P: AsRef<Path>>(p: P, delays: &[Duration]) -> io::Result<()> {
+    let mut text = String::with_capacity(delays.len() * 8);
+    for d in delays {
+        text.push_str(&format!("{}\n", d.as_secs_f64() * 1000.));
+    }
+    return std::fs::write(p, text);
+}
+
+/// Delays of `len` frames in the dir, from its sidecar if exists; Zero if unspecified.
+pub fn dir_delays<P: AsRef<Path>>(dir: P, len: usize) -> Result<Vec<Duration>, String> {
+    let mut delays = match timing_path(dir) {
+        Some(p) if p.is_file() => read_timing(p)?,
+        _ => Vec::with_capacity(len),
+    };
+    delays.resize(len, Duration::ZERO);
+    return Ok(delays);
+}
+
+/// Either a GIMP palette (`.gpl`), whose lines are `R G B name`, or one `rrggbb` per line
+/// with an optional `#`; Empty lines, headers and comments are skipped.
+pub fn read_palette<P: AsRef<Path>>(p: P) -> Result<Vec<[u8; 3]>, String> {
+    let text = match std::fs::read_to_string(p.as_ref()) {
+        Ok(t) => t,
+        Err(e) => Err(format!("Failed to read palette: {:?}", e))?,
+    };
+    let mut lines = text.lines().map(str::trim).peekable();
+    let gpl = lines.next_if(|l| *l == "GIMP Palette").is_some();
+    let mut palette = Vec::new();
+    for l in lines {
+        if l.is_empty() || (gpl && l.starts_with('#')) || (gpl && l.contains(':')) {
+            continue;
+        }
+        let rgb = match gpl {
+            true => {
+                let mut n = l.split_whitespace().map(|n| n.parse::<u8>().ok());
+                [n.next().flatten(), n.next().flatten(), n.next().flatten()]
+            }
+            false => {
+                let h = l.strip_prefix('#').unwrap_or(l);
+                let n = |i: usize| h.get(i..i + 2).and_then(|n| u8::from_str_radix(n, 16).ok());
+                match h.len() {
+                    6 => [n(0), n(2), n(4)],
+                    _ => [None; 3],
+                }
+            }
+        };
+        match rgb {
+            [Some(r), Some(g), Some(b)] => palette.push([r, g, b]),
+            _ => Err(format!("Invalid color \"{}\" in palette", l))?,
+        }
+    }
+    if palette.is_empty() {
+        Err(format!("Empty palette"))?;
+    }
+    return Ok(palette);
+}
+
+/// One `{chars}={multiplier}` per line, e.g. `@#%=0.8` favors the three glyphs,
+/// and `==1.2` avoids `=`; Empty lines are skipped.
+pub fn read_bias<P: AsRef<Path>>(p: P) -> Result<AHashMap<Glyph, f32>, String> {
+    let text = match std::fs::read_to_string(p.as_ref()) {
+        Ok(t) => t,
+        Err(e) => Err(format!("Failed to read bias: {:?}", e))?,
+    };
+    let mut bias = AHashMap::new();
+    for l in text.lines().filter(|l| !l.trim().is_empty()) {
+        let (cs, n) = match l.rsplit_once('=') {
+            Some((cs, n)) if !cs.is_empty() => (cs, n.trim().parse::<f32>()),
+            _ => Err(format!("Invalid line \"{}\" in bias", l))?,
+        };
+        match n {
+            Ok(n) if n >= 0. && n.is_finite() => bias.extend(cluster::split(cs).map(|c| (c, n))),
+            _ => Err(format!("Invalid multiplier \"{}\" in bias", l))?,
+        }
+    }
+    return Ok(bias);
+}
+
+/// Snap colors of cells, including backgrounds, to the nearest ones in the palette.
+pub fn snap_colors(frame: &mut Frame, palette: &[[u8; 3]]) {
+    for cell in frame.lines.iter_mut().flatten() {
+        cell.color = palette[nearest(palette, cell.color)];
+        cell.bg = cell.bg.map(|bg| palette[nearest(palette, bg)]);
+    }
+}
+
+/// Either a `.shoala` animation, a `.shoal` file, or a dir of `.shoal` files.
+pub fn open_art<P: AsRef<Path>>(p: P) -> Result<Art, String> {
+    let p = p.as_ref();
+    return if p.is_dir() {
+        read_art_dir(p)
+    } else if is_anim(p) {
+        read_anim(p)
+    } else {
+        read_art(p).map(|f| Art {
+            frames: vec![f],
+            delays: vec![Duration::ZERO],
+        })
+    };
+}
+
+pub fn is_anim<P: AsRef<Path>>(p: P) -> bool {
+    return p.as_ref().extension().map_or(false, |e| e == ANM_SUFFIX);
+}
+
+pub fn read_anim<P: AsRef<Path>>(p: P) -> Result<Art, String> {
+    return read_anim_stamped(p).map(|(a, _)| a);
+}
+
+/// Like `read_anim`, with the charset stamped if any.
+pub fn read_anim_stamped<P: AsRef<Path>>(p: P) -> Result<(Art, Option<CharsetStamp>), String> {
+    let mut file = match File::open(p.as_ref()) {
+        Ok(f) => f,
+        Err(e) => Err(format!("Failed to open animation: {:?}", e))?,
+    };
+    let mut buf = [0u8; ANM_HEADER_LEN];
+    if let Err(e) = file.read_exact(&mut buf) {
+        Err(format!("Failed to read animation: {:?}", e))?;
+    }
+    if &buf != ANM_HEADER.as_bytes() {
+        Err(format!("Failed to parsing animation: Invalid header"))?;
+    }
+    let mut r = match util::decompress(file) {
+        Ok(r) => util::Crc32::new(r),
+        Err(e) => Err(format!("Failed to parsing animation: {:?}", e))?,
+    };
+    let decode = |r: &mut util::Crc32<_>| read_channels(r).and_then(|c| decode_anim_as(r, c));
+    return match decode(&mut r).and_then(|a| check_footer(&mut r, a.frames.len()).map(|s| (a, s))) {
+        Ok(a) => Ok(a),
+        Err(e) => Err(format!(
+            "Failed to parsing animation at byte {} of payload: {}",
+            r.count, e
+        )),
+    };
+}
+
+pub fn write_anim<P: AsRef<Path>>(p: P, art: &Art, codec: Codec) -> io::Result<()> {
+    return write_anim_as(p, art, codec, Channels::Rgb);
+}
+
+/// Like `write_anim`, but colors are stored as `channels`.
+pub fn write_anim_as<P: AsRef<Path>>(
+    p: P,
+    art: &Art,
+    codec: Codec,
+    channels: Channels,
+) -> io::Result<()> {
+    return write_anim_stamped(p, art, codec, channels, None);
+}
+
+/// Like `write_anim_as`, with the charset stamped if any, see `CharsetStamp`.
+pub fn write_anim_stamped<P: AsRef<Path>>(
+    p: P,
+    art: &Art,
+    codec: Codec,
+    channels: Channels,
+    stamp: Option<&CharsetStamp>,
+) -> io::Result<()> {
+    let mut file = File::create(p.as_ref())?;
+    file.write_all(ANM_HEADER.as_bytes())?;
+    let mut comp = util::Crc32::new(util::compress(file, codec)?);
+    comp.write_all(&[channels as u8])?;
+    encode_anim_as(&mut comp, art, channels)?;
+    write_footer(&mut comp, art.frames.len())?;
+    if let Some(stamp) = stamp {
+        stamp.write(&mut comp)?;
+    }
+    comp.inner.finish()?;
+    return Ok(());
+}
+
+/// Uncompressed payload after flags, i.e. without header:
+/// `u16` columns, `u16` rows, `u32` frames; Each frame `u32` delay in ms and `u8` kind, then
+///
+/// - Keyframe (`0`): as `.shoal`.
+/// - Delta (`1`): `u32` changes since the previous frame; Each change `u16` line, `u16` index,
+///   then as a cell in `.shoal`, whose repeats are the following cells in the line.
+///
+/// If `Channels::Indexed`, each frame is preceded by a palette of its keyframe or changes.
+pub fn decode_anim_as<R: Read>(mut r: R, channels: Channels) -> io::Result<Art> {
+    return match channels {
+        Channels::Rgb => decode_frames(&mut r, read_cell),
+        Channels::Gray => decode_frames(&mut r, read_cell_gray),
+        Channels::Indexed => decode_frames_with(&mut r, true, read_cell),
+        Channels::None => decode_frames(&mut r, read_cell_mono),
+    };
+}
+
+/// Like `decode_anim_as`, whose colors are `rgb/u8*3`.
+pub fn decode_anim<R: Read>(r: R) -> io::Result<Art> {
+    return decode_anim_as(r, Channels::Rgb);
+}
+
+fn decode_frames<R: Read>(
+    r: &mut R,
+    read_cell: fn(&mut R) -> io::Result<(Cell, usize)>,
+) -> io::Result<Art> {
+    return decode_frames_with(r, false, read_cell);
+}
+
+/// If `indexed`, each frame is preceded by a palette, and `read_cell` is ignored.
+fn decode_frames_with<R: Read>(
+    r: &mut R,
+    indexed: bool,
+    read_cell: fn(&mut R) -> io::Result<(Cell, usize)>,
+) -> io::Result<Art> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?; // Dimensions are informative only.
+    let n = u32::from_be_bytes(buf[4..8].try_into().unwrap()) as usize;
+    let mut art = Art {
+        frames: Vec::with_capacity(n.min(4096)),
+        delays: Vec::with_capacity(n.min(4096)),
+    };
+    for i in 0..n {
+        let at = |e: io::Error| io::Error::new(e.kind(), format!("Frame {}: {}", i + 1, e));
+        r.read_exact(&mut buf[..5]).map_err(at)?;
+        let delay = Duration::from_millis(u32::from_be_bytes(buf[..4].try_into().unwrap()) as u64);
+        let palette = match indexed {
+            true => Some(Palette::read(r).map_err(at)?),
+            false => None,
+        };
+        let mut read_cell = |r: &mut R| match &palette {
+            Some(palette) => palette.read_cell(r),
+            None => read_cell(r),
+        };
+        let frame = match (buf[4], art.frames.last()) {
+            (0, _) => decode_lines(r, &mut read_cell).map_err(at)?,
+            (1, Some(prev)) => {
+                r.read_exact(&mut buf[..4]).map_err(at)?;
+                let m = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+                let mut changes = Vec::with_capacity(m.min(1 << 16));
+                for _ in 0..m {
+                    r.read_exact(&mut buf[..4]).map_err(at)?;
+                    let y = u16::from_be_bytes(buf[..2].try_into().unwrap()) as usize;
+                    let x = u16::from_be_bytes(buf[2..4].try_into().unwrap()) as usize;
+                    let (cell, n) = read_cell(r).map_err(at)?;
+                    changes.extend((x..x + n).map(|x| (y, x, cell)));
+                }
+                let mut frame = prev.clone();
+                frame.patch(&changes);
+                frame
+            }
+            _ => Err(at(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid frame kind",
+            )))?,
+        };
+        art.push(frame, delay);
+    }
+    return Ok(art);
+}
+
+/// Uncompressed payload after flags, i.e. without header.
+pub fn encode_anim_as<W: Write>(w: &mut W, art: &Art, channels: Channels) -> io::Result<()> {
+    return match channels {
+        Channels::Rgb => encode_frames(w, art, write_cell),
+        Channels::Gray => encode_frames(w, art, write_cell_gray),
+        Channels::Indexed => encode_frames_with(w, art, true, write_cell),
+        Channels::None => encode_frames(w, art, write_cell_mono),
+    };
+}
+
+/// Like `encode_anim_as`, whose colors are `rgb/u8*3`.
+pub fn encode_anim<W: Write>(w: &mut W, art: &Art) -> io::Result<()> {
+    return encode_anim_as(w, art, Channels::Rgb);
+}
+
+fn encode_frames<W: Write>(
+    w: &mut W,
+    art: &Art,
+    write_cell: fn(&mut W, &Cell, usize) -> io::Result<()>,
+) -> io::Result<()> {
+    return encode_frames_with(w, art, false, write_cell);
+}
+
+/// If `indexed`, each frame is preceded by a palette, and `write_cell` is ignored.
+fn encode_frames_with<W: Write>(
+    w: &mut W,
+    art: &Art,
+    indexed: bool,
+    write_cell: fn(&mut W, &Cell, usize) -> io::Result<()>,
+) -> io::Result<()> {
+    let (cols, rows) = art.dimensions();
+    w.write_all(&(cols as u16).to_be_bytes())?;
+    w.write_all(&(rows as u16).to_be_bytes())?;
+    w.write_all(&(art.frames.len() as u32).to_be_bytes())?;
+    let mut last_key = 0;
+    for (i, (frame, delay)) in art.frames.iter().zip(&art.delays).enumerate() {
+        w.write_all(&(delay.as_millis() as u32).to_be_bytes())?;
+        let cells = frame.lines.iter().map(|l| l.len()).sum::<usize>();
+        let changes = match i {
+            0 => None,
+            _ if i - last_key >= ANM_KEYFRAME_INTERVAL => None,
+            _ => frame
+                .diff(&art.frames[i - 1])
+                .filter(|c| c.len() * 12 < cells * 8),
+        };
+        w.write_all(&[changes.is_some() as u8])?;
+        let palette = match (indexed, &changes) {
+            (false, _) => None,
+            (true, None) => Some(Palette::of(frame.lines.iter().flatten())),
+            (true, Some(changes)) => Some(Palette::of(changes.iter().map(|(_, _, c)| c))),
+        };
+        if let Some(palette) = &palette {
+            palette.write(w)?;
+        }
+        let mut write_cell = |w: &mut W, cell: &Cell, n: usize| match &palette {
+            Some(palette) => palette.write_cell(w, cell, n),
+            None => write_cell(w, cell, n),
+        };
+        match changes {
+            None => {
+                last_key = i;
+                encode_lines(w, frame, &mut write_cell)?;
+            }
+            Some(changes) => {
+                // Consecutive identical changes in a line as runs.
+                let mut runs = Vec::<(usize, usize, Cell, usize)>::with_capacity(changes.len());
+                for (y, x, cell) in changes {
+                    match runs.last_mut() {
+                        Some((ry, rx, rc, n)) if (*ry, *rx + *n) == (y, x) && *rc == cell => {
+                            *n += 1
+                        }
+                        _ => runs.push((y, x, cell, 1)),
+                    }
+                }
+                w.write_all(&(runs.len() as u32).to_be_bytes())?;
+                for (y, x, cell, n) in runs {
+                    w.write_all(&(y as u16).to_be_bytes())?;
+                    w.write_all(&(x as u16).to_be_bytes())?;
+                    write_cell(w, &cell, n)?;
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Uncompressed payload after flags, i.e. without header:
+/// `u16` lines; Each line `u16` cells; Each cell `u8` flags, `u16` repeats if flagged `CELL_RUN`,
+/// `rgb/u8*3` foreground, `rgb/u8*3` background if flagged `CELL_BG`, then `char/u32`;
+/// Other flags are `CELL_ATTRS`.
+///
+/// Colors are `luma/u8` if `Channels::Gray`, read as gray; Left out if `Channels::None`,
+/// see `MONO_COLOR`; Indices into a `Palette` preceding the lines if `Channels::Indexed`.
+pub fn decode_art_as<R: Read>(mut r: R, channels: Channels) -> io::Result<Frame> {
+    return match channels {
+        Channels::Rgb => decode_lines(&mut r, read_cell),
+        Channels::Gray => decode_lines(&mut r, read_cell_gray),
+        Channels::Indexed => {
+            let palette = Palette::read(&mut r)?;
+            decode_lines(&mut r, |r| palette.read_cell(r))
+        }
+        Channels::None => decode_lines(&mut r, read_cell_mono),
+    };
+}
+
+/// Like `decode_art_as`, whose colors are `rgb/u8*3`, which is also what plugins get.
+pub fn decode_art<R: Read>(r: R) -> io::Result<Frame> {
+    return decode_art_as(r, Channels::Rgb);
+}
+
+/// Payload of `Shoalart.v0 ART`, whose cells are `rgb/u8*3` and `char/u32` only.
+pub fn decode_art_v0<R: Read>(mut r: R) -> io::Result<Frame> {
+    return decode_lines(&mut r, read_cell_v0);
+}
+
+fn decode_lines<R: Read, F: FnMut(&mut R) -> io::Result<(Cell, usize)>>(
+    r: &mut R,
+    mut read_cell: F,
+) -> io::Result<Frame> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf)?;
+    let h = u16::from_be_bytes(buf) as usize;
+    let mut lines = Vec::<Vec<Cell>>::with_capacity(h);
+    for _ in 0..h {
+        r.read_exact(&mut buf)?;
+        let w = u16::from_be_bytes(buf) as usize;
+        let mut line = Vec::<Cell>::with_capacity(w);
+        while line.len() < w {
+            let at = |e: io::Error| {
+                let place = format!("Line {}, cell {}", lines.len() + 1, line.len() + 1);
+                io::Error::new(e.kind(), format!("{}: {}", place, e))
+            };
+            let (cell, n) = read_cell(r).map_err(at)?;
+            if line.len() + n > w {
+                Err(at(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid run",
+                )))?;
+            }
+            line.extend(std::iter::repeat(cell).take(n));
+        }
+        lines.push(line);
+    }
+    return Ok(Frame { lines });
+}
+
+/// Flags, and repeats if flagged `CELL_RUN`.
+fn read_flags<R: Read>(r: &mut R) -> io::Result<(u8, usize)> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf[..1])?;
+    let flags = buf[0];
+    let n = match flags & CELL_RUN {
+        0 => 1,
+        _ => {
+            r.read_exact(&mut buf)?;
+            u16::from_be_bytes(buf) as usize
+        }
+    };
+    return Ok((flags, n));
+}
+
+/// Flags of `cell` with `extra` ones, `CELL_RUN` and repeats if `n > 1`.
+fn write_flags<W: Write>(w: &mut W, cell: &Cell, extra: u8, n: usize) -> io::Result<()> {
+    let flags = cell.attrs & CELL_ATTRS | extra;
+    return match n {
+        1 => w.write_all(&[flags]),
+        _ => {
+            w.write_all(&[flags | CELL_RUN])?;
+            w.write_all(&(n as u16).to_be_bytes())
+        }
+    };
+}
+
+fn read_cell_v0<R: Read>(r: &mut R) -> io::Result<(Cell, usize)> {
+    let mut color = [0u8; 3];
+    r.read_exact(&mut color)?;
+    let glyph = read_glyph(r)?;
+    let cell = Cell {
+        color,
+        bg: None,
+        glyph,
+        attrs: 0,
+    };
+    return Ok((cell, 1));
+}
+
+fn read_cell_mono<R: Read>(r: &mut R) -> io::Result<(Cell, usize)> {
+    let (flags, n) = read_flags(r)?;
+    let glyph = read_glyph(r)?;
+    let cell = Cell {
+        color: MONO_COLOR,
+        bg: None,
+        glyph,
+        attrs: flags & CELL_ATTRS,
+    };
+    return Ok((cell, n));
+}
+
+fn read_cell_gray<R: Read>(r: &mut R) -> io::Result<(Cell, usize)> {
+    let (flags, n) = read_flags(r)?;
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    let color = [buf[0]; 3];
+    let bg = match flags & CELL_BG {
+        0 => None,
+        _ => {
+            r.read_exact(&mut buf)?;
+            Some([buf[0]; 3])
+        }
+    };
+    let glyph = read_glyph(r)?;
+    let cell = Cell {
+        color,
+        bg,
+        glyph,
+        attrs: flags & CELL_ATTRS,
+    };
+    return Ok((cell, n));
+}
+
+fn read_cell<R: Read>(r: &mut R) -> io::Result<(Cell, usize)> {
+    let (flags, n) = read_flags(r)?;
+    let mut color = [0u8; 3];
+    r.read_exact(&mut color)?;
+    let bg = match flags & CELL_BG {
+        0 => None,
+        _ => {
+            let mut bg = [0u8; 3];
+            r.read_exact(&mut bg)?;
+            Some(bg)
+        }
+    };
+    let glyph = read_glyph(r)?;
+    let attrs = flags & CELL_ATTRS;
+    let cell = Cell {
+        color,
+        bg,
+        glyph,
+        attrs,
+    };
+    return Ok((cell, n));
+}
+
+fn write_cell<W: Write>(w: &mut W, cell: &Cell, n: usize) -> io::Result<()> {
+    let Cell {
+        color, bg, glyph, ..
+    } = cell;
+    match bg {
+        Some(bg) => {
+            write_flags(w, cell, CELL_BG, n)?;
+            w.write_all(color)?;
+            w.write_all(bg)?;
+        }
+        None => {
+            write_flags(w, cell, 0, n)?;
+            w.write_all(color)?;
+        }
+    }
+    write_glyph(w, *glyph)?;
+    return Ok(());
+}
+
+fn write_cell_mono<W: Write>(w: &mut W, cell: &Cell, n: usize) -> io::Result<()> {
+    write_flags(w, cell, 0, n)?;
+    write_glyph(w, cell.glyph)?;
+    return Ok(());
+}
+
+/// Rec. 709 luma, as `image` does.
+fn luma([r, g, b]: [u8; 3]) -> u8 {
+    return ((r as u32 * 2126 + g as u32 * 7152 + b as u32 * 722 + 5000) / 10000) as u8;
+}
+
+fn write_cell_gray<W: Write>(w: &mut W, cell: &Cell, n: usize) -> io::Result<()> {
+    match cell.bg {
+        Some(bg) => {
+            write_flags(w, cell, CELL_BG, n)?;
+            w.write_all(&[luma(cell.color), luma(bg)])?;
+        }
+        None => {
+            write_flags(w, cell, 0, n)?;
+            w.write_all(&[luma(cell.color)])?;
+        }
+    }
+    write_glyph(w, cell.glyph)?;
+    return Ok(());
+}
+
+/// Uncompressed payload after flags, i.e. without header.
+pub fn encode_art_as<W: Write>(w: &mut W, frame: &Frame, channels: Channels) -> io::Result<()> {
+    return match channels {
+        Channels::Rgb => encode_lines(w, frame, write_cell),
+        Channels::Gray => encode_lines(w, frame, write_cell_gray),
+        Channels::Indexed => {
+            let palette = Palette::of(frame.lines.iter().flatten());
+            palette.write(w)?;
+            encode_lines(w, frame, |w, c, n| palette.write_cell(w, c, n))
+        }
+        Channels::None => encode_lines(w, frame, write_cell_mono),
+    };
+}
+
+/// Like `encode_art_as`, whose colors are `rgb/u8*3`, which is also what plugins get.
+pub fn encode_art<W: Write>(w: &mut W, frame: &Frame) -> io::Result<()> {
+    return encode_art_as(w, frame, Channels::Rgb);
+}
+
+fn encode_lines<W: Write, F: FnMut(&mut W, &Cell, usize) -> io::Result<()>>(
+    w: &mut W,
+    frame: &Frame,
+    mut write_cell: F,
+) -> io::Result<()> {
+    w.write_all(&(frame.lines.len() as u16).to_be_bytes())?;
+    for line in &frame.lines {
+        w.write_all(&(line.len() as u16).to_be_bytes())?;
+        for (cell, n) in runs(line) {
+            write_cell(w, cell, n)?;
+        }
+    }
+    return Ok(());
+}
+
+/// Distinct colors of a frame, in order of appearance:
+/// `u32` colors, then `rgb/u8*3` each; Indices are `u8` if there are at most 256 colors,
+/// `u16` if at most 65536, `u24` otherwise.
+struct Palette {
+    colors: Vec<[u8; 3]>,
+    /// Empty if read
+    indices: AHashMap<[u8; 3], u32>,
+}
+
+impl Palette {
+    fn of<'a, I: Iterator<Item = &'a Cell>>(cells: I) -> Self {
+        let mut palette = Palette {
+            colors: Vec::new(),
+            indices: AHashMap::new(),
+        };
+        for cell in cells {
+            for c in std::iter::once(cell.color).chain(cell.bg) {
+                if !palette.indices.contains_key(&c) {
+                    palette.indices.insert(c, palette.colors.len() as u32);
+                    palette.colors.push(c);
+                }
+            }
+        }
+        return palette;
+    }
+
+    fn width(&self) -> usize {
+        return match self.colors.len() {
+            0..=0x100 => 1,
+            0x101..=0x10000 => 2,
+            _ => 3,
+        };
+    }
+
+    fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; 4];
+        r.read_exact(&mut buf)?;
+        let n = u32::from_be_bytes(buf) as usize;
+        if n > 1 << 24 {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid palette",
+            ))?;
+        }
+        let mut colors = Vec::with_capacity(n);
+        for _ in 0..n {
+            r.read_exact(&mut buf[..3])?;
+            colors.push(buf[..3].try_into().unwrap());
+        }
+        return Ok(Palette {
+            colors,
+            indices: AHashMap::new(),
+        });
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&(self.colors.len() as u32).to_be_bytes())?;
+        for c in &self.colors {
+            w.write_all(c)?;
+        }
+        return Ok(());
+    }
+
+    fn read_color<R: Read>(&self, r: &mut R) -> io::Result<[u8; 3]> {
+        let mut buf = [0u8; 4];
+        r.read_exact(&mut buf[4 - self.width()..])?;
+        return match self.colors.get(u32::from_be_bytes(buf) as usize) {
+            Some(c) => Ok(*c),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid color index",
+            )),
+        };
+    }
+
+    fn write_color<W: Write>(&self, w: &mut W, c: [u8; 3]) -> io::Result<()> {
+        return w.write_all(&self.indices[&c].to_be_bytes()[4 - self.width()..]);
+    }
+
+    fn read_cell<R: Read>(&self, r: &mut R) -> io::Result<(Cell, usize)> {
+        let (flags, n) = read_flags(r)?;
+        let color = self.read_color(r)?;
+        let bg = match flags & CELL_BG {
+            0 => None,
+            _ => Some(self.read_color(r)?),
+        };
+        let glyph = read_glyph(r)?;
+        let cell = Cell {
+            color,
+            bg,
+            glyph,
+            attrs: flags & CELL_ATTRS,
+        };
+        return Ok((cell, n));
+    }
+
+    fn write_cell<W: Write>(&self, w: &mut W, cell: &Cell, n: usize) -> io::Result<()> {
+        write_flags(w, cell, cell.bg.map_or(0, |_| CELL_BG), n)?;
+        self.write_color(w, cell.color)?;
+        if let Some(bg) = cell.bg {
+            self.write_color(w, bg)?;
+        }
+        write_glyph(w, cell.glyph)?;
+        return Ok(());
+    }
+}
+
+/// Runs of identical consecutive cells in a line, `(cell, count)`.
+fn runs(line: &[Cell]) -> impl Iterator<Item = (&Cell, usize)> {
+    let mut i = 0;
+    return std::iter::from_fn(move || {
+        let cell = line.get(i)?;
+        let n = line[i..].iter().take_while(|c| *c == cell).count();
+        i += n;
+        return Some((cell, n));
+    });
+}
+
+/// Print the glyph of a run of cells at once.
+fn queue_run<W: Write>(out: &mut W, cell: &Cell, n: usize) -> io::Result<()> {
+    return match n {
+        1 => queue!(out, Print(cell.glyph)),
+        _ => queue!(out, Print(cell.glyph.as_str().repeat(n))),
+    };
+}
+
+/// Queue escapes if the colors of `cell` differ from `cc`, the current `(foreground, background)`.
+fn queue_colors<W: Write>(
+    out: &mut W,
+    cell: &Cell,
+    cc: &mut (Option<[u8; 3]>, Option<Option<[u8; 3]>>),
+    colors: Colors,
+) -> io::Result<()> {
+    if cc.0 != Some(cell.color) {
+        cc.0 = Some(cell.color);
+        queue!(out, SetForegroundColor(colors.quantize(cell.color)))?;
+    }
+    if cc.1 != Some(cell.bg) {
+        cc.1 = Some(cell.bg);
+        let bg = match cell.bg {
+            Some(bg) => colors.quantize(bg),
+            None => Color::Reset,
+        };
+        queue!(out, SetBackgroundColor(bg))?;
+    }
+    return Ok(());
+}
+
+/// Queue escapes if the attributes of `cell` differ from `ca`, the current ones.
+fn queue_attrs<W: Write>(out: &mut W, cell: &Cell, ca: &mut u8) -> io::Result<()> {
+    let (old, new) = (*ca, cell.attrs);
+    if old == new {
+        return Ok(());
+    }
+    *ca = new;
+    let weight = CELL_BOLD | CELL_DIM;
+    if old & weight != new & weight {
+        if old & weight != 0 {
+            queue!(out, SetAttribute(Attribute::NormalIntensity))?;
+        }
+        if new & CELL_BOLD != 0 {
+            queue!(out, SetAttribute(Attribute::Bold))?;
+        }
+        if new & CELL_DIM != 0 {
+            queue!(out, SetAttribute(Attribute::Dim))?;
+        }
+    }
+    match (old & CELL_ITALIC, new & CELL_ITALIC) {
+        (0, 0) | (CELL_ITALIC, CELL_ITALIC) => (),
+        (0, _) => queue!(out, SetAttribute(Attribute::Italic))?,
+        _ => queue!(out, SetAttribute(Attribute::NoItalic))?,
+    }
+    return Ok(());
+}
+
+/// No color if `colors` is `None`; Attributes are drawn either way.
+pub fn play_art<W: Write>(
+    out: &mut W,
+    frame: &Frame,
+    sx: u16,
+    sy: u16,
+    colors: Option<Colors>,
+) -> io::Result<()> {
+    // queue!(out, Clear(ClearType::All))?;
+    let (mut cc, mut ca) = ((None, None), 0);
+    for (y, line) in frame.lines.iter().enumerate() {
+        queue!(out, MoveTo(sx, sy + y as u16))?;
+        for (cell, n) in runs(line) {
+            if let Some(colors) = colors {
+                queue_colors(out, cell, &mut cc, colors)?;
+            }
+            queue_attrs(out, cell, &mut ca)?;
+            queue_run(out, cell, n)?;
+        }
+    }
+    if let Some(Some(_)) = cc.1 {
+        queue!(out, SetBackgroundColor(Color::Reset))?;
+    }
+    if ca != 0 {
+        queue!(out, SetAttribute(Attribute::NormalIntensity))?;
+        queue!(out, SetAttribute(Attribute::NoItalic))?;
+    }
+    return Ok(());
+}
+
+/// Like `play_art`, but column by column from right to left, each from top to bottom,
+/// as vertical CJK text is read; Columns are two half-width units wide.
+pub fn play_vertical<W: Write>(
+    out: &mut W,
+    frame: &Frame,
+    sx: u16,
+    sy: u16,
+    colors: Option<Colors>,
+    ambiguous: Ambiguous,
+) -> io::Result<()> {
+    // `(unit, line, cell)`
+    let mut cells = Vec::new();
+    for (y, line) in frame.lines.iter().enumerate() {
+        let mut u = 0;
+        for cell in line {
+            cells.push((u, y, cell));
+            u += glyph_width(cell.glyph, ambiguous).unwrap_or(1);
+        }
+    }
+    cells.sort_by_key(|&(u, y, _)| (Reverse(u / 2), y, u));
+    let (mut cc, mut ca) = ((None, None), 0);
+    for (u, y, cell) in cells {
+        queue!(out, MoveTo(sx + u as u16, sy + y as u16))?;
+        if let Some(colors) = colors {
+            queue_colors(out, cell, &mut cc, colors)?;
+        }
+        queue_attrs(out, cell, &mut ca)?;
+        queue_run(out, cell, 1)?;
+    }
+    if let Some(Some(_)) = cc.1 {
+        queue!(out, SetBackgroundColor(Color::Reset))?;
+    }
+    if ca != 0 {
+        queue!(out, SetAttribute(Attribute::NormalIntensity))?;
+        queue!(out, SetAttribute(Attribute::NoItalic))?;
+    }
+    return Ok(());
+}
+
+/// Like `play_art`, but at the cursor, each line ending with a newline; Colors and attributes
+/// are reset at the end.
+pub fn print_art<W: Write>(out: &mut W, frame: &Frame, colors: Option<Colors>) -> io::Result<()> {
+    let (mut cc, mut ca) = ((None, None), 0);
+    for line in &frame.lines {
+        for (cell, n) in runs(line) {
+            if let Some(colors) = colors {
+                queue_colors(out, cell, &mut cc, colors)?;
+            }
+            queue_attrs(out, cell, &mut ca)?;
+            queue_run(out, cell, n)?;
+        }
+        if let Some(Some(_)) = cc.1 {
+            cc.1 = Some(None);
+            queue!(out, SetBackgroundColor(Color::Reset))?;
+        }
+        queue!(out, Print('\n'))?;
+    }
+    return queue!(out, SetAttribute(Attribute::Reset));
+}
+
+/// Like `play_art`, but only redraw what changed since `prev`, which should be on screen;
+/// Ambiguous chars take `ambiguous` columns there.
+pub fn play_diff<W: Write>(
+    out: &mut W,
+    prev: &Frame,
+    frame: &Frame,
+    sx: u16,
+    sy: u16,
+    colors: Option<Colors>,
+    ambiguous: Ambiguous,
+) -> io::Result<()> {
+    let changes = match frame.diff(prev) {
+        Some(c) => c,
+        None => return play_art(out, frame, sx, sy, colors),
+    };
+    let width = |l: &[Cell]| {
+        l.iter()
+            .map(|c| glyph_width(c.glyph, ambiguous).unwrap_or(1))
+            .sum::<usize>()
+    };
+    let (mut cc, mut ca) = ((None, None), 0);
+    let mut changes = changes.into_iter().peekable();
+    for (y, line) in frame.lines.iter().enumerate() {
+        let mut col = 0;
+        let mut tail = false; // A different width shifts the rest of line.
+        for (x, cell) in line.iter().enumerate() {
+            let glyph = &cell.glyph;
+            let changed = changes.next_if(|&(cy, cx, _)| (cy, cx) == (y, x)).is_some();
+            if changed && !tail {
+                tail = glyph_width(*glyph, ambiguous)
+                    != glyph_width(prev.lines[y][x].glyph, ambiguous);
+                queue!(out, MoveTo(sx + col as u16, sy + y as u16))?;
+            }
+            if changed || tail {
+                if let Some(colors) = colors {
+                    queue_colors(out, cell, &mut cc, colors)?;
+                }
+                queue_attrs(out, cell, &mut ca)?;
+                queue!(out, Print(*glyph))?;
+            }
+            col += glyph_width(*glyph, ambiguous).unwrap_or(1);
+        }
+        let old = width(&prev.lines[y]);
+        if tail && old > col {
+            if let Some(Some(_)) = cc.1 {
+                cc.1 = Some(None);
+                queue!(out, SetBackgroundColor(Color::Reset))?;
+            }
+            queue!(out, Print(" ".repeat(old - col)))?;
+        }
+    }
+    if let Some(Some(_)) = cc.1 {
+        queue!(out, SetBackgroundColor(Color::Reset))?;
+    }
+    if ca != 0 {
+        queue!(out, SetAttribute(Attribute::NormalIntensity))?;
+        queue!(out, SetAttribute(Attribute::NoItalic))?;
+    }
+    return Ok(());
+}
+
+/// Match each `8x8` (or `4x8`) block of the draft against the charset.
+///
+/// Each row is split into half and full width glyphs so that the total score is the lowest,
+/// or greedily from left to right when dithering, as errors depend on earlier choices.
+///
+/// The color image should be the same size as the draft.
+pub fn make_art(
+    draft: &GrayImage,
+    color: &RgbImage,
+    cs: &Charset,
+    matching: Matching,
+    sampling: Sampling,
+    plugins: &mut Plugins,
+) -> Result<Frame, String> {
+    let (w, h) = draft.dimensions();
+    let (cw, ch) = sampling.cell;
+    let mut matcher = Matcher {
+        draft,
+        cs,
+        matching,
+        cell: sampling.cell,
+        rescore: plugins.has_score(),
+        plugins,
+        flats: AHashMap::new(),
+        decided: AHashMap::new(),
+    };
+    if matching.vertical {
+        let lines = make_columns(&mut matcher, color, sampling)?;
+        return postprocess(Frame { lines }, plugins);
+    }
+    let mut lines = Vec::<Vec<Cell>>::with_capacity((h / ch) as usize);
+    // Half-width units of each row.
+    let n = (w / cw) as usize;
+    // Brightness errors to be diffused, per unit of this row and the next,
+    // with a margin on both sides.
+    let (mut errs, mut next) = (vec![0f32; n + 2], vec![0f32; n + 2]);
+    // Glyph of the previous frame starting at each unit.
+    let mut starts = vec![None; n];
+    for y in (0..h - h % ch).step_by(ch as usize) {
+        starts.iter_mut().for_each(|g| *g = None);
+        if let Some(line) = matching.prev.and_then(|p| p.lines.get((y / ch) as usize)) {
+            let mut u = 0;
+            for cell in line {
+                if u >= n {
+                    break;
+                }
+                starts[u] = Some(cell.glyph);
+                u += glyph_width(cell.glyph, sampling.ambiguous).unwrap_or(1);
+            }
+        }
+        let kept = |u: usize| starts[u].filter(|_| matching.stabilize > 0.);
+        let x = |u: usize| u as u32 * cw;
+        // `(unit, wide, choice)`
+        let mut row = Vec::<(usize, bool, Choice)>::with_capacity(n);
+        if matching.dither {
+            let mut u = 0;
+            while u < n {
+                let half = matcher.choose(x(u), y, false, errs[u + 1], kept(u))?;
+                let full = match u + 1 < n {
+                    true => {
+                        let err = (errs[u + 1] + errs[u + 2]) / 2.;
+                        matcher.choose(x(u), y, true, err, kept(u))?
+                    }
+                    false => None,
+                };
+                let (wide, c) = match (half, full) {
+                    (Some(half), Some(full)) if full.score <= half.score => (true, full),
+                    (Some(half), _) => (false, half),
+                    (None, Some(full)) => (true, full),
+                    (None, None) => Err(format!("Empty charset"))?,
+                };
+                // Floyd-Steinberg, `v` is the unit with the margin.
+                let (e, k, v) = (c.dc - c.mean, if wide { 2 } else { 1 }, u + 1);
+                errs[v + k] += e * 7. / 16.;
+                next[v - 1] += e * 3. / 16.;
+                next[v..v + k]
+                    .iter_mut()
+                    .for_each(|d| *d += e * 5. / 16. / k as f32);
+                next[v + k] += e * 1. / 16.;
+                row.push((u, wide, c));
+                u += k;
+            }
+            std::mem::swap(&mut errs, &mut next);
+            next.iter_mut().for_each(|d| *d = 0.);
+        } else {
+            let mut halves = Vec::with_capacity(n);
+            let mut fulls = Vec::with_capacity(n);
+            for u in 0..n {
+                halves.push(matcher.choose(x(u), y, false, 0., kept(u))?);
+                fulls.push(match u + 1 < n {
+                    true => matcher.choose(x(u), y, true, 0., kept(u))?,
+                    false => None,
+                });
+            }
+            row = match layout(&halves, &fulls) {
+                Some(row) => row,
+                None => Err(format!("Empty charset"))?,
+            };
+        }
+        let mut line = Vec::with_capacity(row.len());
+        for (u, wide, c) in row {
+            push_choice(&mut line, &c, (x(u), y, wide), color, matching, sampling);
+        }
+        lines.push(line);
+    }
+    return postprocess(Frame { lines }, plugins);
+}
+
+/// Half and full width choices of a row, `(unit, wide, choice)`, with the lowest total score;
+/// `fulls[u]` spans units `u` and `u + 1`. `None` if the row cannot be covered.
+fn layout(
+    halves: &[Option<Choice>],
+    fulls: &[Option<Choice>],
+) -> Option<Vec<(usize, bool, Choice)>> {
+    let n = halves.len();
+    // The lowest total score of the first units, and the choice ending there.
+    let mut dp = vec![(f32::INFINITY, None); n + 1];
+    dp[0].0 = 0.;
+    for u in 0..n {
+        let s = dp[u].0;
+        if let Some(c) = halves[u].filter(|c| s + c.score < dp[u + 1].0) {
+            dp[u + 1] = (s + c.score, Some((false, c)));
+        }
+        if let Some(c) = fulls[u].filter(|c| u + 1 < n && s + c.score < dp[u + 2].0) {
+            dp[u + 2] = (s + c.score, Some((true, c)));
+        }
+    }
+    let mut row = Vec::with_capacity(n);
+    let mut u = n;
+    while u > 0 {
+        let (wide, c) = dp[u].1?;
+        u -= if wide { 2 } else { 1 };
+        row.push((u, wide, c));
+    }
+    row.reverse();
+    return Some(row);
+}
+
+/// Like `make_art`, but see `Matching::vertical`; Dithering diffuses errors down the column
+/// and into the next one on the left.
+fn make_columns(
+    matcher: &mut Matcher,
+    color: &RgbImage,
+    sampling: Sampling,
+) -> Result<Vec<Vec<Cell>>, String> {
+    let (w, h) = matcher.draft.dimensions();
+    let (cw, ch) = sampling.cell;
+    let matching = matcher.matching;
+    let (cols, rows) = ((w / (cw * 2)) as usize, (h / ch) as usize);
+    // Glyph of the previous frame starting at each column of each row.
+    let mut starts = vec![vec![None; cols]; rows];
+    let prev = matching.prev.map_or(&[][..], |p| &p.lines[..]);
+    for (r, line) in prev.iter().take(rows).enumerate() {
+        let mut u = 0;
+        for cell in line {
+            if u / 2 >= cols {
+                break;
+            }
+            if u % 2 == 0 {
+                starts[r][u / 2] = Some(cell.glyph);
+            }
+            u += glyph_width(cell.glyph, sampling.ambiguous).unwrap_or(1);
+        }
+    }
+    // Brightness errors to be diffused, per row of this column and the next,
+    // with a margin on both ends.
+    let (mut errs, mut next) = (vec![0f32; rows + 2], vec![0f32; rows + 2]);
+    // Choices of each column, from the rightmost.
+    let mut columns = Vec::<Vec<Choice>>::with_capacity(cols);
+    for i in (0..cols).rev() {
+        let mut column = Vec::with_capacity(rows);
+        for r in 0..rows {
+            let kept = starts[r][i].filter(|_| matching.stabilize > 0.);
+            let err = if matching.dither { errs[r + 1] } else { 0. };
+            let c = match matcher.choose(i as u32 * cw * 2, r as u32 * ch, true, err, kept)? {
+                Some(c) => c,
+                None => Err(format!("No full width glyphs in the charset"))?,
+            };
+            if matching.dither {
+                // Floyd-Steinberg turned a quarter, `v` is the row with the margin.
+                let (e, v) = (c.dc - c.mean, r + 1);
+                errs[v + 1] += e * 7. / 16.;
+                next[v - 1] += e * 3. / 16.;
+                next[v] += e * 5. / 16.;
+                next[v + 1] += e * 1. / 16.;
+            }
+            column.push(c);
+        }
+        std::mem::swap(&mut errs, &mut next);
+        next.iter_mut().for_each(|d| *d = 0.);
+        columns.push(column);
+    }
+    let mut lines = Vec::with_capacity(rows);
+    for r in 0..rows {
+        let mut line = Vec::with_capacity(cols);
+        for (i, column) in columns.iter().rev().enumerate() {
+            let at = (i as u32 * cw * 2, r as u32 * ch, true);
+            push_choice(&mut line, &column[r], at, color, matching, sampling);
+        }
+        lines.push(line);
+    }
+    return Ok(lines);
+}
+
+/// Push the cell of a choice for the block at `(x, y, wide)`, or blanks, see `Matching::blank`.
+fn push_choice(
+    line: &mut Vec<Cell>,
+    c: &Choice,
+    (x, y, wide): (u32, u32, bool),
+    color: &RgbImage,
+    matching: Matching,
+    sampling: Sampling,
+) {
+    let (cw, ch) = sampling.cell;
+    let cell = Cell {
+        color: average(color, x, y, if wide { cw * 2 } else { cw }, ch, sampling),
+        bg: None,
+        glyph: c.glyph,
+        attrs: match matching.bold {
+            Some(t) if c.energy > t => CELL_BOLD,
+            _ => 0,
+        },
+    };
+    match matching.blank {
+        Some((b, t))
+            if c.glyph.char().map_or(false, char::is_whitespace) || c.dc < t / 128. - 1. =>
+        {
+            let k = if wide { 2 } else { 1 };
+            line.extend((0..k).map(|_| Cell { glyph: b, ..cell }));
+        }
+        _ => line.push(cell),
+    }
+}
+
+/// The best glyph of a width for a block.
+#[derive(Clone, Copy, Debug)]
+struct Choice {
+    glyph: Glyph,
+    /// Lower is better, with `Matching::stabilize` applied
+    score: f32,
+    /// Mean brightness of the glyph, within `[-1, 1)`
+    mean: f32,
+    /// Mean brightness of the block with errors added
+    dc: f32,
+    /// AC energy of the block, see `Matching::bold`
+    energy: f32,
+}
+
+/// Blocks of the draft against the charset.
+struct Matcher<'a> {
+    draft: &'a GrayImage,
+    cs: &'a Charset,
+    matching: Matching<'a>,
+    cell: (u32, u32),
+    plugins: &'a mut Plugins,
+    rescore: bool,
+    /// Whether regions are flat, by `(size, x, y)`
+    flats: AHashMap<(u32, u32, u32), bool>,
+    /// Choices of flat regions without errors or kept glyphs, by the region and the width
+    decided: AHashMap<((u32, u32, u32), bool), Choice>,
+}
+
+impl Matcher<'_> {
+    /// The best glyph of the width for the block at `(x, y)` in px, `err` is added to its mean
+    /// brightness and `kept` is favored by `stabilize`; `None` if no glyphs of the width.
+    fn choose(
+        &mut self,
+        x: u32,
+        y: u32,
+        wide: bool,
+        err: f32,
+        kept: Option<Glyph>,
+    ) -> Result<Option<Choice>, String> {
+        let (m, (cw, ch)) = (self.matching, self.cell);
+        let bw = if wide { cw * 2 } else { cw };
+        // Choices depend on errors and kept glyphs, which differ between blocks of a region.
+        let region = match err == 0. && kept.is_none() {
+            true => m
+                .quadtree
+                .and_then(|q| flat_region(self.draft, (x, y, bw, ch), q, &mut self.flats)),
+            false => None,
+        };
+        if let Some(c) = region.and_then(|k| self.decided.get(&(k, wide))) {
+            return Ok(Some(*c));
+        }
+        let mut im = GrayImage::new(8, 8);
+        let crop = imageops::crop_imm(self.draft, x, y, bw, ch);
+        match self.cell == DEFAULT_CELL {
+            true => imageops::replace(&mut im, &crop, 0, 0),
+            false => {
+                let scaled = imageops::resize(&crop, if wide { 8 } else { 4 }, 8, Triangle);
+                imageops::replace(&mut im, &scaled, 0, 0)
+            }
+        }
+        let mut block = [[0f32; 8]; 8];
+        unsafe {
+            im.pixels().enumerate().for_each(|(i, Luma([n]))| {
+                *block.as_mut_ptr().cast::<f32>().add(i) = *n as f32 / 128. - 1.
+            });
+        }
+        let (mut f, glyphs, area) = match wide {
+            true => (algorithm::dct_8x8_feature(&block), &self.cs.full, 64.),
+            false => (algorithm::dct_4x8_feature(&block), &self.cs.half, 32.),
+        };
+        let o = match (m.orient != 0., wide) {
+            (false, _) => None,
+            (true, true) => Some(algorithm::orient_8x8_feature(&block)),
+            (true, false) => Some(algorithm::orient_4x8_feature(&block)),
+        };
+        // The DC coefficient is the sum of the block, so is where errors go.
+        f[0] += err.clamp(-1., 1.) * area;
+        let energy = f[1..].iter().map(|n| n * n).sum::<f32>().sqrt() / area;
+        // Coefficients after the last weighted one add nothing.
+        let n = match m.metric {
+            algorithm::Metric::Weighted => m.weights.0[..self.cs.coeffs]
+                .iter()
+                .rposition(|w| *w != 0.)
+                .map_or(0, |i| i + 1),
+            _ => self.cs.coeffs,
+        };
+        let mut best: Option<Choice> = None;
+        for (c, f2, o2) in glyphs {
+            let mut score = m.metric.distance(&f[..n], &f2[..n], &m.weights.0[..n]);
+            if let Some(o) = &o {
+                score += algorithm::orient_similarity(o, o2) * m.orient;
+            }
+            if self.rescore {
+                score = self.plugins.score(&f, f2, *c, wide, score)?;
+            }
+            if let Some(b) = m.bias.and_then(|b| b.get(c)) {
+                score *= b;
+            }
+            if kept == Some(*c) {
+                score *= 1. - m.stabilize;
+            }
+            if best.map_or(true, |b| score < b.score) {
+                best = Some(Choice {
+                    glyph: *c,
+                    score,
+                    mean: f2[0] / area,
+                    dc: f[0] / area,
+                    energy,
+                });
+            }
+        }
+        if let (Some(k), Some(c)) = (region, best) {
+            self.decided.insert((k, wide), c);
+        }
+        return Ok(best);
+    }
+}
+
+/// The largest flat region of `quadtree` containing the whole block, see `Matching`.
+fn flat_region(
+    draft: &GrayImage,
+    (x, y, w, h): (u32, u32, u32, u32),
+    (max, threshold): (u32, f32),
+    flats: &mut AHashMap<(u32, u32, u32), bool>,
+) -> Option<(u32, u32, u32)> {
+    let mut s = max;
+    while s >= 16 {
+        let k = (s, x / s, y / s);
+        if (x + w - 1) / s == k.1 && (y + h - 1) / s == k.2 {
+            let flat = *flats.entry(k).or_insert_with(|| {
+                let region = imageops::crop_imm(draft, k.1 * s, k.2 * s, s, s).to_image();
+                let n = (region.width() * region.height()) as f32;
+                let (sum, sq) = region.pixels().fold((0., 0.), |(sum, sq), Luma([p])| {
+                    (sum + *p as f32, sq + (*p as f32).powi(2))
+                });
+                let mean = sum / n;
+                (sq / n - mean * mean).max(0.).sqrt() < threshold
+            });
+            if flat {
+                return Some(k);
+            }
+        }
+        s /= 2;
+    }
+    return None;
+}
+
+/// Each block of the image becomes a `HALF_BLOCK`, colored by its upper and lower halves.
+///
+/// Compared to `make_art` on the same image, vertical resolution is doubled.
+pub fn make_halfblock(
+    color: &RgbImage,
+    sampling: Sampling,
+    plugins: &mut Plugins,
+) -> Result<Frame, String> {
+    let (w, h) = color.dimensions();
+    let (cw, ch) = sampling.cell;
+    let upper = ch / 2;
+    let lines = (0..h - h % ch)
+        .step_by(ch as usize)
+        .map(|y| {
+            (0..w - w % cw)
+                .step_by(cw as usize)
+                .map(|x| Cell {
+                    color: average(color, x, y, cw, upper, sampling),
+                    bg: Some(average(color, x, y + upper, cw, ch - upper, sampling)),
+                    glyph: HALF_BLOCK,
+                    attrs: 0,
+                })
+                .collect()
+        })
+        .collect();
+    return postprocess(Frame { lines }, plugins);
+}
+
+/// Cells of hand-made text art, colored from `color` stretched over all of them, or `MONO_COLOR`
+/// without; Tabs are expanded to multiples of 8 columns, other control chars become spaces.
+pub fn import_text(text: &str, color: Option<&RgbImage>, sampling: Sampling) -> Frame {
+    let lines = text
+        .lines()
+        .map(|l| {
+            let mut chars = Vec::with_capacity(l.len());
+            let mut col = 0;
+            for c in cluster::split(l) {
+                match c.char() {
+                    Some('\t') => {
+                        let n = 8 - col % 8;
+                        chars.extend(std::iter::repeat(Glyph::new(' ')).take(n));
+                        col += n;
+                    }
+                    Some(c) if c.is_control() => {
+                        chars.push(Glyph::new(' '));
+                        col += 1;
+                    }
+                    _ => {
+                        chars.push(c);
+                        col += glyph_width(c, sampling.ambiguous).unwrap_or(0);
+                    }
+                }
+            }
+            return (chars, col);
+        })
+        .collect::<Vec<_>>();
+    let cols = lines.iter().map(|(_, w)| *w).max().unwrap_or(0) as u32;
+    let (cw, ch) = sampling.cell;
+    let img = color
+        .filter(|_| cols > 0)
+        .map(|img| imageops::resize(img, cols * cw, lines.len() as u32 * ch, Triangle));
+    let lines = lines.iter().enumerate().map(|(y, (chars, _))| {
+        let mut col = 0;
+        return chars
+            .iter()
+            .map(|&glyph| {
+                let w = glyph_width(glyph, sampling.ambiguous).unwrap_or(0) as u32;
+                let color = match &img {
+                    Some(img) => {
+                        // Zero-width ones take the color at where they are.
+                        let (x, w) = (col.min(cols - 1), w.max(1));
+                        average(img, x * cw, y as u32 * ch, w * cw, ch, sampling)
+                    }
+                    None => MONO_COLOR,
+                };
+                col += w;
+                return Cell {
+                    color,
+                    bg: None,
+                    glyph,
+                    attrs: 0,
+                };
+            })
+            .collect();
+    });
+    return Frame {
+        lines: lines.collect(),
+    };
+}
+
+/// Set the background of each cell to the average of its block in the image,
+/// which should be the same size as the draft the frame made from.
+pub fn paint_bg(frame: &mut Frame, bg: &RgbImage, sampling: Sampling) {
+    let (cw, ch) = sampling.cell;
+    for (y, line) in frame.lines.iter_mut().enumerate() {
+        let mut x = 0;
+        for cell in line {
+            let w = glyph_width(cell.glyph, sampling.ambiguous).unwrap_or(1) as u32 * cw;
+            cell.bg = Some(average(bg, x, y as u32 * ch, w, ch, sampling));
+            x += w;
+        }
+    }
+}
+
+/// Scale saturation of colors of cells by `saturation` (`1` unchanged), then rotate hues
+/// by `hue_shift` degrees; Backgrounds are graded as well.
+pub fn grade_colors(frame: &mut Frame, saturation: f32, hue_shift: f32) {
+    if (saturation, hue_shift) == (1., 0.) {
+        return;
+    }
+    let grade = |[r, g, b]: [u8; 3]| -> [u8; 3] {
+        let [r, g, b] = [r, g, b].map(|c| c as f32 / 255.);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let d = max - min;
+        let h = match d > 0. {
+            false => 0.,
+            true if max == r => 60. * ((g - b) / d).rem_euclid(6.),
+            true if max == g => 60. * ((b - r) / d + 2.),
+            true => 60. * ((r - g) / d + 4.),
+        };
+        let s = match max > 0. {
+            true => (d / max * saturation).clamp(0., 1.),
+            false => 0.,
+        };
+        let h = (h + hue_shift).rem_euclid(360.);
+        let c = max * s;
+        let x = c * (1. - ((h / 60.) % 2. - 1.).abs());
+        let m = max - c;
+        let (r, g, b) = match (h / 60.) as u32 {
+            0 => (c, x, 0.),
+            1 => (x, c, 0.),
+            2 => (0., c, x),
+            3 => (0., x, c),
+            4 => (x, 0., c),
+            _ => (c, 0., x),
+        };
+        return [r, g, b].map(|n| ((n + m) * 255.).round().clamp(0., 255.) as u8);
+    };
+    for cell in frame.lines.iter_mut().flatten() {
+        cell.color = grade(cell.color);
+        cell.bg = cell.bg.map(grade);
+    }
+}
+
+/// Move colors of cells toward the current ones from those of the previous frame by `weight`,
+/// i.e. exponential moving average, unless any channel changes more than `threshold`.
+///
+/// Cells are matched by columns, where ambiguous chars are `ambiguous`; Those without
+/// counterparts are left as is.
+pub fn smooth_colors(
+    frame: &mut Frame,
+    prev: &Frame,
+    weight: f32,
+    threshold: u8,
+    ambiguous: Ambiguous,
+) {
+    let ema = |cur: [u8; 3], old: [u8; 3]| -> [u8; 3] {
+        match cur
+            .iter()
+            .zip(&old)
+            .any(|(c, o)| c.abs_diff(*o) > threshold)
+        {
+            true => cur,
+            false => [0, 1, 2]
+                .map(|i| (old[i] as f32 + (cur[i] as f32 - old[i] as f32) * weight).round() as u8),
+        }
+    };
+    for (line, old) in frame.lines.iter_mut().zip(&prev.lines) {
+        // Cells of the previous frame by the column they start from.
+        let mut cols = AHashMap::with_capacity(old.len());
+        let mut x = 0;
+        for cell in old {
+            cols.insert(x, cell);
+            x += glyph_width(cell.glyph, ambiguous).unwrap_or(1);
+        }
+        let mut x = 0;
+        for cell in line {
+            if let Some(o) = cols.get(&x) {
+                cell.color = ema(cell.color, o.color);
+                if let (Some(bg), Some(ob)) = (cell.bg, o.bg) {
+                    cell.bg = Some(ema(bg, ob));
+                }
+            }
+            x += glyph_width(cell.glyph, ambiguous).unwrap_or(1);
+        }
+    }
+}
+
+/// Replace cells whose blocks are mostly transparent in the alpha mask with spaces, wide ones by two;
+/// The mask should be the same size as the draft the frame made from with `sampling`.
+pub fn clear_transparent(frame: &mut Frame, alpha: &GrayImage, sampling: Sampling) {
+    let (cw, ch) = sampling.cell;
+    for (y, line) in frame.lines.iter_mut().enumerate() {
+        let mut x = 0;
+        let mut cleared = Vec::with_capacity(line.len());
+        for cell in line.drain(..) {
+            let n = glyph_width(cell.glyph, sampling.ambiguous).unwrap_or(1) as u32;
+            let block = imageops::crop_imm(alpha, x, y as u32 * ch, n * cw, ch).to_image();
+            let sum = block.pixels().map(|Luma([a])| *a as u32).sum::<u32>();
+            match sum < block.width() * block.height() * 128 {
+                true => cleared.extend((0..n).map(|_| Cell {
+                    color: cell.color,
+                    bg: None,
+                    glyph: Glyph::new(' '),
+                    attrs: 0,
+                })),
+                false => cleared.push(cell),
+            }
+            x += n * cw;
+        }
+        *line = cleared;
+    }
+}
+
+fn average(img: &RgbImage, x: u32, y: u32, w: u32, h: u32, sampling: Sampling) -> [u8; 3] {
+    let block = imageops::crop_imm(img, x, y, w, h).to_image();
+    if sampling.cell_color == CellColor::Dominant {
+        // Bins of 4 bits per channel, the most populous one wins.
+        let mut bins = Vec::<([u8; 3], Vec<[u8; 3]>)>::with_capacity(16);
+        for Rgb(p) in block.pixels() {
+            let key = p.map(|c| c >> 4);
+            match bins.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => v.push(*p),
+                None => bins.push((key, vec![*p])),
+            }
+        }
+        let top = bins.into_iter().max_by_key(|(_, v)| v.len());
+        return mean(
+            &top.map_or(Vec::with_capacity(0), |(_, v)| v),
+            sampling.linear,
+        );
+    }
+    if sampling.linear {
+        return mean(&block.pixels().map(|Rgb(p)| *p).collect::<Vec<_>>(), true);
+    }
+    let Rgb(rgb) = *imageops::resize(&block, 1, 1, Triangle).get_pixel(0, 0);
+    return rgb;
+}
+
+fn mean(pixels: &[[u8; 3]], linear: bool) -> [u8; 3] {
+    let mut sum = [0f32; 3];
+    for p in pixels {
+        sum.iter_mut().zip(p).for_each(|(s, c)| {
+            *s += match linear {
+                true => to_linear(*c),
+                false => *c as f32,
+            }
+        });
+    }
+    let n = pixels.len().max(1) as f32;
+    return sum.map(|s| match linear {
+        true => to_srgb(s / n),
+        false => (s / n).round() as u8,
+    });
+}
+
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.;
+    return match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    };
+}
+
+fn to_srgb(l: f32) -> u8 {
+    let c = match l <= 0.0031308 {
+        true => l * 12.92,
+        false => 1.055 * l.powf(1. / 2.4) - 0.055,
+    };
+    return (c * 255.).round().clamp(0., 255.) as u8;
+}
+
+/// Apply `shoal_postprocess` of plugins, if any.
+fn postprocess(mut frame: Frame, plugins: &mut Plugins) -> Result<Frame, String> {
+    if !plugins.is_empty() {
+        let mut payload = Vec::<u8>::with_capacity(
+            2 + frame.lines.iter().map(|l| 2 + l.len() * 8).sum::<usize>(),
+        );
+        encode_art(&mut payload, &frame).unwrap();
+        plugins.postprocess(&mut payload)?;
+        frame = match decode_art(&payload[..]) {
+            Ok(f) => f,
+            Err(e) => Err(format!("Invalid frame from plugins: {:?}", e))?,
+        };
+    }
+    return Ok(frame);
+}
@@ -0,0 +1,210 @@
+//! Audio tracks played along with animations; Needs the `audio` feature.
+
+use crate::*;
+use std::time::Duration;
+
+/// An audio track being played, whose position is the clock of playback.
+pub struct Track(
+    #[cfg(feature = "audio")] imp::Track,
+    #[cfg(not(feature = "audio"))] (),
+);
+
+impl Track {
+    /// Start playing from the beginning.
+    pub fn play<P: AsRef<Path>>(p: P) -> Result<Self, String> {
+        #[cfg(not(feature = "audio"))]
+        return Err(format!(
+            "Failed to play \"{}\": Shoalart was built without `audio` feature",
+            p.as_ref().to_string_lossy()
+        ));
+        #[cfg(feature = "audio")]
+        return imp::Track::play(p.as_ref()).map(Track);
+    }
+
+    /// Time played, pauses excluded.
+    pub fn position(&self) -> Duration {
+        #[cfg(feature = "audio")]
+        return self.0.position();
+        #[cfg(not(feature = "audio"))]
+        return Duration::ZERO;
+    }
+
+    pub fn pause(&mut self) {
+        #[cfg(feature = "audio")]
+        self.0.pause();
+    }
+
+    pub fn resume(&mut self) {
+        #[cfg(feature = "audio")]
+        self.0.resume();
+    }
+
+    /// Play from `_at` instead, keeping paused if so.
+    pub fn seek(&mut self, _at: Duration) -> Result<(), String> {
+        #[cfg(feature = "audio")]
+        return self.0.seek(_at);
+        #[cfg(not(feature = "audio"))]
+        return Ok(());
+    }
+}
+
+#[cfg(feature = "audio")]
+mod imp {
+    use super::*;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sample, Sink, Source};
+    use std::{
+        fs::File,
+        io::BufReader,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    pub struct Track {
+        path: PathBuf,
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        sink: Sink,
+        /// Where the sink starts in the track.
+        base: Duration,
+        /// Nanoseconds of samples taken by the sink since `base`.
+        played: Arc<AtomicU64>,
+        /// Position when started by wall clock, and when; `None` if paused.
+        wall: (Duration, Option<Instant>),
+    }
+
+    impl Track {
+        pub fn play(p: &Path) -> Result<Self, String> {
+            let (stream, handle) = match OutputStream::try_default() {
+                Ok(s) => s,
+                Err(e) => Err(format!("Failed to open audio output: {:?}", e))?,
+            };
+            let (sink, played) = sink(&handle, p, Duration::ZERO)?;
+            return Ok(Track {
+                path: p.to_path_buf(),
+                _stream: stream,
+                handle,
+                sink,
+                base: Duration::ZERO,
+                played,
+                wall: (Duration::ZERO, Some(Instant::now())),
+            });
+        }
+
+        /// By samples taken by the sink; Once the track ends, no more samples are taken,
+        /// so wall clock keeps the time.
+        pub fn position(&self) -> Duration {
+            let sampled = self.base + Duration::from_nanos(self.played.load(Ordering::Relaxed));
+            return match self.sink.empty() {
+                true => {
+                    sampled.max(self.wall.0 + self.wall.1.map_or(Duration::ZERO, |s| s.elapsed()))
+                }
+                false => sampled,
+            };
+        }
+
+        pub fn pause(&mut self) {
+            self.sink.pause();
+            self.wall = (self.position(), None);
+        }
+
+        pub fn resume(&mut self) {
+            if self.wall.1.is_none() {
+                self.sink.play();
+                self.wall = (self.position(), Some(Instant::now()));
+            }
+        }
+
+        pub fn seek(&mut self, at: Duration) -> Result<(), String> {
+            let paused = self.wall.1.is_none();
+            self.sink.stop();
+            (self.sink, self.played) = sink(&self.handle, &self.path, at)?;
+            if paused {
+                self.sink.pause();
+            }
+            self.base = at;
+            self.wall = (at, (!paused).then(Instant::now));
+            return Ok(());
+        }
+    }
+
+    /// The sink, and nanoseconds of samples it has taken.
+    fn sink(
+        handle: &OutputStreamHandle,
+        p: &Path,
+        at: Duration,
+    ) -> Result<(Sink, Arc<AtomicU64>), String> {
+        let file = match File::open(p) {
+            Ok(f) => f,
+            Err(e) => Err(format!("Failed to open audio: {:?}", e))?,
+        };
+        let source = match Decoder::new(BufReader::new(file)) {
+            Ok(d) => d,
+            Err(e) => Err(format!("Failed to decode audio: {:?}", e))?,
+        };
+        let sink = match Sink::try_new(handle) {
+            Ok(s) => s,
+            Err(e) => Err(format!("Failed to open audio output: {:?}", e))?,
+        };
+        let played = Arc::new(AtomicU64::new(0));
+        // Skipped samples are not counted.
+        sink.append(Counted {
+            inner: source.skip_duration(at),
+            played: Arc::clone(&played),
+            acc: 0.,
+        });
+        return Ok((sink, played));
+    }
+
+    /// Counts time of samples taken from the source, so that the clock follows
+    /// what is actually played, through pauses, underruns and slow seeks.
+    struct Counted<S> {
+        inner: S,
+        /// In nanoseconds
+        played: Arc<AtomicU64>,
+        /// `played` before rounding
+        acc: f64,
+    }
+
+    impl<S: Source> Iterator for Counted<S>
+    where
+        S::Item: Sample,
+    {
+        type Item = S::Item;
+
+        fn next(&mut self) -> Option<S::Item> {
+            let sample = self.inner.next()?;
+            let rate = self.inner.sample_rate() as f64 * self.inner.channels() as f64;
+            self.acc += 1e9 / rate.max(1.);
+            self.played.store(self.acc as u64, Ordering::Relaxed);
+            return Some(sample);
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            return self.inner.size_hint();
+        }
+    }
+
+    impl<S: Source> Source for Counted<S>
+    where
+        S::Item: Sample,
+    {
+        fn current_frame_len(&self) -> Option<usize> {
+            return self.inner.current_frame_len();
+        }
+
+        fn channels(&self) -> u16 {
+            return self.inner.channels();
+        }
+
+        fn sample_rate(&self) -> u32 {
+            return self.inner.sample_rate();
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            return self.inner.total_duration();
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! The charset format: glyphs with their features.
+
+use crate::{algorithm::ORIENT_BINS, *};
+use cluster::{Glyph, CLUSTER_FLAG};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, Read, Write},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_chars() {
+        let expand = |s| expand_chars(s).map(|s| s.chars().count());
+        assert_eq!(expand_chars("U+0041..43, u+3000").unwrap(), "ABC\u{3000}");
+        assert_eq!(expand_chars("U+0061").unwrap(), "a");
+        assert_eq!(expand("ascii"), Some(95));
+        assert_eq!(expand("Hiragana, block"), Some(96 + 32));
+        // Surrogates are skipped.
+        assert_eq!(expand("U+D7FF..U+E000"), Some(2));
+        assert_eq!(expand("U+0043..U+0041"), Some(0));
+        assert_eq!(expand("abc"), None);
+        assert_eq!(expand("ascii, 42"), None);
+        assert_eq!(expand("ascii, U+00G0"), None);
+    }
+    #[test]
+    fn test_closest_pairs() {
+        let cs = Charset::builtin();
+        let mut all = Vec::new();
+        for (i, (_, f, _)) in cs.half.iter().enumerate().filter(|(_, g)| g.0 != ' ') {
+            for (_, kf, _) in cs.half[i + 1..].iter().filter(|g| g.0 != ' ') {
+                all.push(algorithm::similarity(f, kf));
+            }
+        }
+        all.sort_unstable_by(f32::total_cmp);
+        let (pairs, close) = cs.closest_pairs(5, all[10]);
+        let top = pairs.iter().map(|p| p.2).collect::<Vec<_>>();
+        assert_eq!(top, all[..5]);
+        assert_eq!(close, all.iter().filter(|d| **d <= all[10]).count());
+        assert!(cs.closest_pairs(0, 0.).0.is_empty());
+        // NaN is the farthest instead of a panic.
+        let mut cs = cs;
+        cs.half
+            .push((Glyph::new('~'), [f32::NAN; 10], [0.; ORIENT_BINS]));
+        assert_eq!(cs.closest_pairs(5, all[10]), (pairs, close));
+    }
+    #[test]
+    fn test_read_item() {
+        // Short reads within an item, as at the boundary of chained readers.
+        let (head, tail) = ([1u8, 2, 3], [4u8, 5, 6, 7]);
+        let mut r = (&head[..]).chain(&tail[..]);
+        let mut buf = [0u8; 3];
+        assert!(read_item(&mut r, &mut buf).unwrap());
+        assert!(read_item(&mut r, &mut buf).unwrap());
+        assert_eq!(buf, [4, 5, 6]);
+        assert!(read_item(&mut r, &mut buf).is_err());
+        assert!(!read_item(&mut r, &mut buf).unwrap());
+    }
+}
+
+pub const CST_HEADER: &str = "Shoalart.v1 CHR";
+pub const CST_HEADER_LEN: usize = CST_HEADER.len();
+/// Payloads begin with the cell size, `width/u16`; `height/u16`; Then `flags/u8`,
+/// followed by `coeffs/u8` if `CST_COEFFS`, `Meta` if `CST_META`, clusters if `CST_CLUSTERS`,
+/// and bitmaps if `CST_BITMAPS`.
+pub const CST_CELL_LEN: usize = 2 + 2;
+pub const CST_META: u8 = 1;
+/// `count/u32`, then `glyph/char`; `bitmap/u8*64` of each.
+pub const CST_BITMAPS: u8 = 1 << 1;
+pub const CST_BITMAP_LEN: usize = 4 + 64;
+/// `count/u32`, then `len/u8`; `cluster/utf8*len` of each;
+/// Glyphs of clusters are `CLUSTER_FLAG` with the index, see `glyph_of`.
+pub const CST_CLUSTERS: u8 = 1 << 2;
+/// Features of items are of `coeffs` floats instead of `MAX_COEFFS`, see `Charset::coeffs`.
+pub const CST_COEFFS: u8 = 1 << 3;
+/// `width/bool`; `glyph/char`; `feature/f32*10`; `orient/f32*ORIENT_BINS`
+pub const CST_ITEM_LEN: usize = 1 + 4 + 10 * 4 + ORIENT_BINS * 4;
+/// Without the cell size, which is `art::DEFAULT_CELL`, flags, and orientations, which are
+/// derived from features when read; Still readable.
+pub const CST_HEADER_V0: &str = "Shoalart.v0 CHR";
+
+/// DCT coefficients of features, in zigzag order, see `algorithm::extract`.
+pub const MAX_COEFFS: usize = 10;
+pub const CST_ITEM_LEN_V0: usize = 1 + 4 + 10 * 4;
+
+/// Histogram of gradient orientations, see `algorithm::orient_8x8_feature`.
+pub type Orient = [f32; ORIENT_BINS];
+
+/// How glyphs of a charset were made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenMode {
+    /// Glyphs are fitted in the proportion of cells, see `charset gen`
+    Adaptive,
+    /// Glyphs are taken from fixed areas
+    Compat,
+    /// Glyphs are taken from an image
+    Atlas,
+    /// Of several charsets
+    Merged,
+    /// Glyphs are captured from a terminal, see `charset calibrate`
+    Calibrated,
+    /// Glyphs are fitted by metrics of the font, see `charset gen`
+    Hybrid,
+}
+
+impl GenMode {
+    pub const NAMES: &'static [&'static str] = &[
+        "adaptive",
+        "compat",
+        "atlas",
+        "merged",
+        "calibrated",
+        "hybrid",
+    ];
+
+    pub fn name(self) -> &'static str {
+        return GenMode::NAMES[self as usize];
+    }
+
+    fn from_byte(b: u8) -> Option<GenMode> {
+        return [
+            GenMode::Adaptive,
+            GenMode::Compat,
+            GenMode::Atlas,
+            GenMode::Merged,
+            GenMode::Calibrated,
+            GenMode::Hybrid,
+        ]
+        .get(b as usize)
+        .copied();
+    }
+}
+
+/// Where glyphs of a charset come from, for charsets not to be mixed up.
+///
+/// Written as `mode/u8`; `scale/f32`; `hash/u32`; `created/u64`; `len/u16`; `source/utf8*len`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Meta {
+    pub mode: GenMode,
+    /// Name of the font, the atlas image or the terminal; Names joined by commas if merged.
+    pub source: String,
+    /// CRC-32 of the font or atlas file; 0 if unknown.
+    pub hash: u32,
+    /// Height in px glyphs were rasterized at; 0 if unknown.
+    pub scale: f32,
+    /// Seconds since the UNIX epoch.
+    pub created: u64,
+}
+
+impl Meta {
+    pub fn new(mode: GenMode, source: String, hash: u32, scale: f32) -> Self {
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        return Meta {
+            mode,
+            source,
+            hash,
+            scale,
+            created,
+        };
+    }
+
+    /// e.g. `adaptive, from "Foo.ttf" (CRC-32 1a2b3c4d) at 64px, on 2021-10-01`
+    pub fn describe(&self) -> String {
+        let mut s = format!("{}, from \"{}\"", self.mode.name(), self.source);
+        if self.hash != 0 {
+            s += &format!(" (CRC-32 {:08x})", self.hash);
+        }
+        if self.scale > 0. {
+            s += &format!(" at {}px", self.scale);
+        }
+        return s + &format!(", on {}", util::format_date(self.created));
+    }
+
+    fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; 1 + 4 + 4 + 8 + 2];
+        r.read_exact(&mut buf)?;
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mode = GenMode::from_byte(buf[0]).ok_or(invalid("Unknown mode"))?;
+        let mut source = vec![0u8; u16::from_be_bytes([buf[17], buf[18]]) as usize];
+        r.read_exact(&mut source)?;
+        return Ok(Meta {
+            mode,
+            source: String::from_utf8(source).map_err(|_| invalid("Source is not UTF-8"))?,
+            hash: u32::from_be_bytes(buf[5..9].try_into().unwrap()),
+            scale: f32::from_be_bytes(buf[1..5].try_into().unwrap()),
+            created: u64::from_be_bytes(buf[9..17].try_into().unwrap()),
+        });
+    }
+
+    fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let source = &self.source.as_bytes()[..self.source.len().min(u16::MAX as usize)];
+        w.write_all(&[self.mode as u8])?;
+        w.write_all(&self.scale.to_be_bytes())?;
+        w.write_all(&self.hash.to_be_bytes())?;
+        w.write_all(&self.created.to_be_bytes())?;
+        w.write_all(&(source.len() as u16).to_be_bytes())?;
+        w.write_all(source)?;
+        return Ok(());
+    }
+}
+
+/// Glyphs with their features, divided by width, and sorted.
+///
+/// The blank `' '` is always written out even if absent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Charset {
+    /// Half width glyphs, whose features come from `4x8` blocks.
+    pub half: Vec<(Glyph, [f32; 10], Orient)>,
+    /// Full width glyphs, whose features come from `8x8` blocks.
+    pub full: Vec<(Glyph, [f32; 10], Orient)>,
+    /// Size of half width cells in px the glyphs were fitted in, see `art::Sampling`.
+    pub cell: (u32, u32),
+    /// Absent in the built-in charset and those of older versions.
+    pub meta: Option<Meta>,
+    /// Glyphs downsampled to `8x8` before features were taken, half width ones are on the left;
+    /// Only if asked for when generated.
+    pub bitmaps: AHashMap<Glyph, [u8; 64]>,
+    /// Leading coefficients of features compared when matching, within `1..=MAX_COEFFS`;
+    /// The rest are zero, and not stored in files. Fewer are smaller but less accurate.
+    pub coeffs: usize,
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        return Charset {
+            half: Vec::new(),
+            full: Vec::new(),
+            cell: art::DEFAULT_CELL,
+            meta: None,
+            bitmaps: AHashMap::new(),
+            coeffs: MAX_COEFFS,
+        };
+    }
+}
+
+impl Charset {
+    /// `chars/ASCII+font/Sarasa-Term-SC`
+    pub fn builtin() -> Self {
+        return Charset {
+            half: BULITIN_CHARSET
+                .iter()
+                .map(|(c, f)| (Glyph::new(*c), *f, derive_orient(f, false)))
+                .collect(),
+            full: Vec::with_capacity(0),
+            cell: art::DEFAULT_CELL,
+            meta: None,
+            bitmaps: AHashMap::new(),
+            coeffs: MAX_COEFFS,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.half.len() + self.full.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.half.is_empty() && self.full.is_empty();
+    }
+
+    /// Keep only glyphs for which `f` returns `true`.
+    pub fn retain<F: Fn(Glyph) -> bool>(&mut self, f: F) {
+        self.half.retain(|(c, ..)| f(*c));
+        self.full.retain(|(c, ..)| f(*c));
+        self.bitmaps.retain(|c, _| f(*c));
+    }
+
+    /// Keep only the first `n` coefficients of features, see `coeffs`.
+    pub fn truncate_coeffs(&mut self, n: usize) {
+        let n = n.clamp(1, MAX_COEFFS);
+        for (_, f, _) in self.half.iter_mut().chain(&mut self.full) {
+            f[n..].iter_mut().for_each(|v| *v = 0.);
+        }
+        self.coeffs = n;
+    }
+
+    /// Drop glyphs whose features are within `threshold` of a kept one of the same width,
+    /// by `algorithm::similarity`; Lower code points are kept first, e.g. `0` over `O`.
+    ///
+    /// Returns pairs of dropped glyphs and those kept for them.
+    pub fn dedupe(&mut self, threshold: f32) -> Vec<(Glyph, Glyph)> {
+        let mut dropped = Vec::new();
+        for glyphs in [&mut self.half, &mut self.full] {
+            let mut kept = Vec::<(Glyph, [f32; 10], Orient)>::with_capacity(glyphs.len());
+            for (c, f, o) in glyphs.drain(..) {
+                match kept
+                    .iter()
+                    .find(|(k, kf, _)| *k != ' ' && algorithm::similarity(kf, &f) <= threshold)
+                {
+                    Some((k, ..)) if c != ' ' => dropped.push((c, *k)),
+                    _ => kept.push((c, f, o)),
+                }
+            }
+            *glyphs = kept;
+        }
+        for (c, _) in &dropped {
+            self.bitmaps.remove(c);
+        }
+        return dropped;
+    }
+
+    /// The `top` closest pairs of glyphs of the same width, by `algorithm::similarity`, and how
+    /// many pairs are within `threshold`; Spaces are left out as by `dedupe`.
+    pub fn closest_pairs(&self, top: usize, threshold: f32) -> (Vec<(Glyph, Glyph, f32)>, usize) {
+        // Max-heap of those kept, the farthest is dropped once more than `top`.
+        let mut heap = BinaryHeap::with_capacity(top + 1);
+        let mut close = 0;
+        for glyphs in [&self.half, &self.full] {
+            for (i, (c, f, _)) in glyphs.iter().enumerate().filter(|(_, g)| g.0 != ' ') {
+                for (k, kf, _) in glyphs[i + 1..].iter().filter(|g| g.0 != ' ') {
+                    let d = algorithm::similarity(f, kf);
+                    close += (d <= threshold) as usize;
+                    heap.push(Pair(d, *c, *k));
+                    if heap.len() > top {
+                        heap.pop();
+                    }
+                }
+            }
+        }
+        let pairs = heap.into_sorted_vec().into_iter();
+        return (pairs.map(|Pair(d, c, k)| (c, k, d)).collect(), close);
+    }
+
+    /// `(glyph, wide, feature, orient)`
+    pub fn iter(&self) -> impl Iterator<Item = (Glyph, bool, &[f32; 10], &Orient)> {
+        return self
+            .half
+            .iter()
+            .map(|(c, f, o)| (*c, false, f, o))
+            .chain(self.full.iter().map(|(c, f, o)| (*c, true, f, o)));
+    }
+}
+
+/// Two glyphs by their distance, ordered by `f32::total_cmp` so that NaN does not panic.
+struct Pair(f32, Glyph, Glyph);
+
+impl Ord for Pair {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self
+            .0
+            .total_cmp(&other.0)
+            .then((self.1, self.2).cmp(&(other.1, other.2)));
+    }
+}
+
+impl PartialOrd for Pair {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Pair {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Pair {}
+
+/// Mean brightness of a glyph within `0..=255`, by the DC term of its features.
+pub fn brightness(f: &[f32; 10], wide: bool) -> u8 {
+    let area = if wide { 64. } else { 32. };
+    return ((f[0] / area + 1.) * 128.).clamp(0., 255.) as u8;
+}
+
+/// Orientations approximated from DCT features, for glyphs without their own.
+pub fn derive_orient(f: &[f32; 10], wide: bool) -> Orient {
+    let b = algorithm::reconstruct(f, wide);
+    return match wide {
+        false => algorithm::orient_4x8_feature(&b),
+        true => algorithm::orient_8x8_feature(&b),
+    };
+}
+
+/// Named Unicode blocks for `expand_chars`, most of which are for drawing.
+#[rustfmt::skip]
+pub const BLOCKS: &[(&str, char, char)] = &[
+    ("ascii",      ' ',        '~'),
+    ("latin1",     '\u{a1}',   '\u{ff}'),
+    ("greek",      '\u{370}',  '\u{3ff}'),
+    ("cyrillic",   '\u{400}',  '\u{4ff}'),
+    ("box",        '\u{2500}', '\u{257f}'),
+    ("block",      '\u{2580}', '\u{259f}'),
+    ("geometric",  '\u{25a0}', '\u{25ff}'),
+    ("braille",    '\u{2800}', '\u{28ff}'),
+    ("cjk-punct",  '\u{3000}', '\u{303f}'),
+    ("hiragana",   '\u{3040}', '\u{309f}'),
+    ("katakana",   '\u{30a0}', '\u{30ff}'),
+    ("cjk",        '\u{4e00}', '\u{9fff}'),
+    ("hangul",     '\u{ac00}', '\u{d7a3}'),
+    ("fullwidth",  '\u{ff01}', '\u{ff60}'),
+];
+
+/// Expand comma separated ranges like `U+0020..U+007E`, single code points like `U+3000`,
+/// and names of `BLOCKS` like `hiragana`; `None` if any item is not of them,
+/// for the text to be taken as chars literally.
+pub fn expand_chars(s: &str) -> Option<String> {
+    let point = |s: &str| {
+        let s = s.trim();
+        let hex = s
+            .strip_prefix("U+")
+            .or_else(|| s.strip_prefix("u+"))
+            .unwrap_or(s);
+        return u32::from_str_radix(hex, 16).ok();
+    };
+    let mut chars = String::new();
+    for item in s.split(',').map(str::trim) {
+        let (start, end) = match BLOCKS.iter().find(|b| b.0.eq_ignore_ascii_case(item)) {
+            Some(&(_, start, end)) => (start as u32, end as u32),
+            None => match item.split_once("..") {
+                Some((start, end)) => (point(start)?, point(end)?),
+                None if item.starts_with("U+") || item.starts_with("u+") => {
+                    (point(item)?, point(item)?)
+                }
+                None => return None,
+            },
+        };
+        chars.extend((start..=end).filter_map(char::from_u32));
+    }
+    return Some(chars);
+}
+
+/// Later glyphs replace earlier ones.
+impl FromIterator<(Glyph, bool, [f32; 10], Orient)> for Charset {
+    fn from_iter<I: IntoIterator<Item = (Glyph, bool, [f32; 10], Orient)>>(iter: I) -> Self {
+        let mut map = AHashMap::<Glyph, (bool, [f32; 10], Orient)>::with_capacity(384);
+        map.extend(iter.into_iter().map(|(c, w, f, o)| (c, (w, f, o))));
+        let mut cs = Charset::default();
+        for (c, (w, f, o)) in map {
+            match w {
+                false => cs.half.push((c, f, o)),
+                true => cs.full.push((c, f, o)),
+            }
+        }
+        cs.half.sort_unstable_by_key(|v| v.0);
+        cs.full.sort_unstable_by_key(|v| v.0);
+        return cs;
+    }
+}
+
+////////////////////////////////////////
+
+pub fn read_charset<P: AsRef<Path>>(p: P) -> Result<Charset, String> {
+    let mut file = match File::open(p.as_ref()) {
+        Ok(f) => f,
+        Err(e) => Err(format!("Failed to open charset: {:?}", e))?,
+    };
+    let mut buf = [0u8; CST_ITEM_LEN];
+    if let Err(e) = file.read_exact(&mut buf[..CST_HEADER_LEN]) {
+        Err(format!("Failed to read charset: {:?}", e))?;
+    }
+    let flagged = match &buf[..CST_HEADER_LEN] {
+        h if h == CST_HEADER.as_bytes() => true,
+        h if h == CST_HEADER_V0.as_bytes() => false,
+        _ => Err(format!("Failed to parse charset: Invalid header"))?,
+    };
+    return match || -> io::Result<((u32, u32), Option<Meta>, AHashMap<_, _>, usize, Vec<_>)> {
+        let (mut len, mut coeffs) = (CST_ITEM_LEN, MAX_COEFFS);
+        if !flagged {
+            len = CST_ITEM_LEN_V0;
+        }
+        let mut comp = util::decompress(file)?;
+        let mut cell = art::DEFAULT_CELL;
+        let mut meta = None;
+        let mut bitmaps = AHashMap::new();
+        let mut clusters = Vec::new();
+        if flagged {
+            comp.read_exact(&mut buf[..CST_CELL_LEN])?;
+            cell = (
+                u16::from_be_bytes([buf[0], buf[1]]) as u32,
+                u16::from_be_bytes([buf[2], buf[3]]) as u32,
+            );
+            if cell.0 == 0 || cell.1 == 0 {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Zero cell size"))?;
+            }
+            comp.read_exact(&mut buf[..1])?;
+            let flags = buf[0];
+            if flags & CST_COEFFS != 0 {
+                comp.read_exact(&mut buf[..1])?;
+                if buf[0] == 0 || buf[0] as usize > MAX_COEFFS {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid coeffs"))?;
+                }
+                coeffs = buf[0] as usize;
+                len -= (MAX_COEFFS - coeffs) * 4;
+            }
+            if flags & CST_META != 0 {
+                meta = Some(Meta::read(&mut comp)?);
+            }
+            if flags & CST_CLUSTERS != 0 {
+                comp.read_exact(&mut buf[..4])?;
+                let count = u32::from_be_bytes(buf[..4].try_into().unwrap());
+                for _ in 0..count {
+                    let mut len = [0u8];
+                    comp.read_exact(&mut len)?;
+                    let mut cluster = vec![0u8; len[0] as usize];
+                    comp.read_exact(&mut cluster)?;
+                    let cluster = String::from_utf8_lossy(&cluster);
+                    clusters.push(Glyph::parse(&cluster));
+                }
+            }
+            if flags & CST_BITMAPS != 0 {
+                comp.read_exact(&mut buf[..4])?;
+                let count = u32::from_be_bytes(buf[..4].try_into().unwrap());
+                let mut item = [0u8; CST_BITMAP_LEN];
+                for _ in 0..count {
+                    comp.read_exact(&mut item)?;
+                    let c = u32::from_be_bytes(item[..4].try_into().unwrap());
+                    if let Some(c) = glyph_of(c, &clusters) {
+                        bitmaps.insert(c, item[4..].try_into().unwrap());
+                    }
+                }
+            }
+        }
+        let buf = &mut buf[..len];
+        let mut cs = Vec::with_capacity(384);
+        while read_item(&mut comp, buf)? {
+            let c = match glyph_of(u32::from_be_bytes(buf[0..4].try_into().unwrap()), &clusters) {
+                Some(c) => c,
+                None => continue,
+            };
+            let w = buf[4] != 0;
+            let mut floats = (5..len)
+                .step_by(4)
+                .map(|i| f32::from_be_bytes(buf[i..i + 4].try_into().unwrap()));
+            let mut f = [0f32; 10];
+            f.iter_mut()
+                .zip(floats.by_ref().take(coeffs))
+                .for_each(|(v, n)| *v = n);
+            let o = match flagged {
+                true => floats.collect::<Vec<_>>().try_into().unwrap(),
+                false => derive_orient(&f, w),
+            };
+            cs.push((c, w, f, o));
+        }
+        Ok((cell, meta, bitmaps, coeffs, cs))
+    }() {
+        Ok((cell, meta, bitmaps, coeffs, cs)) => Ok(Charset {
+            cell,
+            meta,
+            bitmaps,
+            coeffs,
+            ..cs.into_iter().collect()
+        }),
+        Err(e) => Err(format!("Failed to parse charset: {:?}", e)),
+    };
+}
+
+/// Fill `buf` with the next item; `false` at the end, but `Err` if it ends within an item.
+fn read_item<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    return match n {
+        0 => Ok(false),
+        _ if n == buf.len() => Ok(true),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Truncated item of {} bytes", n),
+        )),
+    };
+}
+
+/// A char, or the cluster of the index flagged `CLUSTER_FLAG`.
+fn glyph_of(n: u32, clusters: &[Option<Glyph>]) -> Option<Glyph> {
+    return match n & CLUSTER_FLAG {
+        0 => char::from_u32(n).map(Glyph::new),
+        _ => clusters
+            .get((n & !CLUSTER_FLAG) as usize)
+            .copied()
+            .flatten(),
+    };
+}
+
+pub fn write_charset<P: AsRef<Path>>(p: P, cs: &Charset, codec: util::Codec) -> io::Result<()> {
+    let mut file = File::create(p.as_ref())?;
+    file.write_all(CST_HEADER.as_bytes())?;
+    let mut comp = util::compress(file, codec)?;
+    comp.write_all(&(cs.cell.0 as u16).to_be_bytes())?;
+    comp.write_all(&(cs.cell.1 as u16).to_be_bytes())?;
+    let glyphs = cs.iter().map(|(c, ..)| c).chain(cs.bitmaps.keys().copied());
+    let mut clusters = glyphs.filter(Glyph::is_cluster).collect::<Vec<_>>();
+    clusters.sort_unstable();
+    clusters.dedup();
+    let glyph = |c: Glyph| match (c.char(), clusters.binary_search(&c)) {
+        (Some(c), _) => c as u32,
+        (None, i) => CLUSTER_FLAG | i.unwrap() as u32,
+    };
+    let flags = match cs.meta.is_some() {
+        true => CST_META,
+        false => 0,
+    } | match cs.bitmaps.is_empty() {
+        true => 0,
+        false => CST_BITMAPS,
+    } | match clusters.is_empty() {
+        true => 0,
+        false => CST_CLUSTERS,
+    } | match cs.coeffs < MAX_COEFFS {
+        true => CST_COEFFS,
+        false => 0,
+    };
+    comp.write_all(&[flags])?;
+    if cs.coeffs < MAX_COEFFS {
+        comp.write_all(&[cs.coeffs as u8])?;
+    }
+    if let Some(meta) = &cs.meta {
+        meta.write(&mut comp)?;
+    }
+    if !clusters.is_empty() {
+        comp.write_all(&(clusters.len() as u32).to_be_bytes())?;
+        for c in &clusters {
+            let cluster = c.as_str();
+            comp.write_all(&[cluster.len() as u8])?;
+            comp.write_all(cluster.as_bytes())?;
+        }
+    }
+    if !cs.bitmaps.is_empty() {
+        comp.write_all(&(cs.bitmaps.len() as u32).to_be_bytes())?;
+        for (c, bitmap) in &cs.bitmaps {
+            comp.write_all(&glyph(*c).to_be_bytes())?;
+            comp.write_all(bitmap)?;
+        }
+    }
+    comp.write_all(b"\x00\x00\x00\x20\x00")?;
+    // 别特么忘了我们的值域是`[-1, 1)`！
+    comp.write_all(&(-32f32).to_be_bytes())?;
+    (1..cs.coeffs + ORIENT_BINS).try_for_each(|_| comp.write_all(&0f32.to_be_bytes()))?;
+    for (c, w, feat, orient) in cs.iter().filter(|(c, _, _, _)| *c != ' ') {
+        comp.write_all(&glyph(c).to_be_bytes())?;
+        comp.write_all(&(w as u8).to_be_bytes())?;
+        feat[..cs.coeffs]
+            .iter()
+            .chain(orient)
+            .try_for_each(|f| comp.write_all(&f.to_be_bytes()))?;
+    }
+    comp.finish()?;
+    return Ok(());
+}
+
+#[rustfmt::skip]
+const BULITIN_CHARSET: [(char, [f32; 10]); 95] = [
+    (' ', [-32.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000]),
+    ('!', [-27.687500,  0.153072,  0.083712, -3.049398,  0.005655, -1.810784, -0.375900, -0.034486, -0.108238, -0.202099]),
+    ('"', [-27.671875,  2.566176,  0.122190, -2.032932,  0.075350, -0.864137, -2.685735, -0.016573, -1.201202,  0.092894]),
+    ('#', [-18.093750, -0.366140,  0.402446, -5.778388, -0.019004, -6.350002,  0.116994, -0.181468,  0.148199,  0.192067]),
+    ('$', [-18.960938,  0.381606,  0.295505, -6.314242,  0.238307, -5.032045,  0.090663, -0.072012, -0.248507, -0.080546]),
+    ('%', [-17.656250,  0.521049,  0.519682, -3.425048,  0.848369, -6.567137, -0.180730, -0.137714, -0.070680,  0.215259]),
+    ('&', [-19.843750, -0.451876,  0.441400, -5.071281,  0.444430, -5.632273,  1.096957, -0.298243, -0.389327, -0.146957]),
+    ('\'',[-29.851562,  1.274990,  0.044846, -1.519175,  0.025806, -0.423975, -1.323533, -0.010575, -0.901554, -0.108267]),
+    ('(', [-25.070312, -0.179685,  0.072743, -4.226068, -0.005199, -1.347678, -0.124397, -1.219271,  0.111271, -0.849313]),
+    (')', [-25.046875, -0.187074,  0.226566, -4.132155,  0.004431, -1.303859, -0.108054,  1.182301,  0.106552,  0.575849]),
+    ('*', [-26.734375,  2.531234,  0.130946, -2.618505,  0.064653, -2.245230, -3.664769, -0.056583, -1.232644, -0.030322]),
+    ('+', [-27.835938, -0.120207,  0.092381, -2.292573, -0.001408, -3.551126,  0.276549, -0.078877,  0.065600, -0.080121]),
+    (',', [-29.164062, -2.126778,  0.086702, -2.005311, -0.080844,  0.519470,  0.864870,  0.067713,  1.503859, -0.209316]),
+    ('-', [-29.851562, -0.065538,  0.056504, -0.867311, -0.001408, -1.984897,  0.186637, -0.052203,  0.026943,  0.006492]),
+    ('.', [-30.421875, -1.011232,  0.029897, -1.115903, -0.019085, -0.223716,  1.142172, -0.004576,  0.715049, -0.072178]),
+    ('/', [-25.585938, -0.203722,  0.162807, -3.297990, -1.539253, -1.539445, -0.069962,  0.099356, -0.031454, -0.025581]),
+    ('0', [-19.375000,  0.446021,  0.460914, -3.159883, -0.047313, -6.233440, -0.185610, -0.262044, -0.115228,  0.275479]),
+    ('1', [-26.125000,  0.926521, -0.079960, -3.800699,  0.296921, -2.481578, -0.942908,  0.181746, -0.450515,  0.846323]),
+    ('2', [-23.171875,  0.132234,  0.233747, -3.491340, -0.537179, -2.804240, -0.016471,  0.114277, -0.068887, -0.013109]),
+    ('3', [-22.796875,  0.200516, -1.060608, -3.513437, -0.036830, -3.416266,  0.128429,  0.920007, -0.247994,  1.192726]),
+    ('4', [-24.132812, -0.268562, -0.194082, -4.148728,  0.111756, -4.893866,  1.009882, -0.287851, -0.095038,  1.162668]),
+    ('5', [-21.843750,  0.542205,  0.842696, -3.325612,  0.645371, -4.266465, -0.197154, -0.243413, -0.125037, -0.217509]),
+    ('6', [-22.726562, -0.949528,  0.758947, -3.574204,  0.094181, -5.027889,  1.275183, -0.584171, -0.096499, -0.607358]),
+    ('7', [-25.476562,  1.450719, -0.662002, -3.507912, -0.459557, -2.083646, -0.762941,  0.515639, -0.318104,  0.985762]),
+    ('8', [-20.421875,  0.288087,  0.342227, -4.618291, -0.004735, -5.274710,  0.013473, -0.161544, -0.315211,  0.133299]),
+    ('9', [-22.765625,  1.573469, -0.187715, -3.646019,  0.169756, -4.681046, -1.681783,  0.286937, -0.125990,  0.759409]),
+    (':', [-28.843750, -0.590174,  0.059794, -2.231806, -0.014166, -1.497428,  0.176183, -0.025056,  0.417316, -0.144356]),
+    (';', [-27.578125, -1.712216,  0.113609, -3.126738, -0.073439, -0.751253, -0.099595,  0.046090,  1.210719, -0.274277]),
+    ('<', [-27.406250, -0.130908, -0.070727, -1.988738, -0.003484, -3.715560,  0.245948, -0.171300,  0.059391,  0.089090]),
+    ('=', [-27.687500, -0.121531,  0.127444, -1.745670,  0.000000, -3.739001,  0.214547, -0.109930,  0.049106,  0.018964]),
+    ('>', [-27.406250, -0.121995,  0.329843, -1.955592, -0.003926, -3.694419,  0.225264, -0.036171,  0.055572, -0.040955]),
+    ('?', [-25.312500,  1.389435, -0.591787, -3.380854, -0.149249, -2.292413, -1.396848,  0.526491, -0.254172,  0.938740]),
+    ('@', [-17.453125, -0.174052,  0.558885, -4.872407, -0.205499, -6.259057, -0.182926, -0.194182,  0.166714,  0.569745]),
+    ('A', [-22.265625, -0.384896,  0.266846, -4.474660, -0.042674, -5.220045,  0.892567, -0.132697, -0.823877,  0.009057]),
+    ('B', [-19.593750,  0.369113,  1.274137, -3.369806,  0.138414, -5.629320, -0.114359, -0.389657, -0.250380,  0.333273]),
+    ('C', [-23.421875,  0.266863,  2.007504, -1.999786,  0.067025, -2.803266,  0.148635, -1.471554, -0.057286, -0.436893]),
+    ('D', [-20.609375,  0.362096,  0.930460, -2.132369,  0.028989, -4.721627,  0.018683, -0.068627, -0.062999,  0.080986]),
+    ('E', [-22.460938,  0.362861,  2.488972, -2.911291,  0.086686, -3.342021, -0.091548, -1.516960, -0.112359, -1.252205]),
+    ('F', [-24.476562,  1.371431,  2.149274, -2.988631, -0.179298, -3.216630, -0.914221, -1.225676, -0.686668, -2.187794]),
+    ('G', [-21.179688, -0.061635,  0.881086, -2.458301,  0.273389, -4.603578,  0.726455, -0.593764, -0.140191,  0.322677]),
+    ('H', [-21.117188,  0.414544,  0.444939, -1.375544,  0.012675, -5.888223, -0.366809, -0.233001, -0.077484,  0.395705]),
+    ('I', [-24.187500,  0.239964,  0.180533, -4.474660,  0.010479, -1.988788,  0.202812, -0.040681, -0.145335, -0.170450]),
+    ('J', [-24.789062, -0.365011, -1.542677, -2.867097, -0.688413, -2.470557,  0.909462,  0.996825,  0.051710,  2.295300]),
+    ('K', [-21.664062,  0.356033,  1.886853, -3.872515,  0.117591, -5.208298, -0.138047, -1.445287, -0.177452, -0.655992]),
+    ('L', [-25.593750, -0.923295,  2.244432, -2.220757,  0.346405, -2.359938,  0.814594, -1.297573,  0.481206, -2.376696]),
+    ('M', [-18.406250,  1.350361,  0.542274, -1.668330,  0.040686, -7.238306, -1.589606, -0.276051, -0.514539,  0.385285]),
+    ('N', [-18.929688,  0.447730,  0.479065, -2.867097,  0.416399, -6.752536, -0.215339, -0.192991, -0.080750,  0.333734]),
+    ('O', [-21.007812,  0.379488,  0.421322, -2.104748,  0.013769, -4.736576, -0.031906, -0.222900, -0.066026,  0.309816]),
+    ('P', [-22.664062,  1.837856,  1.596265, -2.568786, -0.420267, -4.502777, -1.876660, -0.679979, -0.694728, -1.097693]),
+    ('Q', [-19.468750, -0.978253,  0.010881, -2.949961,  0.389398, -3.853688, -0.320380, -0.504509,  0.678237,  0.892407]),
+    ('R', [-20.382812,  0.924624,  1.261365, -2.977582,  0.100143, -5.571790, -0.417643, -0.659433, -0.405686,  0.547843]),
+    ('S', [-22.718750,  0.274411,  0.224990, -3.513437,  0.329904, -3.469781,  0.059243, -0.018861, -0.147018,  0.110106]),
+    ('T', [-25.460938,  1.561839,  0.154652, -3.817272,  0.050030, -1.979819, -0.818250, -0.034012, -0.494736, -0.189627]),
+    ('U', [-21.765625, -0.054476,  0.385958, -1.568893,  0.028607, -4.795132,  0.245321, -0.198041,  0.659680,  0.354362]),
+    ('V', [-23.179688,  0.715139,  0.262105, -3.817272,  0.052421, -4.416215, -0.553110, -0.114981,  0.747905,  0.040918]),
+    ('W', [-19.906250,  0.003179,  0.422861, -3.458194,  0.050549, -6.621926,  0.340759, -0.215284,  1.178285,  0.183611]),
+    ('X', [-22.546875,  0.327342,  0.250659, -4.331029,  0.002333, -4.304321, -0.067554, -0.091347, -0.158510,  0.027721]),
+    ('Y', [-24.539062,  1.377002,  0.195181, -3.839369,  0.062966, -3.559084, -1.686085, -0.064870, -0.030778, -0.062908]),
+    ('Z', [-23.351562,  0.244906,  0.236737, -4.115582, -0.651134, -2.315642,  0.225567, -0.125914, -0.182088, -0.020327]),
+    ('[', [-22.742188, -0.214890,  2.780140, -3.806223, -0.075267,  0.151677, -0.322796, -1.158647,  0.085283, -3.118804]),
+    ('\\',[-25.593750, -0.103387,  0.170025, -3.303514,  1.522461, -1.556870, -0.099181, -0.126896,  0.212933, -0.022591]),
+    (']', [-22.906250, -0.206061, -2.268702, -4.231592,  0.058555,  0.218389, -0.308281,  1.148073,  0.089876,  3.415210]),
+    ('^', [-28.789062,  1.792508,  0.091355, -1.508126,  0.049317, -0.982663, -2.421526, -0.030383, -0.932512,  0.004016]),
+    ('_', [-29.820312, -1.786733,  0.070940, -0.889408, -0.060062,  0.777963,  0.427701,  0.031054,  0.728649,  0.012472]),
+    ('`', [-30.273438,  1.281634,  0.377341, -1.055136,  0.319417,  0.247545, -0.709477,  0.167055, -0.763505, -0.604757]),
+    ('a', [-22.882812, -2.044169, -0.272188, -3.054922,  0.009604, -5.289057,  2.390256,  0.304261,  0.504303,  0.310065]),
+    ('b', [-21.710938, -0.863091,  1.505796, -2.005311,  0.467237, -5.362862,  0.770163, -0.420108,  0.370968, -0.103511]),
+    ('c', [-24.835938, -1.384317,  1.023141, -2.126844, -0.211549, -3.988225,  1.157078, -0.787506,  0.383002, -0.142766]),
+    ('d', [-21.843750, -0.897785, -0.736569, -2.209709, -0.517137, -5.339458,  0.811523,  0.006587,  0.275580,  0.675821]),
+    ('e', [-22.929688, -1.706877,  0.622571, -2.933388, -0.179431, -5.714520,  2.020030, -0.422397,  0.553496, -0.012721]),
+    ('f', [-24.554688,  1.447436,  0.245120, -4.192922, -0.454684, -3.534565, -1.783444, -0.509512, -0.522445, -1.183809]),
+    ('g', [-20.007812, -4.601786, -0.017301, -3.297990,  0.028617, -2.984084,  0.471007, -0.078044,  1.550148,  0.347994]),
+    ('h', [-22.726562, -0.212798,  1.406198, -1.287155,  0.578000, -5.268217,  0.139215, -0.471167, -0.185641, -0.026379]),
+    ('i', [-25.296875, -0.768052,  0.373025, -3.988524,  0.101222, -2.478750,  0.719876, -0.210152,  0.247224, -0.369772]),
+    ('j', [-23.765625, -2.434944, -0.225871, -4.607243, -0.643026, -0.477951, -1.323584,  1.036065,  0.780308,  2.341824]),
+    ('k', [-22.882812, -0.675447,  2.144093, -3.198553,  0.252043, -5.122835,  1.051329, -1.171199,  0.464536, -0.929968]),
+    ('l', [-24.992188, -0.381748,  0.338387, -4.192922,  0.224972, -2.141264,  0.534718,  0.095957,  0.073721, -0.265732]),
+    ('m', [-20.914062, -1.815744,  0.523698, -2.292573, -0.030414, -7.030564,  2.053936, -0.340365,  0.172793,  0.123905]),
+    ('n', [-24.117188, -1.096497,  0.497728, -1.287155,  0.000696, -5.072948,  1.095973, -0.343601, -0.185641,  0.349921]),
+    ('o', [-23.687500, -1.601338,  0.289438, -2.154466, -0.058681, -4.941063,  1.688719, -0.187663,  0.378737,  0.179082]),
+    ('p', [-21.664062, -3.041747,  1.546626, -1.994262, -0.962356, -4.267579,  1.348060,  0.296564,  0.367452, -0.120423]),
+    ('q', [-21.781250, -2.949914, -0.777399, -2.209709,  0.730617, -4.305420,  1.344067, -0.622644,  0.518247,  0.692734]),
+    ('r', [-26.875000, -0.231429,  0.824861, -2.463825, -0.467170, -3.490445, -0.180412, -0.409141,  0.158152, -1.848484]),
+    ('s', [-24.343750, -1.529653,  0.195394, -3.303514,  0.097704, -4.396863,  1.499120, -0.220578,  0.625398,  0.038654]),
+    ('t', [-24.179688, -0.243258,  0.343143, -3.960903,  0.730226, -3.701440, -0.123235, -0.513858, -0.129830, -1.726683]),
+    ('u', [-24.132812, -1.990543,  0.124154, -1.309253,  0.058266, -4.429024,  2.333935, -0.174800,  0.694964,  0.169813]),
+    ('v', [-25.507812, -1.060046,  0.182197, -2.845000, -0.006952, -4.296026,  1.360911, -0.133564,  1.038488,  0.050100]),
+    ('w', [-23.234375, -1.954892,  0.297169, -2.508019, -0.040788, -5.579309,  2.802272, -0.204906,  1.194064,  0.140004]),
+    ('x', [-24.820312, -1.406142,  0.194155, -3.375330, -0.037798, -4.350866,  1.577819, -0.106498,  0.685004,  0.021228]),
+    ('y', [-24.085938, -2.333444,  0.411166, -4.038242, -0.221094, -3.441071,  1.018816,  0.042772,  1.898888, -0.523097]),
+    ('z', [-24.726562, -1.385258,  0.200135, -3.375330, -0.324105, -3.633438,  0.749318,  0.132319,  0.634341,  0.006793]),
+    ('{', [-23.804688, -0.203985,  0.034213, -5.098903, -0.000923, -1.509071, -0.122314, -0.880701,  0.121482, -0.307164]),
+    ('|', [-25.820312, -0.151578,  0.128558, -4.369699, -0.002932, -1.530989, -0.072253, -0.032672,  0.107182, -0.310366]),
+    ('}', [-23.812500, -0.210658,  0.306226, -5.038136, -0.004737, -1.456795, -0.130692,  0.826880,  0.126706, -0.126843]),
+    ('~', [-27.789062, -0.437989,  0.137138, -1.265058, -0.016172, -3.733959,  1.073460, -0.124296,  0.129772,  0.056805]),
+];
@@ -0,0 +1,171 @@
+//! Glyphs of cells and charsets, which are single chars or grapheme clusters of several,
+//! e.g. `e` with a combining acute accent, or emoji ZWJ sequences like `👩‍💻`.
+//!
+//! Glyphs keep their UTF-8 inline, so that cells stay `Copy` and clusters mean the same
+//! in every thread and run; Files keep clusters themselves.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+/// Longest glyphs in bytes of UTF-8, e.g. family emoji of four; Longer clusters are skipped.
+pub const CLUSTER_LEN: usize = 31;
+/// Bit of glyphs `char/u32` in files, which are clusters then: `len/u8` in the lowest byte,
+/// followed by `cluster/utf8*len`, see `read_glyph`.
+pub const CLUSTER_FLAG: u32 = 1 << 31;
+
+/// A single char, or a grapheme cluster of several; Ordered by code points, as `char`s are.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Glyph {
+    /// UTF-8 padded with zeros, which sort before any other byte.
+    bytes: [u8; CLUSTER_LEN],
+    len: u8,
+}
+
+impl Glyph {
+    pub const fn new(c: char) -> Glyph {
+        let (n, mut bytes) = (c as u32, [0u8; CLUSTER_LEN]);
+        let len = match n {
+            0..=0x7f => {
+                bytes[0] = n as u8;
+                1
+            }
+            0x80..=0x7ff => {
+                bytes[0] = 0xc0 | (n >> 6) as u8;
+                bytes[1] = 0x80 | (n & 0x3f) as u8;
+                2
+            }
+            0x800..=0xffff => {
+                bytes[0] = 0xe0 | (n >> 12) as u8;
+                bytes[1] = 0x80 | (n >> 6 & 0x3f) as u8;
+                bytes[2] = 0x80 | (n & 0x3f) as u8;
+                3
+            }
+            _ => {
+                bytes[0] = 0xf0 | (n >> 18) as u8;
+                bytes[1] = 0x80 | (n >> 12 & 0x3f) as u8;
+                bytes[2] = 0x80 | (n >> 6 & 0x3f) as u8;
+                bytes[3] = 0x80 | (n & 0x3f) as u8;
+                4
+            }
+        };
+        return Glyph { bytes, len };
+    }
+
+    /// `None` if `s` is empty or longer than `CLUSTER_LEN`; Whether `s` is a single grapheme
+    /// cluster is not checked, see `split`.
+    pub fn parse(s: &str) -> Option<Glyph> {
+        if s.is_empty() || s.len() > CLUSTER_LEN {
+            return None;
+        }
+        let mut bytes = [0u8; CLUSTER_LEN];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        return Some(Glyph {
+            bytes,
+            len: s.len() as u8,
+        });
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever filled from `char`s and `str`s.
+        return unsafe { std::str::from_utf8_unchecked(&self.bytes[..self.len as usize]) };
+    }
+
+    /// `None` if a cluster.
+    pub fn char(&self) -> Option<char> {
+        let mut chars = self.as_str().chars();
+        return chars.next().filter(|_| chars.next().is_none());
+    }
+
+    /// The first char, which labels clusters where only a `char` fits.
+    pub fn first(&self) -> char {
+        return self.as_str().chars().next().unwrap();
+    }
+
+    pub fn is_cluster(&self) -> bool {
+        return self.char().is_none();
+    }
+
+    /// Columns the glyph takes on terminals: Two for emoji sequences, i.e. with ZWJ, VS16 or
+    /// regional indicators of flags; Otherwise the sum of its chars, at most two, as terminals
+    /// lay out chars one by one. `None` for control chars.
+    pub fn width(&self, cjk: bool) -> Option<usize> {
+        let s = self.as_str();
+        if self.is_cluster()
+            && s.chars()
+                .any(|c| matches!(c, '\u{200d}' | '\u{fe0f}' | '\u{1f1e6}'..='\u{1f1ff}'))
+        {
+            return Some(2);
+        }
+        let mut width = 0;
+        for c in s.chars() {
+            width += match cjk {
+                true => c.width_cjk()?,
+                false => c.width()?,
+            };
+        }
+        return Some(width.min(2));
+    }
+}
+
+impl From<char> for Glyph {
+    fn from(c: char) -> Self {
+        return Glyph::new(c);
+    }
+}
+
+impl PartialEq<char> for Glyph {
+    fn eq(&self, c: &char) -> bool {
+        return self.char() == Some(*c);
+    }
+}
+
+impl fmt::Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(self.as_str());
+    }
+}
+
+impl fmt::Debug for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self.char() {
+            Some(c) => fmt::Debug::fmt(&c, f),
+            None => fmt::Debug::fmt(self.as_str(), f),
+        };
+    }
+}
+
+/// Grapheme clusters of `s`; Too long ones are skipped.
+pub fn split(s: &str) -> impl Iterator<Item = Glyph> + '_ {
+    return s.graphemes(true).filter_map(Glyph::parse);
+}
+
+/// `char/u32`, or a cluster flagged `CLUSTER_FLAG`.
+pub fn read_glyph<R: Read>(r: &mut R) -> io::Result<Glyph> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    let n = u32::from_be_bytes(buf);
+    if n & CLUSTER_FLAG == 0 {
+        return char::from_u32(n)
+            .map(Glyph::new)
+            .ok_or(invalid("Invalid char"));
+    }
+    let mut cluster = vec![0u8; (n & 0xff) as usize];
+    r.read_exact(&mut cluster)?;
+    let cluster = String::from_utf8(cluster).map_err(|_| invalid("Cluster is not UTF-8"))?;
+    return Glyph::parse(&cluster).ok_or(invalid("Cluster is empty or too long"));
+}
+
+pub fn write_glyph<W: Write>(w: &mut W, g: Glyph) -> io::Result<()> {
+    return match g.char() {
+        Some(c) => w.write_all(&(c as u32).to_be_bytes()),
+        None => {
+            w.write_all(&(CLUSTER_FLAG | g.len as u32).to_be_bytes())?;
+            w.write_all(g.as_str().as_bytes())
+        }
+    };
+}
@@ -0,0 +1,467 @@
+//! Exporting art into formats readable without Shoalart, or rasterizing it.
+
+use crate::*;
+use art::{glyph_width, Ambiguous, Art, Cell, Frame, CELL_BOLD, CELL_DIM, CELL_ITALIC};
+use cluster::Glyph;
+use image::{Rgb, RgbImage};
+use rusttype::{point, Font, Scale};
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Plain UTF-8 text, characters only
+    Txt,
+    /// Text with truecolor SGR escape sequences
+    Ansi,
+    /// Standalone web page, animations are played by a small script
+    Html,
+    /// Scalable image, one `<text>` per run of same-colored characters
+    Svg,
+    /// Recording of asciinema (v2 `.cast`)
+    Asciicast,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["txt", "ansi", "html", "svg", "asciicast"];
+
+    /// Whether all frames are exported, instead of one.
+    pub fn animated(self) -> bool {
+        return matches!(self, Format::Html | Format::Asciicast);
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "txt" | "text" => Ok(Format::Txt),
+            "ansi" => Ok(Format::Ansi),
+            "html" => Ok(Format::Html),
+            "svg" => Ok(Format::Svg),
+            "asciicast" | "cast" => Ok(Format::Asciicast),
+            _ => Err(format!("Unknown format \"{}\"", s)),
+        };
+    }
+}
+
+////////////////////////////////////////
+
+/// Characters of each line, without trailing whitespaces.
+pub fn export_txt<W: Write>(w: &mut W, frame: &Frame) -> io::Result<()> {
+    let mut s = String::with_capacity(1 << 12);
+    for line in &frame.lines {
+        s.clear();
+        s.extend(line.iter().map(|c| c.glyph.as_str()));
+        writeln!(w, "{}", s.trim_end())?;
+    }
+    return Ok(());
+}
+
+/// Colors and attributes are reset at the end of each line.
+pub fn export_ansi<W: Write>(w: &mut W, frame: &Frame) -> io::Result<()> {
+    for line in &frame.lines {
+        ansi_line(w, line)?;
+        w.write_all(b"\n")?;
+    }
+    return Ok(());
+}
+
+fn ansi_line<W: Write>(w: &mut W, line: &[Cell]) -> io::Result<()> {
+    let (mut cc, mut cb, mut ca) = (None, None, 0);
+    for Cell {
+        color,
+        bg,
+        glyph,
+        attrs,
+    } in line
+    {
+        if cc != Some(*color) {
+            cc = Some(*color);
+            let [r, g, b] = *color;
+            write!(w, "\x1b[38;2;{};{};{}m", r, g, b)?;
+        }
+        if cb != Some(*bg) {
+            cb = Some(*bg);
+            match bg {
+                Some([r, g, b]) => write!(w, "\x1b[48;2;{};{};{}m", r, g, b)?,
+                None => w.write_all(b"\x1b[49m")?,
+            }
+        }
+        if ca != *attrs {
+            ca = *attrs;
+            w.write_all(b"\x1b[22;23")?;
+            for (a, sgr) in [(CELL_BOLD, ";1"), (CELL_DIM, ";2"), (CELL_ITALIC, ";3")] {
+                if attrs & a != 0 {
+                    w.write_all(sgr.as_bytes())?;
+                }
+            }
+            w.write_all(b"m")?;
+        }
+        write!(w, "{}", glyph)?;
+    }
+    return w.write_all(b"\x1b[0m");
+}
+
+/// Delay of frames which have none in animated formats, in ms.
+pub const DEFAULT_DELAY: u128 = 200;
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Shoalart</title>
+<style>
+body { background: #000; margin: 0; }
+pre { font-family: monospace; line-height: 1; margin: 0; }
+pre[hidden] { display: none; }
+</style>
+</head>
+<body>
+"#;
+
+const HTML_PLAYER: &str = r#"<script>
+(function () {
+  const frames = document.querySelectorAll("pre");
+  let i = 0;
+  function next() {
+    frames[i].hidden = true;
+    i = (i + 1) % frames.length;
+    frames[i].hidden = false;
+    setTimeout(next, frames[i].dataset.delay);
+  }
+  setTimeout(next, frames[0].dataset.delay);
+})();
+</script>
+"#;
+
+/// Each frame is a `<pre>` of colored `<span>`s; A player is included if more than one frame.
+pub fn export_html<W: Write>(w: &mut W, art: &Art) -> io::Result<()> {
+    w.write_all(HTML_HEAD.as_bytes())?;
+    for (i, (frame, delay)) in art.frames.iter().zip(&art.delays).enumerate() {
+        let delay = match delay.is_zero() {
+            true => DEFAULT_DELAY,
+            false => delay.as_millis(),
+        };
+        let hidden = if i > 0 { " hidden" } else { "" };
+        write!(w, "<pre data-delay=\"{}\"{}>", delay, hidden)?;
+        for line in &frame.lines {
+            let mut cc = None;
+            for Cell {
+                color, bg, glyph, ..
+            } in line
+            {
+                if cc != Some((*color, *bg)) {
+                    if cc.is_some() {
+                        w.write_all(b"</span>")?;
+                    }
+                    cc = Some((*color, *bg));
+                    let [r, g, b] = *color;
+                    write!(w, "<span style=\"color:#{:02x}{:02x}{:02x}", r, g, b)?;
+                    if let Some([r, g, b]) = bg {
+                        write!(w, ";background:#{:02x}{:02x}{:02x}", r, g, b)?;
+                    }
+                    w.write_all(b"\">")?;
+                }
+                match glyph.char() {
+                    Some('&') => w.write_all(b"&amp;")?,
+                    Some('<') => w.write_all(b"&lt;")?,
+                    Some('>') => w.write_all(b"&gt;")?,
+                    _ => write!(w, "{}", glyph)?,
+                }
+            }
+            if cc.is_some() {
+                w.write_all(b"</span>")?;
+            }
+            w.write_all(b"\n")?;
+        }
+        w.write_all(b"</pre>\n")?;
+    }
+    if art.frames.len() > 1 {
+        w.write_all(HTML_PLAYER.as_bytes())?;
+    }
+    w.write_all(b"</body>\n</html>\n")?;
+    return Ok(());
+}
+
+/// Each frame is an output event drawing from the top left, as `export_ansi` does.
+pub fn export_asciicast<W: Write>(w: &mut W, art: &Art) -> io::Result<()> {
+    let (cols, rows) = art.dimensions();
+    writeln!(
+        w,
+        r#"{{"version": 2, "width": {}, "height": {}, "env": {{"TERM": "xterm-256color"}}}}"#,
+        cols, rows
+    )?;
+    let mut time = 0;
+    let mut ansi = Vec::<u8>::with_capacity(1 << 12);
+    let mut data = String::with_capacity(1 << 12);
+    for (i, (frame, delay)) in art.frames.iter().zip(&art.delays).enumerate() {
+        ansi.clear();
+        if i == 0 {
+            ansi.extend_from_slice(b"\x1b[2J");
+        }
+        ansi.extend_from_slice(b"\x1b[H");
+        for line in &frame.lines {
+            ansi_line(&mut ansi, line)?;
+            // Erase leftovers of the previous frame; Raw terminals need `\r\n`.
+            ansi.extend_from_slice(b"\x1b[K\r\n");
+        }
+        data.clear();
+        for c in String::from_utf8_lossy(&ansi).chars() {
+            match c {
+                '"' => data.push_str("\\\""),
+                '\\' => data.push_str("\\\\"),
+                c if (c as u32) < 0x20 => data.push_str(&format!("\\u{:04x}", c as u32)),
+                c => data.push(c),
+            }
+        }
+        writeln!(
+            w,
+            r#"[{}.{:03}, "o", "{}"]"#,
+            time / 1000,
+            time % 1000,
+            data
+        )?;
+        time += match delay.is_zero() {
+            true => DEFAULT_DELAY,
+            false => delay.as_millis(),
+        };
+    }
+    return Ok(());
+}
+
+/// Escape for XML.
+fn escape(s: &mut String, g: Glyph) {
+    match g.char() {
+        Some('&') => s.push_str("&amp;"),
+        Some('<') => s.push_str("&lt;"),
+        Some('>') => s.push_str("&gt;"),
+        _ => s.push_str(g.as_str()),
+    }
+}
+
+/// Size of a half-width cell in SVG, `(width, height)`.
+pub const SVG_CELL_SIZE: (usize, usize) = (8, 16);
+
+/// Runs are stretched by `textLength`, so the grid is kept whatever the font is;
+/// Backgrounds are drawn as `<rect>`s beneath; Ambiguous chars take `ambiguous` cells.
+pub fn export_svg<W: Write>(w: &mut W, frame: &Frame, ambiguous: Ambiguous) -> io::Result<()> {
+    let (cw, ch) = SVG_CELL_SIZE;
+    let (cols, rows) = frame.dimensions_as(ambiguous);
+    writeln!(
+        w,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        cols * cw,
+        rows * ch
+    )?;
+    writeln!(w, r##"<rect width="100%" height="100%" fill="#000"/>"##)?;
+    writeln!(
+        w,
+        r#"<g font-family="monospace" font-size="{}" xml:space="preserve">"#,
+        ch * 7 / 8
+    )?;
+    for (y, line) in frame.lines.iter().enumerate() {
+        let mut col = 0;
+        for Cell { bg, glyph, .. } in line {
+            let width = glyph_width(*glyph, ambiguous).unwrap_or(1);
+            if let Some([r, g, b]) = bg {
+                writeln!(
+                    w,
+                    r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02x}{:02x}{:02x}"/>"##,
+                    col * cw,
+                    y * ch,
+                    width * cw,
+                    ch,
+                    r,
+                    g,
+                    b
+                )?;
+            }
+            col += width;
+        }
+    }
+    let mut text = String::with_capacity(256);
+    for (y, line) in frame.lines.iter().enumerate() {
+        let mut col = 0;
+        // Start column, columns, color
+        let mut run = (0, 0, line.first().map_or([0; 3], |c| c.color));
+        for Cell { color, glyph, .. } in line {
+            if run.2 != *color {
+                svg_run(w, &text, run, y)?;
+                text.clear();
+                run = (col, 0, *color);
+            }
+            let width = glyph_width(*glyph, ambiguous).unwrap_or(1);
+            escape(&mut text, *glyph);
+            run.1 += width;
+            col += width;
+        }
+        svg_run(w, &text, run, y)?;
+        text.clear();
+    }
+    w.write_all(b"</g>\n</svg>\n")?;
+    return Ok(());
+}
+
+fn svg_run<W: Write>(
+    w: &mut W,
+    text: &str,
+    (x, n, color): (usize, usize, [u8; 3]),
+    y: usize,
+) -> io::Result<()> {
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+    let (cw, ch) = SVG_CELL_SIZE;
+    let [r, g, b] = color;
+    return writeln!(
+        w,
+        r##"<text x="{}" y="{}" textLength="{}" lengthAdjust="spacingAndGlyphs" fill="#{:02x}{:02x}{:02x}">{}</text>"##,
+        x * cw,
+        (y + 1) * ch - ch / 4,
+        n * cw,
+        r,
+        g,
+        b,
+        text
+    );
+}
+
+////////////////////////////////////////
+
+/// Draw each cell with the font onto a black canvas, `cell` is the size of a half-width cell in px.
+///
+/// Backgrounds fill whole cells, ambiguous chars take `ambiguous` cells.
+///
+/// Glyphs are scaled to fit in cells, and centered.
+pub fn render(frame: &Frame, font: &Font, (cw, ch): (u32, u32), ambiguous: Ambiguous) -> RgbImage {
+    let (cols, rows) = frame.dimensions_as(ambiguous);
+    let mut img = RgbImage::new(cols as u32 * cw, rows as u32 * ch);
+    let adv = font
+        .glyph('0')
+        .scaled(Scale::uniform(1.))
+        .h_metrics()
+        .advance_width;
+    let scale = Scale::uniform(match adv > 0. {
+        true => (ch as f32).min(cw as f32 / adv),
+        false => ch as f32,
+    });
+    let v = font.v_metrics(scale);
+    let base = (ch as f32 - (v.ascent - v.descent)) / 2. + v.ascent;
+    for (y, line) in frame.lines.iter().enumerate() {
+        let mut col = 0;
+        for Cell {
+            color, bg, glyph, ..
+        } in line
+        {
+            let width = glyph_width(*glyph, ambiguous).unwrap_or(1);
+            if let Some(bg) = bg {
+                for py in y as u32 * ch..(y as u32 + 1) * ch {
+                    for px in col as u32 * cw..(col + width) as u32 * cw {
+                        img.put_pixel(px, py, Rgb(*bg));
+                    }
+                }
+            }
+            // Chars of a cluster follow each other, combining marks do not advance.
+            let glyphs = glyph
+                .as_str()
+                .chars()
+                .map(|c| font.glyph(c).scaled(scale))
+                .collect::<Vec<_>>();
+            let advance = glyphs
+                .iter()
+                .map(|g| g.h_metrics().advance_width)
+                .sum::<f32>();
+            let mut left = (col as u32 * cw) as f32 + (width as f32 * cw as f32 - advance) / 2.;
+            for g in glyphs {
+                let adv = g.h_metrics().advance_width;
+                let g = g.positioned(point(left, (y as u32 * ch) as f32 + base));
+                left += adv;
+                if let Some(b) = g.pixel_bounding_box() {
+                    g.draw(|x, y, a| {
+                        let x = x as i32 + b.min.x;
+                        let y = y as i32 + b.min.y;
+                        if (x >= 0 && x < img.width() as i32) && (y >= 0 && y < img.height() as i32)
+                        {
+                            let Rgb(p) = img.get_pixel_mut(x as u32, y as u32);
+                            for (p, c) in p.iter_mut().zip(color) {
+                                *p = (*c as f32 * a + *p as f32 * (1. - a)) as u8;
+                            }
+                        }
+                    });
+                }
+            }
+            col += width;
+        }
+    }
+    return img;
+}
+
+/// Levels of red, green and blue in the palette of Sixel, 252 colors totally.
+const SIXEL_LEVELS: [usize; 3] = [6, 7, 6];
+
+/// Encode the image into Sixel, for DEC-compatible terminals; Colors are reduced to a fixed palette.
+pub fn export_sixel<W: Write>(w: &mut W, img: &RgbImage) -> io::Result<()> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let [lr, lg, lb] = SIXEL_LEVELS;
+    let q = |c: u8, l: usize| (c as usize * (l - 1) + 127) / 255;
+    write!(w, "\x1bPq\"1;1;{};{}", width, height)?;
+    for i in 0..lr * lg * lb {
+        let (r, g, b) = (i / (lg * lb), i / lb % lg, i % lb);
+        write!(
+            w,
+            "#{};2;{};{};{}",
+            i,
+            r * 100 / (lr - 1),
+            g * 100 / (lg - 1),
+            b * 100 / (lb - 1)
+        )?;
+    }
+    let mut band = Vec::with_capacity(width * 6);
+    let mut sixels = vec![0u8; width];
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        band.clear();
+        for y in top..top + rows {
+            band.extend((0..width).map(|x| {
+                let Rgb([r, g, b]) = *img.get_pixel(x as u32, y as u32);
+                (q(r, lr) * lg + q(g, lg)) * lb + q(b, lb)
+            }));
+        }
+        // Colors in order of appearance, each drawn over the band then back to its left.
+        let mut used = Vec::with_capacity(16);
+        for i in &band {
+            if !used.contains(i) {
+                used.push(*i);
+            }
+        }
+        for (n, i) in used.into_iter().enumerate() {
+            sixels.iter_mut().for_each(|s| *s = 0);
+            for (dy, row) in band.chunks(width).enumerate() {
+                for (x, _) in row.iter().enumerate().filter(|(_, c)| **c == i) {
+                    sixels[x] |= 1 << dy;
+                }
+            }
+            if n > 0 {
+                w.write_all(b"$")?;
+            }
+            write!(w, "#{}", i)?;
+            let mut x = 0;
+            while x < width {
+                let s = sixels[x];
+                let run = sixels[x..].iter().take_while(|t| **t == s).count();
+                let c = (s + 0x3f) as char;
+                match run {
+                    1..=3 => (0..run).try_for_each(|_| write!(w, "{}", c))?,
+                    _ => write!(w, "!{}{}", run, c)?,
+                }
+                x += run;
+            }
+        }
+        w.write_all(b"-")?;
+    }
+    return w.write_all(b"\x1b\\");
+}
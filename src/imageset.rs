@@ -0,0 +1,127 @@
+//! The imageset format: images with their features, for photomosaic.
+
+use crate::*;
+use image::{imageops::Triangle, DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+};
+
+pub const IMS_HEADER: &str = "Shoalart.v0 IMG";
+pub const IMS_HEADER_LEN: usize = IMS_HEADER.len();
+
+/// Features of each channel, only one for `Gray` mode.
+pub type Feature = Vec<[f32; 10]>;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Imageset {
+    pub gray: bool,
+    /// `filename`; `feature`
+    pub items: Vec<(String, Feature)>,
+}
+
+////////////////////////////////////////
+
+pub fn read_imageset<P: AsRef<Path>>(p: P) -> Result<Imageset, String> {
+    let mut file = match File::open(p.as_ref()) {
+        Ok(f) => f,
+        Err(e) => Err(format!("Failed to open imageset: {:?}", e))?,
+    };
+    let mut buf = [0u8; IMS_HEADER_LEN];
+    if let Err(e) = file.read_exact(&mut buf) {
+        Err(format!("Failed to read imageset: {:?}", e))?;
+    }
+    if &buf != IMS_HEADER.as_bytes() {
+        Err(format!("Failed to parse imageset: Invalid header"))?;
+    }
+    return match || -> io::Result<Imageset> {
+        let mut comp = util::lz4read(file);
+        comp.read_exact(&mut buf[..5])?;
+        let gray = buf[0] != 0;
+        let n = u32::from_be_bytes(buf[1..5].try_into().unwrap()) as usize;
+        let chs = if gray { 1 } else { 3 };
+        let mut items = Vec::with_capacity(n.min(4096)); // The count may be corrupt.
+        for _ in 0..n {
+            comp.read_exact(&mut buf[..2])?;
+            let mut name = vec![0u8; u16::from_be_bytes(buf[..2].try_into().unwrap()) as usize];
+            comp.read_exact(&mut name)?;
+            let name = match String::from_utf8(name) {
+                Ok(s) => s,
+                Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e))?,
+            };
+            let mut feat = Vec::with_capacity(chs);
+            for _ in 0..chs {
+                let mut f = [0f32; 10];
+                for n in f.iter_mut() {
+                    comp.read_exact(&mut buf[..4])?;
+                    *n = f32::from_be_bytes(buf[..4].try_into().unwrap());
+                }
+                feat.push(f);
+            }
+            items.push((name, feat));
+        }
+        Ok(Imageset { gray, items })
+    }() {
+        Ok(ims) => Ok(ims),
+        Err(e) => Err(format!("Failed to parse imageset: {:?}", e)),
+    };
+}
+
+pub fn write_imageset<P: AsRef<Path>>(p: P, ims: &Imageset) -> io::Result<()> {
+    let mut file = File::create(p.as_ref())?;
+    file.write_all(IMS_HEADER.as_bytes())?;
+    let mut comp = util::lz4write(file);
+    comp.write_all(&[ims.gray as u8])?;
+    comp.write_all(&(ims.items.len() as u32).to_be_bytes())?;
+    for (name, feat) in &ims.items {
+        comp.write_all(&(name.len() as u16).to_be_bytes())?;
+        comp.write_all(name.as_bytes())?;
+        for f in feat {
+            f.iter()
+                .try_for_each(|n| comp.write_all(&n.to_be_bytes()))?;
+        }
+    }
+    comp.finish()?;
+    return Ok(());
+}
+
+/// Image of any size will be resized to `8x8` first.
+pub fn feature(img: &DynamicImage, gray: bool) -> Feature {
+    let small = img.resize_exact(8, 8, Triangle);
+    return match gray {
+        true => vec![feature_luma(&small.to_luma8())],
+        false => feature_rgb(&small.to_rgb8()),
+    };
+}
+
+/// `8x8` only.
+pub fn feature_luma(img: &GrayImage) -> [f32; 10] {
+    let mut block = [[0f32; 8]; 8];
+    unsafe {
+        img.pixels().enumerate().for_each(|(i, Luma([n]))| {
+            *block.as_mut_ptr().cast::<f32>().add(i) = *n as f32 / 128. - 1.
+        });
+    }
+    return algorithm::dct_8x8_feature(&block);
+}
+
+/// `8x8` only.
+pub fn feature_rgb(img: &RgbImage) -> Feature {
+    let mut blocks = [[[0f32; 8]; 8]; 3];
+    unsafe {
+        img.pixels().enumerate().for_each(|(i, Rgb(rgb))| {
+            for ch in 0..3 {
+                *blocks[ch].as_mut_ptr().cast::<f32>().add(i) = rgb[ch] as f32 / 128. - 1.
+            }
+        });
+    }
+    return blocks.iter().map(algorithm::dct_8x8_feature).collect();
+}
+
+pub fn similarity(f: &Feature, f2: &Feature) -> f32 {
+    return f
+        .iter()
+        .zip(f2.iter())
+        .map(|(a, b)| algorithm::similarity(a, b))
+        .sum();
+}
@@ -0,0 +1,487 @@
+//! JSON and MessagePack documents of art, and JSON and CSV tables of charsets, for other
+//! programs to read and write without the binary formats, see `SCHEMA` and `CHARSET_SCHEMA`.
+
+use crate::*;
+use algorithm::ORIENT_BINS;
+use art::{Art, Cell, Frame, CELL_ATTRS};
+use charset::{Charset, Meta, Orient, MAX_COEFFS};
+use cluster::Glyph;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, Read, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_art() {
+        let cell = |ch: &str, bg, attrs| Cell {
+            color: [1, 2, 3],
+            bg,
+            glyph: Glyph::parse(ch).unwrap(),
+            attrs,
+        };
+        let mut art = Art::default();
+        let lines = vec![
+            vec![cell("a", None, 0), cell("👩‍💻", Some([4, 5, 6]), CELL_ATTRS)],
+            vec![cell("\"", None, 0)],
+        ];
+        art.push(Frame { lines }, Duration::from_millis(40));
+        art.push(Frame::default(), Duration::ZERO);
+        for format in [Interchange::Json, Interchange::Msgpack] {
+            let mut buf = Vec::new();
+            export(&mut buf, &art, format).unwrap();
+            assert_eq!(import(&buf[..], format).unwrap(), art);
+        }
+        let doc = r#"{"format": "shoalart", "version": 2, "frames": []}"#;
+        assert!(import(doc.as_bytes(), Interchange::Json).is_err());
+        let doc = r#"{"format": "shoalart", "version": 1, "frames": [
+            {"lines": [[{"ch": "ab", "fg": [0, 0, 0]}]]}
+        ]}"#;
+        assert!(import(doc.as_bytes(), Interchange::Json).is_err());
+    }
+
+    #[test]
+    fn test_charset() {
+        let builtin = Charset::builtin();
+        let extra = [("e\u{301}", false), ("👩‍💻", true), ("字", true)];
+        let extra = extra.iter().map(|(c, w)| {
+            let f = [-20., 1.5, -0.25, 3., 0., 0.125, 7., 0., 1e-3, -1e3];
+            return (Glyph::parse(c).unwrap(), *w, f, [0.1, 0.2, 0.3, 0.4]);
+        });
+        let glyphs = builtin.iter().map(|(c, w, f, o)| (c, w, *f, *o));
+        let mut cs = Charset {
+            cell: (5, 10),
+            meta: Some(Meta::new(
+                charset::GenMode::Adaptive,
+                "Foo.ttf".to_string(),
+                42,
+                64.,
+            )),
+            ..glyphs.chain(extra).collect()
+        };
+        cs.truncate_coeffs(6);
+        cs.bitmaps.insert(Glyph::new(','), [7; 64]);
+        let mut buf = Vec::new();
+        export_charset(&mut buf, &cs, Table::Json).unwrap();
+        assert_eq!(import_charset(&buf[..], Table::Json).unwrap(), cs);
+        buf.clear();
+        export_charset(&mut buf, &cs, Table::Csv).unwrap();
+        (cs.meta, cs.bitmaps) = (None, AHashMap::new());
+        assert_eq!(import_charset(&buf[..], Table::Csv).unwrap(), cs);
+        // Features of files written before truncation have all coefficients.
+        let line = "U+0041,0,1,2,3,4,5,6,7,8,9,10,0,0,0,0";
+        assert!(parse_glyph(line, MAX_COEFFS).is_some());
+        assert!(parse_glyph(line, 6).is_some());
+        assert!(parse_glyph("A,0,1,2,3,0,0,0,0", 6).is_none());
+    }
+}
+
+/// Documents are of the same schema in both formats; MessagePack maps are keyed by field names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interchange {
+    Json,
+    Msgpack,
+}
+
+impl Interchange {
+    pub const NAMES: &'static [&'static str] = &["json", "msgpack"];
+}
+
+impl FromStr for Interchange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Interchange::Json),
+            "msgpack" | "mp" => Ok(Interchange::Msgpack),
+            _ => Err(format!("Unknown format \"{}\"", s)),
+        };
+    }
+}
+
+/// Value of `format` in documents.
+pub const FORMAT: &str = "shoalart";
+/// Value of `version` in documents, increased on incompatible changes.
+pub const VERSION: u32 = 1;
+
+pub const SCHEMA: &str = r#"SCHEMA (version 1):
+{
+  "format": "shoalart",
+  "version": 1,
+  "columns": 80,              // Maximum width of frames on terminals, informative only
+  "rows": 24,                 // Maximum lines of frames, informative only
+  "frames": [
+    {
+      "delay_ms": 0,          // How long the frame lasts; 0 if unspecified
+      "lines": [              // Lines may differ in length, as glyphs are half or full width
+        [
+          {
+            "ch": "A",        // A single char, or a grapheme cluster of several
+            "fg": [255, 255, 255],
+            "bg": [0, 0, 0],  // Optional; Terminal default if omitted or null
+            "attrs": 0        // Optional; Bits of bold (2), dim (4) and italic (8)
+          }
+        ]
+      ]
+    }
+  ]
+}
+A `.shoal` file is a document of a single frame."#;
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    format: String,
+    version: u32,
+    #[serde(default)]
+    columns: usize,
+    #[serde(default)]
+    rows: usize,
+    frames: Vec<DocFrame>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocFrame {
+    #[serde(default)]
+    delay_ms: u64,
+    lines: Vec<Vec<DocCell>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocCell {
+    ch: String,
+    fg: [u8; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bg: Option<[u8; 3]>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    attrs: u8,
+}
+
+fn is_zero(n: &u8) -> bool {
+    return *n == 0;
+}
+
+pub fn export<W: Write>(mut w: W, art: &Art, format: Interchange) -> Result<(), String> {
+    let (columns, rows) = art.dimensions();
+    let frames = art.frames.iter().zip(&art.delays).map(|(frame, delay)| {
+        let lines = frame.lines.iter().map(|line| {
+            line.iter()
+                .map(|c| DocCell {
+                    ch: c.glyph.to_string(),
+                    fg: c.color,
+                    bg: c.bg,
+                    attrs: c.attrs,
+                })
+                .collect()
+        });
+        return DocFrame {
+            delay_ms: delay.as_millis() as u64,
+            lines: lines.collect(),
+        };
+    });
+    let doc = Document {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        columns,
+        rows,
+        frames: frames.collect(),
+    };
+    return match format {
+        Interchange::Json => serde_json::to_writer(&mut w, &doc).map_err(|e| e.to_string()),
+        Interchange::Msgpack => {
+            rmp_serde::encode::write_named(&mut w, &doc).map_err(|e| e.to_string())
+        }
+    }
+    .and_then(|_| w.flush().map_err(|e| e.to_string()))
+    .map_err(|e| format!("Failed to export: {}", e));
+}
+
+pub fn import<R: Read>(r: R, format: Interchange) -> Result<Art, String> {
+    let doc: Document = match format {
+        Interchange::Json => serde_json::from_reader(r).map_err(|e| e.to_string()),
+        Interchange::Msgpack => rmp_serde::from_read(r).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Failed to import: {}", e))?;
+    if doc.format != FORMAT {
+        Err(format!("Failed to import: Not a document of Shoalart"))?;
+    } else if doc.version > VERSION {
+        Err(format!(
+            "Failed to import: Version {} is newer than supported {}",
+            doc.version, VERSION
+        ))?;
+    }
+    let mut art = Art::default();
+    for DocFrame { delay_ms, lines } in doc.frames {
+        let mut frame = Frame::default();
+        for line in lines {
+            let mut cells = Vec::with_capacity(line.len());
+            for c in line {
+                cells.push(Cell {
+                    color: c.fg,
+                    bg: c.bg,
+                    glyph: glyph_of(&c.ch)
+                        .ok_or(format!("Failed to import: Invalid char \"{}\"", c.ch))?,
+                    attrs: c.attrs & CELL_ATTRS,
+                });
+            }
+            frame.lines.push(cells);
+        }
+        art.push(frame, Duration::from_millis(delay_ms));
+    }
+    return Ok(art);
+}
+
+////////////////////////////////////////
+
+/// Formats of charsets; CSV keeps no metadata or bitmaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    Json,
+    Csv,
+}
+
+impl Table {
+    pub const NAMES: &'static [&'static str] = &["json", "csv"];
+}
+
+impl FromStr for Table {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Table::Json),
+            "csv" => Ok(Table::Csv),
+            _ => Err(format!("Unknown format \"{}\"", s)),
+        };
+    }
+}
+
+/// Value of `format` in JSON of charsets.
+pub const CHARSET_FORMAT: &str = "shoalart-charset";
+/// Value of `version` in JSON of charsets.
+pub const CHARSET_VERSION: u32 = 1;
+
+pub const CHARSET_SCHEMA: &str = r#"SCHEMA of JSON (version 1):
+{
+  "format": "shoalart-charset",
+  "version": 1,
+  "cell": [4, 8],             // Size of half width cells glyphs were fitted in
+  "meta": null,               // Optional; See `charset read`
+  "coeffs": 10,               // Optional; Leading coefficients of features kept, within 1..=10
+  "glyphs": [
+    {
+      "ch": "A",              // A single char, or a grapheme cluster of several
+      "wide": false,          // Full width glyphs come from 8x8 blocks, half width from 4x8
+      "feature": [...],       // `coeffs` DCT coefficients, 10 if omitted
+      "orient": [...],        // Histogram of gradient orientations, of 4 bins
+      "bitmap": [...]         // Optional; 64 pixels of the 8x8 glyph, row by row
+    }
+  ]
+}
+SCHEMA of CSV:
+# cell 4x8                    // Optional; 4x8 if omitted
+# coeffs 10                   // Optional; 10 if omitted, and as many columns of f0..
+ch,wide,f0,f1,f2,f3,f4,f5,f6,f7,f8,f9,o0,o1,o2,o3
+A,0,-22.265625,...
+U+002C,0,...                  // `U+{hex}` for commas, quotes and whitespaces
+Bitmaps are kept in JSON only."#;
+
+#[derive(Serialize, Deserialize)]
+struct CharsetDocument {
+    format: String,
+    version: u32,
+    cell: (u32, u32),
+    #[serde(default)]
+    meta: Option<Meta>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coeffs: Option<usize>,
+    glyphs: Vec<DocGlyph>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocGlyph {
+    ch: String,
+    wide: bool,
+    feature: Vec<f32>,
+    orient: Orient,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bitmap: Option<Vec<u8>>,
+}
+
+pub fn export_charset<W: Write>(mut w: W, cs: &Charset, format: Table) -> Result<(), String> {
+    let exported = match format {
+        Table::Json => {
+            let doc = CharsetDocument {
+                format: CHARSET_FORMAT.to_string(),
+                version: CHARSET_VERSION,
+                cell: cs.cell,
+                meta: cs.meta.clone(),
+                coeffs: Some(cs.coeffs).filter(|n| *n < MAX_COEFFS),
+                glyphs: cs
+                    .iter()
+                    .map(|(ch, wide, feature, orient)| DocGlyph {
+                        ch: ch.to_string(),
+                        wide,
+                        feature: feature[..cs.coeffs].to_vec(),
+                        orient: *orient,
+                        bitmap: cs.bitmaps.get(&ch).map(|b| b.to_vec()),
+                    })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut w, &doc).map_err(|e| e.to_string())
+        }
+        Table::Csv => || -> std::io::Result<()> {
+            writeln!(w, "# cell {}x{}", cs.cell.0, cs.cell.1)?;
+            if cs.coeffs < MAX_COEFFS {
+                writeln!(w, "# coeffs {}", cs.coeffs)?;
+            }
+            let f = (0..cs.coeffs).map(|i| format!(",f{}", i));
+            let o = (0..ORIENT_BINS).map(|i| format!(",o{}", i));
+            writeln!(w, "ch,wide{}", f.chain(o).collect::<String>())?;
+            for (ch, wide, feature, orient) in cs.iter() {
+                match ch.char() {
+                    Some(c @ (',' | '"' | '#')) => write!(w, "U+{:04X}", c as u32)?,
+                    Some(c) if c.is_whitespace() || c.is_control() => {
+                        write!(w, "U+{:04X}", c as u32)?
+                    }
+                    _ => write!(w, "{}", ch)?,
+                }
+                write!(w, ",{}", wide as u8)?;
+                feature[..cs.coeffs]
+                    .iter()
+                    .chain(orient)
+                    .try_for_each(|v| write!(w, ",{}", v))?;
+                writeln!(w)?;
+            }
+            return Ok(());
+        }()
+        .map_err(|e| e.to_string()),
+    };
+    return exported
+        .and_then(|_| w.flush().map_err(|e| e.to_string()))
+        .map_err(|e| format!("Failed to export: {}", e));
+}
+
+pub fn import_charset<R: BufRead>(r: R, format: Table) -> Result<Charset, String> {
+    return match format {
+        Table::Json => {
+            let doc: CharsetDocument =
+                serde_json::from_reader(r).map_err(|e| format!("Failed to import: {}", e))?;
+            if doc.format != CHARSET_FORMAT {
+                Err(format!("Failed to import: Not a charset of Shoalart"))?;
+            } else if doc.version > CHARSET_VERSION {
+                Err(format!(
+                    "Failed to import: Version {} is newer than supported {}",
+                    doc.version, CHARSET_VERSION
+                ))?;
+            } else if doc.cell.0 == 0 || doc.cell.1 == 0 {
+                Err(format!("Failed to import: Zero cell size"))?;
+            }
+            let coeffs = doc.coeffs.unwrap_or(MAX_COEFFS);
+            let mut glyphs = Vec::with_capacity(doc.glyphs.len());
+            let mut bitmaps = AHashMap::new();
+            for g in doc.glyphs {
+                let ch = glyph_of(&g.ch)
+                    .ok_or(format!("Failed to import: Invalid char \"{}\"", g.ch))?;
+                let feature = feature_of(&g.feature, coeffs).ok_or(format!(
+                    "Failed to import: {} coefficients of \"{}\", but {} were expected",
+                    g.feature.len(),
+                    g.ch,
+                    coeffs
+                ))?;
+                if let Some(bitmap) = g.bitmap.and_then(|b| b.try_into().ok()) {
+                    bitmaps.insert(ch, bitmap);
+                }
+                glyphs.push((ch, g.wide, feature, g.orient));
+            }
+            let mut cs = Charset {
+                cell: doc.cell,
+                meta: doc.meta,
+                bitmaps,
+                ..glyphs.into_iter().collect()
+            };
+            cs.truncate_coeffs(coeffs);
+            Ok(cs)
+        }
+        Table::Csv => {
+            let (mut cell, mut coeffs) = (art::DEFAULT_CELL, MAX_COEFFS);
+            let mut glyphs = Vec::new();
+            for (i, line) in r.lines().enumerate() {
+                let line = line.map_err(|e| format!("Failed to import: {:?}", e))?;
+                let line = line.trim();
+                if let Some(comment) = line.strip_prefix('#') {
+                    if let Some((w, h)) = comment.trim().strip_prefix("cell ").and_then(|c| {
+                        let (w, h) = c.trim().split_once('x')?;
+                        return Some((w.parse().ok()?, h.parse().ok()?));
+                    }) {
+                        cell = (w, h);
+                    } else if let Some(Ok(n)) = comment
+                        .trim()
+                        .strip_prefix("coeffs ")
+                        .map(|n| n.trim().parse())
+                    {
+                        coeffs = n;
+                    }
+                    continue;
+                } else if line.is_empty() || line.starts_with("ch,") {
+                    continue;
+                }
+                let glyph = parse_glyph(line, coeffs)
+                    .ok_or(format!("Failed to import: Invalid line {}", i + 1))?;
+                glyphs.push(glyph);
+            }
+            if cell.0 == 0 || cell.1 == 0 {
+                Err(format!("Failed to import: Zero cell size"))?;
+            }
+            let mut cs = Charset {
+                cell,
+                ..glyphs.into_iter().collect()
+            };
+            cs.truncate_coeffs(coeffs);
+            Ok(cs)
+        }
+    };
+}
+
+/// A single char or grapheme cluster.
+fn glyph_of(s: &str) -> Option<Glyph> {
+    let mut glyphs = cluster::split(s);
+    return glyphs.next().filter(|_| glyphs.next().is_none());
+}
+
+/// Padded with zeros; `None` unless of `coeffs` to `MAX_COEFFS` coefficients, as files written
+/// before features were truncated have all of them.
+fn feature_of(v: &[f32], coeffs: usize) -> Option<[f32; 10]> {
+    if v.len() < coeffs || v.len() > MAX_COEFFS {
+        return None;
+    }
+    let mut f = [0f32; 10];
+    f[..v.len()].copy_from_slice(v);
+    return Some(f);
+}
+
+/// `{char},{wide},{f0..},{o0..}`, of `coeffs` features, see `feature_of`; Finite floats only.
+fn parse_glyph(line: &str, coeffs: usize) -> Option<(Glyph, bool, [f32; 10], Orient)> {
+    let mut fields = line.split(',').map(str::trim);
+    let ch = fields.next()?;
+    let ch = match ch.strip_prefix("U+") {
+        Some(hex) if !hex.is_empty() => {
+            Glyph::new(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?)
+        }
+        _ => glyph_of(ch)?,
+    };
+    let wide = match fields.next()? {
+        "0" | "false" => false,
+        "1" | "true" => true,
+        _ => return None,
+    };
+    let floats = fields
+        .map(|f| f.parse::<f32>().ok().filter(|f| f.is_finite()))
+        .collect::<Option<Vec<_>>>()?;
+    let (f, o) = floats.split_at(floats.len().checked_sub(ORIENT_BINS)?);
+    return Some((ch, wide, feature_of(f, coeffs)?, o.try_into().ok()?));
+}
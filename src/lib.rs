@@ -0,0 +1,37 @@
+//! (WIP) Powerful ASCII Art generator, but not yet easy to use.
+//!
+//! ```no_run
+//! use shoalart::{art, charset::Charset, plugin::Plugins, util::Codec};
+//!
+//! let img = image::open("input.png").unwrap();
+//! let frame = art::make_art(
+//!     &img.to_luma8(),
+//!     &img.to_rgb8(),
+//!     &Charset::builtin(),
+//!     Default::default(),
+//!     Default::default(),
+//!     &mut Plugins::none(),
+//! )
+//! .unwrap();
+//! art::write_art("output.shoal", &frame, Codec::default()).unwrap();
+//! ```
+
+pub mod algorithm;
+pub mod ansi;
+pub mod art;
+pub mod audio;
+pub mod charset;
+pub mod cluster;
+pub mod export;
+pub mod imageset;
+pub mod interchange;
+pub mod live;
+pub mod plugin;
+pub mod preview;
+pub mod serve;
+pub mod subtitle;
+pub mod util;
+pub mod video;
+
+pub(crate) use ahash::AHashMap;
+pub(crate) use std::path::{Path, PathBuf};
@@ -0,0 +1,125 @@
+//! Live sources of images; Webcams need the `camera` feature.
+
+use image::RgbImage;
+use std::{io, thread, time::Duration};
+
+/// Any of live sources.
+pub enum Source {
+    Camera(Camera),
+    Screen(Screen),
+}
+
+impl Source {
+    /// Blocks until the next frame.
+    pub fn frame(&mut self) -> Result<RgbImage, String> {
+        return match self {
+            Source::Camera(c) => c.frame(),
+            Source::Screen(s) => s.frame(),
+        };
+    }
+}
+
+/// A webcam streaming frames.
+pub struct Camera(
+    #[cfg(feature = "camera")] nokhwa::Camera,
+    #[cfg(not(feature = "camera"))] (),
+);
+
+impl Camera {
+    /// `index` of cameras, counting from 0.
+    pub fn open(index: usize) -> Result<Self, String> {
+        #[cfg(not(feature = "camera"))]
+        return Err(format!(
+            "Failed to open camera {}: Shoalart was built without `camera` feature",
+            index
+        ));
+        #[cfg(feature = "camera")]
+        {
+            // YUYV needs no decoding of MJPEG, whose decoder is left out.
+            let format = nokhwa::CameraFormat::new_from(640, 480, nokhwa::FrameFormat::YUYV, 30);
+            let mut cam = match nokhwa::Camera::new(index, Some(format)) {
+                Ok(c) => c,
+                Err(e) => Err(format!("Failed to open camera {}: {:?}", index, e))?,
+            };
+            if let Err(e) = cam.open_stream() {
+                Err(format!("Failed to open camera {}: {:?}", index, e))?;
+            }
+            return Ok(Camera(cam));
+        }
+    }
+
+    /// Blocks until the next frame.
+    pub fn frame(&mut self) -> Result<RgbImage, String> {
+        #[cfg(not(feature = "camera"))]
+        return Err(String::with_capacity(0));
+        #[cfg(feature = "camera")]
+        {
+            let f = match self.0.frame() {
+                Ok(f) => f,
+                Err(e) => Err(format!("Failed to capture camera: {:?}", e))?,
+            };
+            let (w, h) = f.dimensions();
+            return match RgbImage::from_raw(w, h, f.into_raw()) {
+                Some(img) => Ok(img),
+                None => Err(format!("Invalid frame from camera")),
+            };
+        }
+    }
+}
+
+/// A display being captured.
+pub struct Screen {
+    cap: scrap::Capturer,
+    width: usize,
+    height: usize,
+}
+
+impl Screen {
+    /// `index` of displays, counting from 0; The primary one if `None`.
+    pub fn open(index: Option<usize>) -> Result<Self, String> {
+        let display = match index {
+            None => scrap::Display::primary(),
+            Some(i) => scrap::Display::all().and_then(|mut d| match i < d.len() {
+                true => Ok(d.swap_remove(i)),
+                false => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Display {} out of {}", i, d.len()),
+                )),
+            }),
+        };
+        let cap = match display.and_then(scrap::Capturer::new) {
+            Ok(c) => c,
+            Err(e) => Err(format!("Failed to capture display: {:?}", e))?,
+        };
+        let (width, height) = (cap.width(), cap.height());
+        return Ok(Screen { cap, width, height });
+    }
+
+    /// Blocks until the next frame.
+    pub fn frame(&mut self) -> Result<RgbImage, String> {
+        let (w, h) = (self.width, self.height);
+        loop {
+            match self.cap.frame() {
+                Ok(frame) => {
+                    // Rows may be padded.
+                    let stride = frame.len() / h;
+                    let mut img = RgbImage::new(w as u32, h as u32);
+                    for (y, row) in frame.chunks_exact(stride).enumerate() {
+                        for (x, bgra) in row[..w * 4].chunks_exact(4).enumerate() {
+                            img.put_pixel(
+                                x as u32,
+                                y as u32,
+                                image::Rgb([bgra[2], bgra[1], bgra[0]]),
+                            );
+                        }
+                    }
+                    return Ok(img);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(3));
+                }
+                Err(e) => Err(format!("Failed to capture display: {:?}", e))?,
+            }
+        }
+    }
+}
@@ -1,11 +1,12 @@
-mod algorithm;
 mod routine;
-mod util;
 
 pub use ahash::{AHashMap, AHashSet};
+pub use routine::util;
+pub use shoalart::{algorithm, plugin};
 pub use std::path::{Path, PathBuf};
 pub use structopt::StructOpt;
 
+use routine::util::try_again;
 use routine::*;
 use rustdct::num_traits::Num;
 use std::panic;
@@ -31,6 +32,9 @@ enum Opt {
     Charset(charset::Param),
     Edgedet(edgedet::Param),
     Art(art::Param),
+    Imageset(imageset::Param),
+    Photon(photon::Param),
+    Shoal(shoal::Param),
 }
 
 const INVALID_SYNTAX: &str = "Invalid syntax";
@@ -58,6 +62,35 @@ fn opt_resize<T: Num>(s: &str) -> Result<(T, T), &'static str> {
     ));
 }
 
+/// Like `opt_resize`, but both must be positive and fit in `u16`, as cells are stored in charsets.
+fn opt_cell(s: &str) -> Result<(u32, u32), &'static str> {
+    let (w, h) = opt_resize::<u16>(s)?;
+    if w == 0 || h == 0 {
+        return Err(INVALID_NUMBER);
+    }
+    return Ok((w as u32, h as u32));
+}
+
+#[rustfmt::skip]
+fn opt_ratio(s: &str) -> Result<(f32, f32), &'static str> {
+    let p = s.find(":").ok_or(INVALID_SYNTAX)?;
+    let n = |s: &str| s.parse::<f32>().ok().filter(|n| *n > 0.).ok_or(INVALID_NUMBER);
+    return Ok((n(&s[0..p])?, n(&s[p + 1..s.len()])?));
+}
+
+#[rustfmt::skip]
+fn opt_rgb(s: &str) -> Result<[u8; 3], &'static str> {
+    let s = s.strip_prefix("#").ok_or(INVALID_SYNTAX)?;
+    if s.len() != 6 || !s.is_ascii() {
+        return Err(INVALID_SYNTAX);
+    }
+    return Ok([
+        u8::from_str_radix(&s[0..2], 16).ok().ok_or(INVALID_NUMBER)?,
+        u8::from_str_radix(&s[2..4], 16).ok().ok_or(INVALID_NUMBER)?,
+        u8::from_str_radix(&s[4..6], 16).ok().ok_or(INVALID_NUMBER)?,
+    ]);
+}
+
 ////////////////////////////////////////
 
 fn main() {
@@ -80,6 +113,9 @@ fn main() {
         Opt::Charset(param) => charset::main(param),
         Opt::Edgedet(param) => edgedet::main(param),
         Opt::Art(param) => art::main(param),
+        Opt::Imageset(param) => imageset::main(param),
+        Opt::Photon(param) => photon::main(param),
+        Opt::Shoal(param) => shoal::main(param),
     }
     println!("*** DONE ***");
 }
@@ -0,0 +1,284 @@
+//! WASM plugin hooks; Needs the `plugin` feature.
+//!
+//! A plugin is a `.wasm` module which exports `memory` and `shoal_alloc(len: i32) -> i32`,
+//! plus any of the following hooks:
+//!
+//! - `shoal_preprocess(ptr: i32, width: i32, height: i32)`:
+//!   Modify the luma draft (`width * height` bytes, row-major) in place before matching.
+//! - `shoal_score(block: i32, glyph: i32, char: i32, wide: i32, score: f32) -> f32`:
+//!   Rescore a glyph candidate; `block` and `glyph` point to `f32 * 10` features (little-endian),
+//!   `score` is the similarity computed by Shoalart. Lower is better. `char` is the code point,
+//!   or for grapheme clusters negative: `CLUSTER_FLAG` (the sign bit) with the first char.
+//! - `shoal_postprocess(ptr: i32, len: i32)`:
+//!   Modify the uncompressed `.shoal` payload (`u16` lines; each line `u16` cells;
+//!   each cell `flags/u8` + repeats `u16` if `flags & 16` + `rgb/u8*3` + background `rgb/u8*3`
+//!   if `flags & 1` + `char/u32`, big-endian) in place. Sizes and flags must not be changed.
+//!   Clusters are `CLUSTER_FLAG | len` instead of `char/u32`, followed by `cluster/utf8*len`.
+
+use crate::*;
+use cluster::Glyph;
+use image::GrayImage;
+
+pub const PLUGIN_SUFFIX: &str = "wasm";
+
+/// Loaded plugins, hooks are applied in the order they were specified.
+pub struct Plugins(
+    #[cfg(feature = "plugin")] Vec<imp::Plugin>,
+    #[cfg(not(feature = "plugin"))] (),
+);
+
+impl Plugins {
+    pub fn none() -> Self {
+        #[cfg(feature = "plugin")]
+        return Plugins(Vec::with_capacity(0));
+        #[cfg(not(feature = "plugin"))]
+        return Plugins(());
+    }
+
+    /// Each name is either a path to a `.wasm` file, or a plugin name looked up in `dir`.
+    pub fn load<P: AsRef<Path>>(names: &[String], dir: P) -> Result<Self, String> {
+        if names.is_empty() {
+            return Ok(Self::none());
+        }
+        let dir = dir.as_ref();
+        let mut paths = Vec::with_capacity(names.len());
+        for name in names {
+            let p = match PathBuf::from(name) {
+                p if p.is_file() => p,
+                _ => dir.join(format!("{}.{}", name, PLUGIN_SUFFIX)),
+            };
+            if !p.is_file() {
+                Err(format!(
+                    "Plugin \"{}\" not found; Available in \"{}\": [{}]",
+                    name,
+                    dir.to_string_lossy(),
+                    discover(dir).join(", ")
+                ))?;
+            }
+            paths.push((name, p));
+        }
+        #[cfg(not(feature = "plugin"))]
+        return Err(format!(
+            "Failed to load {} plugin(s): Shoalart was built without `plugin` feature",
+            paths.len()
+        ));
+        #[cfg(feature = "plugin")]
+        return paths
+            .into_iter()
+            .map(|(name, p)| imp::Plugin::load(name, &p))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Plugins);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "plugin")]
+        return self.0.is_empty();
+        #[cfg(not(feature = "plugin"))]
+        return true;
+    }
+
+    pub fn has_score(&self) -> bool {
+        #[cfg(feature = "plugin")]
+        return self.0.iter().any(|p| p.score.is_some());
+        #[cfg(not(feature = "plugin"))]
+        return false;
+    }
+
+    pub fn preprocess(&mut self, _img: &mut GrayImage) -> Result<(), String> {
+        #[cfg(feature = "plugin")]
+        for p in &mut self.0 {
+            p.preprocess(_img)?;
+        }
+        return Ok(());
+    }
+
+    pub fn score(
+        &mut self,
+        _block: &[f32; 10],
+        _glyph: &[f32; 10],
+        _c: Glyph,
+        _wide: bool,
+        score: f32,
+    ) -> Result<f32, String> {
+        #[allow(unused_mut)]
+        let mut score = score;
+        #[cfg(feature = "plugin")]
+        for p in &mut self.0 {
+            score = p.score(_block, _glyph, _c, _wide, score)?;
+        }
+        return Ok(score);
+    }
+
+    pub fn postprocess(&mut self, _payload: &mut [u8]) -> Result<(), String> {
+        #[cfg(feature = "plugin")]
+        for p in &mut self.0 {
+            p.postprocess(_payload)?;
+        }
+        return Ok(());
+    }
+}
+
+/// Names of plugins in the dir, sorted.
+pub fn discover<P: AsRef<Path>>(dir: P) -> Vec<String> {
+    let mut names = match std::fs::read_dir(dir) {
+        Ok(d) => d
+            .filter_map(|d| d.ok().map(|d| d.path()))
+            .filter(|p| p.extension().map_or(false, |e| e == PLUGIN_SUFFIX))
+            .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::with_capacity(0),
+    };
+    names.sort_unstable();
+    return names;
+}
+
+#[cfg(feature = "plugin")]
+mod imp {
+    use super::*;
+    use wasmi::{core::F32, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+    pub struct Plugin {
+        name: String,
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        /// Reusable buffer in plugin memory, `(ptr, cap)`
+        buf: (i32, usize),
+        /// Feature buffers for scoring, `f32 * 10` each
+        feat: (i32, i32),
+        pub preprocess: Option<TypedFunc<(i32, i32, i32), ()>>,
+        pub score: Option<TypedFunc<(i32, i32, i32, i32, F32), F32>>,
+        pub postprocess: Option<TypedFunc<(i32, i32), ()>>,
+    }
+
+    impl Plugin {
+        pub fn load(name: &str, p: &Path) -> Result<Self, String> {
+            let err = |e: &dyn std::fmt::Display| format!("Plugin \"{}\": {}", name, e);
+            let bytes = match std::fs::read(p) {
+                Ok(b) => b,
+                Err(e) => Err(err(&e))?,
+            };
+            let engine = Engine::default();
+            let module = Module::new(&engine, &bytes[..]).map_err(|e| err(&e))?;
+            let mut store = Store::new(&engine, ());
+            let instance = <Linker<()>>::new(&engine)
+                .instantiate(&mut store, &module)
+                .and_then(|i| i.start(&mut store))
+                .map_err(|e| err(&e))?;
+            let memory = match instance.get_memory(&store, "memory") {
+                Some(m) => m,
+                None => Err(err(&"Missing export `memory`"))?,
+            };
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&store, "shoal_alloc")
+                .map_err(|e| err(&e))?;
+            let preprocess = instance.get_typed_func(&store, "shoal_preprocess").ok();
+            let score = instance.get_typed_func(&store, "shoal_score").ok();
+            let postprocess = instance.get_typed_func(&store, "shoal_postprocess").ok();
+            let mut plugin = Plugin {
+                name: name.to_string(),
+                store,
+                memory,
+                alloc,
+                buf: (0, 0),
+                feat: (0, 0),
+                preprocess,
+                score,
+                postprocess,
+            };
+            if plugin.score.is_some() {
+                plugin.feat = (plugin.alloc(40)?, plugin.alloc(40)?);
+            }
+            return Ok(plugin);
+        }
+
+        fn err(&self, e: &dyn std::fmt::Display) -> String {
+            return format!("Plugin \"{}\": {}", self.name, e);
+        }
+
+        fn alloc(&mut self, len: usize) -> Result<i32, String> {
+            return match self.alloc.call(&mut self.store, len as i32) {
+                Ok(ptr) if ptr > 0 => Ok(ptr),
+                Ok(_) => Err(self.err(&"`shoal_alloc` returned null")),
+                Err(e) => Err(self.err(&e)),
+            };
+        }
+
+        fn write(&mut self, ptr: i32, data: &[u8]) -> Result<(), String> {
+            return match self.memory.write(&mut self.store, ptr as usize, data) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(self.err(&e)),
+            };
+        }
+
+        fn read(&self, ptr: i32, data: &mut [u8]) -> Result<(), String> {
+            return match self.memory.read(&self.store, ptr as usize, data) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(self.err(&e)),
+            };
+        }
+
+        /// Copy data into the reusable buffer, which grows on demand.
+        fn stage(&mut self, data: &[u8]) -> Result<i32, String> {
+            if self.buf.1 < data.len() {
+                self.buf = (self.alloc(data.len())?, data.len());
+            }
+            self.write(self.buf.0, data)?;
+            return Ok(self.buf.0);
+        }
+
+        pub fn preprocess(&mut self, img: &mut GrayImage) -> Result<(), String> {
+            if let Some(f) = self.preprocess {
+                let (w, h) = img.dimensions();
+                let ptr = self.stage(img.as_raw())?;
+                if let Err(e) = f.call(&mut self.store, (ptr, w as i32, h as i32)) {
+                    Err(self.err(&e))?;
+                }
+                self.read(ptr, &mut *img)?;
+            }
+            return Ok(());
+        }
+
+        pub fn score(
+            &mut self,
+            block: &[f32; 10],
+            glyph: &[f32; 10],
+            c: Glyph,
+            wide: bool,
+            score: f32,
+        ) -> Result<f32, String> {
+            return match self.score {
+                None => Ok(score),
+                Some(f) => {
+                    let mut buf = [0u8; 40];
+                    let (pb, pg) = self.feat;
+                    for (feat, ptr) in [(block, pb), (glyph, pg)] {
+                        feat.iter().enumerate().for_each(|(i, n)| {
+                            buf[i * 4..i * 4 + 4].copy_from_slice(&n.to_le_bytes())
+                        });
+                        self.write(ptr, &buf)?;
+                    }
+                    let c = match c.char() {
+                        Some(c) => c as u32,
+                        None => cluster::CLUSTER_FLAG | c.first() as u32,
+                    };
+                    let args = (pb, pg, c as i32, wide as i32, F32::from(score));
+                    f.call(&mut self.store, args)
+                        .map(f32::from)
+                        .map_err(|e| self.err(&e))
+                }
+            };
+        }
+
+        pub fn postprocess(&mut self, payload: &mut [u8]) -> Result<(), String> {
+            if let Some(f) = self.postprocess {
+                let ptr = self.stage(payload)?;
+                if let Err(e) = f.call(&mut self.store, (ptr, payload.len() as i32)) {
+                    Err(self.err(&e))?;
+                }
+                self.read(ptr, payload)?;
+            }
+            return Ok(());
+        }
+    }
+}
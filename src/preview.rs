@@ -0,0 +1,113 @@
+//! Inline images on terminals, by the Kitty graphics protocol or iTerm2 escape sequences.
+
+use image::{imageops::Triangle, DynamicImage, GenericImageView, ImageOutputFormat};
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+/// Escape sequences understood by terminals to show images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Kitty graphics protocol, also supported by WezTerm, Konsole and Ghostty
+    Kitty,
+    /// Inline images of iTerm2, also supported by WezTerm and mintty
+    Iterm2,
+}
+
+impl Protocol {
+    pub const NAMES: &'static [&'static str] = &["kitty", "iterm2"];
+
+    /// Guess by environment variables; `None` if neither is likely supported.
+    pub fn detect() -> Option<Protocol> {
+        let var = |k: &str| std::env::var(k).unwrap_or_default();
+        return if std::env::var_os("KITTY_WINDOW_ID").is_some() || var("TERM") == "xterm-kitty" {
+            Some(Protocol::Kitty)
+        } else if matches!(&*var("TERM_PROGRAM"), "iTerm.app" | "WezTerm" | "mintty") {
+            Some(Protocol::Iterm2)
+        } else if var("TERM") == "xterm-ghostty" || var("KONSOLE_VERSION") != "" {
+            Some(Protocol::Kitty)
+        } else {
+            None
+        };
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "kitty" => Ok(Protocol::Kitty),
+            "iterm2" | "iterm" => Ok(Protocol::Iterm2),
+            _ => Err(format!("Unknown protocol \"{}\"", s)),
+        };
+    }
+}
+
+/// Size of base64 chunks in Kitty graphics protocol.
+const KITTY_CHUNK: usize = 4096;
+
+/// Show the image at the cursor as PNG, then move to the next line.
+///
+/// Images wider than `max_width` are shrunk proportionally, others are shown in their own size.
+pub fn show<W: Write>(
+    w: &mut W,
+    img: &DynamicImage,
+    max_width: u32,
+    protocol: Protocol,
+) -> io::Result<()> {
+    let shrunk;
+    let img = match img.width() > max_width {
+        true => {
+            let h = (img.height() as u64 * max_width as u64 / img.width() as u64).max(1);
+            shrunk = img.resize_exact(max_width, h as u32, Triangle);
+            &shrunk
+        }
+        false => img,
+    };
+    let mut png = Vec::with_capacity(1 << 16);
+    if let Err(e) = img.write_to(&mut png, ImageOutputFormat::Png) {
+        Err(io::Error::new(io::ErrorKind::Other, e))?;
+    }
+    let data = base64(&png);
+    match protocol {
+        Protocol::Kitty => {
+            let chunks = data.as_bytes().chunks(KITTY_CHUNK);
+            let last = chunks.len() - 1;
+            for (i, chunk) in chunks.enumerate() {
+                match i {
+                    0 => w.write_all(b"\x1b_Ga=T,f=100,")?,
+                    _ => w.write_all(b"\x1b_G")?,
+                }
+                write!(w, "m={};", (i != last) as u8)?;
+                w.write_all(chunk)?;
+                w.write_all(b"\x1b\\")?;
+            }
+        }
+        Protocol::Iterm2 => {
+            write!(w, "\x1b]1337;File=inline=1;size={}:{}\x07", png.len(), data)?;
+        }
+    }
+    return w.write_all(b"\n");
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with paddings.
+fn base64(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - i * 8));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => s.push(BASE64[(n >> (18 - i * 6) & 0x3f) as usize] as char),
+                false => s.push('='),
+            }
+        }
+    }
+    return s;
+}
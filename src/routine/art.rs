@@ -1,21 +1,43 @@
+use super::edgedet;
 use crate::*;
 use crossterm::{
     cursor::{Hide as HideCursor, MoveTo, MoveToNextLine, Show as ShowCursor},
     queue,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
     terminal::{
-        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
+    tty::IsTty,
 };
 use image::{
-    imageops::{self, Lanczos3, Triangle},
-    GrayImage, Luma, Rgb, RgbImage,
+    imageops::{Lanczos3, Triangle},
+    DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage,
 };
+use rayon::prelude::*;
+use rusttype::Font;
 use scrap;
+use shoalart::{
+    ansi,
+    art::{
+        self, clear_transparent, glyph_width, grade_colors, make_art, make_halfblock, paint_bg,
+        play_art, play_diff, play_vertical, print_art, read_anim_stamped, read_art,
+        read_art_stamped, smooth_colors, snap_colors, write_anim_as, write_anim_stamped,
+        write_art_as, write_art_stamped, Art, Frame,
+    },
+    audio,
+    charset::{read_charset, Charset},
+    cluster, export, live, preview, serve, subtitle, video,
+};
 use std::{
-    fs::File,
-    io::{self, stdout, Read, Write},
+    collections::BTreeMap,
+    io::{self, stdout, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
     time::{Duration, Instant},
 };
 
@@ -23,7 +45,13 @@ use std::{
 #[derive(StructOpt, Debug)]
 pub enum Param {
     Make(ParamMake),
+    Preview(ParamPreview),
     Play(ParamPlay),
+    Export(ParamExport),
+    Render(ParamRender),
+    Live(ParamLive),
+    Serve(ParamServe),
+    Import(ParamImport),
 }
 
 /// Create ASCII Art for images from Charset
@@ -31,10 +59,13 @@ pub enum Param {
 /// Use a unique format for storage, which suffixed with `.shoal` and included colors.
 #[derive(StructOpt, Debug)]
 pub struct ParamMake {
+    /// Videos (`.mp4`, `.mkv`, etc.) are decoded by `ffmpeg` into frames;
+    /// So are animated GIF/APNG, but by Shoalart itself
     #[structopt(parse(from_os_str))]
     image_dir_or_file: PathBuf,
-    #[structopt(parse(from_os_str))]
-    output_dir_or_file: PathBuf,
+    /// Frames are packed into one file if suffixed with `.shoala`; Omitted with `stdout`
+    #[structopt(parse(from_os_str), required_unless = "stdout")]
+    output_dir_or_file: Option<PathBuf>,
     /// Linking color
     ///
     /// NOTICE: If the two are not the same size, then the colorize image
@@ -46,10 +77,25 @@ pub struct ParamMake {
     /// Colorize image will be also `crop` then `resize`.
     #[structopt(long = "color", default_value = "", parse(from_os_str))]
     colorize_dir_or_file: PathBuf,
+    /// Populate backgrounds of cells, by a color or an image; Ignored in `halfblock` mode
+    ///
+    /// Syntax: `#{rrggbb}` (hexadecimal), or path to an image, which will be also `crop` then `resize`.
+    #[structopt(long)]
+    bg_from: Option<String>,
 
     /// Charset to be used; Bulit-in `chars/ASCII+font/Sarasa-Term-SC` by default
     #[structopt(short, long, parse(from_os_str))]
     charset: Option<PathBuf>,
+    /// Drop glyphs of the charset, e.g. those rendering poorly in your font
+    ///
+    /// Syntax: Characters to drop, e.g. `"_\`"`
+    #[structopt(long)]
+    exclude: Option<String>,
+    /// Use only glyphs of the charset which are also in this
+    ///
+    /// Syntax: Characters to use
+    #[structopt(long)]
+    only: Option<String>,
 
     /// Crop images before resize; No cropping by default
     ///
@@ -64,10 +110,177 @@ pub struct ParamMake {
     /// Conflicted with `resize`, but proportionally; Float
     #[structopt(short, long)]
     zoom: Option<f32>,
+    /// Resize images proportionally to fit in your terminal at make time, leaving a row for
+    /// the prompt; Conflicted with `resize` and `zoom`
+    #[structopt(long, conflicts_with_all = &["resize", "zoom"])]
+    fit_terminal: bool,
+    /// Aspect ratio of cells of your terminal, heights of images are scaled after resize so that
+    /// arts look in proportion; No scaling by default, i.e. `1:2`
+    ///
+    /// Syntax: `{width}:{height}` (Positive floats only)
+    #[structopt(long, parse(try_from_str = opt_ratio))]
+    cell_aspect: Option<(f32, f32)>,
+    /// Size of blocks each half-width cell comes from, which are resampled to `4x8` before
+    /// matching; Larger ones trade resolution for fidelity; That of the charset by default
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_cell))]
+    cell: Option<(u32, u32)>,
+    /// Extend the right and bottom of images with the color to whole cells, so that edges
+    /// are kept; The remainder would be dropped otherwise
+    ///
+    /// Syntax: `#{rrggbb}` (hexadecimal)
+    #[structopt(long, default_value = "#000000", parse(try_from_str = opt_rgb))]
+    pad_color: [u8; 3],
+    /// Width of East Asian ambiguous chars on your terminal, e.g. `○` and `─`;
+    /// `wide` for CJK terminals drawing them in two columns
+    #[structopt(long, default_value = "narrow", possible_values = art::Ambiguous::NAMES)]
+    ambiguous: art::Ambiguous,
+    /// Resample video input to the frame rate, which is also stored in `.shoala`;
+    /// Original frame rate by default
+    #[structopt(short, long)]
+    fps: Option<f32>,
+    /// Durations of frames in ms, one per line; Override those of sources, e.g. for frames
+    /// extracted from variable frame rate videos
+    ///
+    /// Stored in `.shoala`, or copied to the sidecar `{output_dir}.timing` of the output dir.
+    #[structopt(long, parse(from_os_str))]
+    timing: Option<PathBuf>,
 
+    /// How images are converted: `glyph` matches blocks against the charset;
+    /// `halfblock` draws `▀` in two colors, doubling vertical resolution
+    #[structopt(long, default_value = "glyph", possible_values = art::Mode::NAMES)]
+    mode: art::Mode,
     /// Invert dark and light; Not recommended for use
     #[structopt(short, long)]
     negate: bool,
+    /// Diffuse brightness errors of chosen glyphs into neighboring blocks, for smoother gradients;
+    /// Ignored in `halfblock` mode
+    #[structopt(long)]
+    dither: bool,
+    /// Add to luma of drafts before matching, within `-1..=1`; Ignored in `halfblock` mode
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    brightness: f32,
+    /// Scale luma of drafts around the middle gray before matching
+    #[structopt(long, default_value = "1")]
+    contrast: f32,
+    /// Gamma of luma of drafts before matching, larger is brighter
+    #[structopt(long, default_value = "1")]
+    gamma: f32,
+    /// Stretch luma of drafts so that the darkest and the brightest become black and white;
+    /// Ignored in `halfblock` mode
+    #[structopt(long)]
+    auto_contrast: bool,
+    /// Weight of levels of each frame in `auto-contrast`, smaller keeps brightness from pumping
+    /// between frames; `1` stretches frames independently, otherwise frames are made one by one
+    #[structopt(long, default_value = "0.1")]
+    auto_contrast_smooth: f32,
+    /// Equalize histograms of drafts, which varies glyphs of low-contrast footage: `global`;
+    /// `clahe` equalizes tiles with limited contrast, keeping local details; `global` if no value
+    #[structopt(long, possible_values = Equalize::NAMES)]
+    equalize: Option<Option<Equalize>>,
+    /// Match edges detected by Canny instead of images, like `edgedet` then `art make` but
+    /// without intermediate files; Colors still come from images
+    #[structopt(long)]
+    edgedet: bool,
+    /// Blend edges detected by Canny into drafts by the weight within `0..=1`,
+    /// so that strokes follow contours; Ignored in `halfblock` mode
+    #[structopt(long, default_value = "0")]
+    edge_weight: f32,
+    /// Sigma of Canny, see `edgedet`
+    #[structopt(long, default_value = "2.35")]
+    sigma: f32,
+    /// Strong threshold of Canny, see `edgedet`
+    #[structopt(long = "strong", default_value = "0.18")]
+    thr_strong: f32,
+    /// Weak threshold of Canny, see `edgedet`
+    #[structopt(long = "weak", default_value = "0.08")]
+    thr_weak: f32,
+    /// Keep glyphs of the previous frame, unless a candidate scores lower by the fraction
+    /// (e.g. `0.1`), which suppresses shimmer of animations; Frames are made one by one then
+    #[structopt(long, default_value = "0")]
+    stabilize: f32,
+    /// Weight of gradient orientations in matching (e.g. `0.5`), so that `/`, `\`, `|` and `_`
+    /// follow local strokes; Charsets generated before are approximated
+    #[structopt(long, default_value = "0")]
+    orient_weight: f32,
+    /// Weights of the 10 DCT features for the `weighted` metric, from the mean brightness on:
+    /// `uniform`; `contrast` favors brightness; `structure` favors outlines; Or custom.
+    ///
+    /// Syntax: `{preset}`, or `{w0},{w1},...,{w9}` (Non-negative numbers only)
+    #[structopt(long, default_value = "uniform")]
+    weights: art::Weights,
+    /// File of multipliers of scores per glyph, nudging results toward (`< 1`) or away from
+    /// (`> 1`) them, e.g. `@#%=0.8` for a denser look
+    ///
+    /// Syntax: One `{chars}={multiplier}` per line
+    #[structopt(long, parse(from_os_str))]
+    bias: Option<PathBuf>,
+    /// How blocks are compared with glyphs: `weighted` L1 by `weights`; `l1`; `l2` penalizes
+    /// brightness offsets more; `cosine` ignores scales, which suits edge-detected inputs
+    #[structopt(long, default_value = "weighted", possible_values = algorithm::Metric::NAMES)]
+    metric: algorithm::Metric,
+    /// Match each flat region of up to the size in px (`16` or `32`) only once then repeat,
+    /// which speeds up mostly flat frames; Conversions are exhaustive by default
+    #[structopt(long, possible_values = &["16", "32"])]
+    quadtree: Option<u32>,
+    /// Standard deviation of luma below which regions are flat, see `quadtree`
+    #[structopt(long, default_value = "4")]
+    flat_threshold: f32,
+    /// Make cells bold whose blocks have more details than this (e.g. `0.3`), i.e. AC energy
+    /// of DCT per px, so that strokes pop on terminals where bold changes weight
+    #[structopt(long)]
+    bold_threshold: Option<f32>,
+    /// Half-width character for empty regions instead of spaces, e.g. `·` or `░`
+    #[structopt(long)]
+    blank_char: Option<char>,
+    /// Blocks darker than this in mean luma (within `0..=255`) also become `blank-char`;
+    /// Only those matched as spaces by default
+    #[structopt(long, default_value = "0")]
+    blank_threshold: f32,
+    /// Only full-width glyphs, matched column by column from right to left, each from top
+    /// to bottom, as vertical CJK text is set; Play with `--vertical` to draw them in that order
+    #[structopt(long)]
+    vertical: bool,
+    /// How colors of cells are taken: `average` of blocks; `dominant` colors of blocks,
+    /// which keeps high-contrast ones (e.g. red text on white) vivid
+    #[structopt(long, default_value = "average", possible_values = art::CellColor::NAMES)]
+    cell_color: art::CellColor,
+    /// Average colors of cells in linear light, more faithful but slower
+    #[structopt(long)]
+    linear_color: bool,
+    /// Scale saturation of colors of cells (e.g. `1.3`), which look duller on terminals;
+    /// `0` is grayscale
+    #[structopt(long, default_value = "1")]
+    saturation: f32,
+    /// Rotate hues of colors of cells by degrees
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    hue_shift: f32,
+    /// Smooth colors of cells across frames, which flicker from noise of sensors: Each moves toward
+    /// the current by the weight (e.g. `0.3`); Frames are made one by one then
+    #[structopt(long, default_value = "0")]
+    smooth_color: f32,
+    /// Snap colors of cells to the nearest in the palette, for looks like Game Boy or CGA:
+    /// Either a GIMP palette (`.gpl`), or one hex color per line
+    #[structopt(long, parse(from_os_str))]
+    palette: Option<PathBuf>,
+    /// Colors changing more than this in any channel are taken at once, without smoothing
+    #[structopt(long, default_value = "32")]
+    smooth_threshold: u8,
+    /// Handle transparency of images, which is discarded by default: `skip` emits spaces for
+    /// mostly transparent cells; `threshold:{N}` matches the shape by alpha, opaque if at least N;
+    /// `matte:#{rrggbb}` composites images onto the color
+    #[structopt(long)]
+    alpha: Option<Alpha>,
+
+    /// Apply WASM plugin, by name in plugins dir or by path; Can be specified multiple times
+    ///
+    /// NOTICE: Available only if Shoalart was built with `plugin` feature.
+    #[structopt(long = "plugin")]
+    plugins: Vec<String>,
+    /// Specify the plugins dir
+    #[structopt(long, default_value = "Shoalart-Plugins", parse(from_os_str))]
+    plugin_dir: PathBuf,
 
     /// Specify the value of skipping first N COLOR files
     #[structopt(long = "skip", default_value = "0")]
@@ -79,16 +292,124 @@ pub struct ParamMake {
     #[structopt(long = "ctr", default_value = "1")]
     i_ctr: u32,
 
+    /// Number of worker threads; All logical CPUs by default
+    #[structopt(short, long, default_value = "0")]
+    jobs: usize,
+    /// Compression of outputs; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+    /// Save no colors, for monochrome play, which makes outputs several times smaller;
+    /// Played in white if colored
+    #[structopt(long)]
+    no_color: bool,
+    /// Save colors as gray, which halves outputs of black-and-white footage
+    #[structopt(long, conflicts_with = "no-color")]
+    gray: bool,
+    /// Save colors as indices into a palette of each frame, which makes outputs much smaller
+    /// unless frames are colorful
+    #[structopt(long, conflicts_with_all = &["no-color", "gray"])]
+    indexed: bool,
+    /// Stamp outputs with the charset used, i.e. its name, CRC-32 and glyphs, for `shoal info`
+    /// and `art play --font`; Older versions cannot read stamped outputs
+    #[structopt(long)]
+    stamp_charset: bool,
+    /// Print results to stdout in the format instead of saving them, then progress goes to stderr;
+    /// Formats without animation take a single image only
+    #[structopt(long, possible_values = export::Format::NAMES)]
+    stdout: Option<export::Format>,
+    /// Play frames on your terminal as soon as they are made, while still saved, so that long
+    /// videos can be watched during conversion; Press ESC to stop
+    #[structopt(long, conflicts_with_all = &["stdout", "preview"])]
+    play: bool,
+
+    /// Show sources and results inline on your terminal as they are made, for tuning parameters;
+    /// By the protocol guessed from environment variables if not specified
+    #[structopt(long, possible_values = preview::Protocol::NAMES)]
+    preview: Option<Option<preview::Protocol>>,
+    /// Font to rasterize results for `preview`; Results are printed as text without it
+    #[structopt(long, parse(from_os_str))]
+    preview_font: Option<PathBuf>,
+
     /// Verbose mode (-v, -vv, -vvv, etc.)
     #[structopt(short, long, parse(from_occurrences))]
     verbose: u8,
 }
 
+/// Convert an image in memory and print it on your terminal, with the parameters chosen,
+/// for tuning them before a large batch of `art make`
+#[derive(StructOpt, Debug)]
+pub struct ParamPreview {
+    #[structopt(parse(from_os_str))]
+    image_file: PathBuf,
+
+    /// Charset to be used; Bulit-in `chars/ASCII+font/Sarasa-Term-SC` by default
+    #[structopt(short, long, parse(from_os_str))]
+    charset: Option<PathBuf>,
+
+    /// Crop the image before resize; No cropping by default
+    ///
+    /// Syntax: `{width}x{height}+{left}+{top}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_crop))]
+    crop: Option<(u32, u32, u32, u32)>,
+    /// Resize the image proportionally; Fit in your terminal if neither this nor `zoom`
+    ///
+    /// Syntax: `{nwidth}x{nheight}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_resize))]
+    resize: Option<(u32, u32)>,
+    /// Conflicted with `resize`, but proportionally; Float
+    #[structopt(short, long, conflicts_with = "resize")]
+    zoom: Option<f32>,
+    /// Aspect ratio of cells of your terminal, see `art make`
+    ///
+    /// Syntax: `{width}:{height}` (Positive floats only)
+    #[structopt(long, parse(try_from_str = opt_ratio))]
+    cell_aspect: Option<(f32, f32)>,
+    /// Size of blocks each half-width cell comes from, see `art make`
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_cell))]
+    cell: Option<(u32, u32)>,
+
+    /// How the image is converted, see `art make`
+    #[structopt(long, default_value = "glyph", possible_values = art::Mode::NAMES)]
+    mode: art::Mode,
+    /// Invert dark and light; Not recommended for use
+    #[structopt(short, long)]
+    negate: bool,
+    /// Diffuse brightness errors of chosen glyphs, see `art make`
+    #[structopt(long)]
+    dither: bool,
+    /// Weights of DCT features in matching, see `art make`
+    #[structopt(long, default_value = "uniform")]
+    weights: art::Weights,
+    /// How blocks are compared with glyphs, see `art make`
+    #[structopt(long, default_value = "weighted", possible_values = algorithm::Metric::NAMES)]
+    metric: algorithm::Metric,
+
+    /// Use no color on your terminal
+    #[structopt(short, long = "monoch")]
+    monoch: bool,
+    /// Colors supported by your terminal, others are approximated
+    #[structopt(long, default_value = "auto", possible_values = art::Colors::NAMES)]
+    colors: art::Colors,
+}
+
 /// Play ASCII animation on your terminal
+///
+/// Press SPACE to pause or resume, LEFT/RIGHT to step while paused, PGUP/PGDN to skip 50 frames,
+/// 0-9 to jump to the tenth, and ESC to quit.
 #[derive(StructOpt, Debug)]
 pub struct ParamPlay {
-    #[structopt(parse(from_os_str))]
-    shoal_dir_or_file: PathBuf,
+    /// Played back to back; Each is a `.shoala` animation, a `.shoal` file, a dir of `.shoal` files,
+    /// or a playlist (`.m3u`) of them
+    ///
+    /// In playlists, one path per line, relative to the playlist; `#FPS:{fps}` before a path
+    /// overrides `--fps` for it, other lines starting with `#` are ignored.
+    ///
+    /// Durations of frames in a dir are read from its sidecar `{dir}.timing` if exists,
+    /// one per line in ms.
+    #[structopt(required = true, parse(from_os_str))]
+    shoal_dirs_or_files: Vec<PathBuf>,
 
     /// Set the left mergin of animation
     #[structopt(short = "x", default_value = "0")]
@@ -97,11 +418,30 @@ pub struct ParamPlay {
     #[structopt(short = "y", default_value = "0")]
     sy: u16,
 
-    /// Maximum frame rate during play
+    /// Maximum frame rate during play; Overridden by delays stored in `.shoala` or sidecars,
+    /// and playlists
     ///
     /// On Windows: A too large value (about 5) may prevent the art from being fully captured!
     #[structopt(short = "f", long = "fps", default_value = "5")]
     max_fps: f32,
+    /// Skip frames which are already late, instead of slowing down, when your terminal can not
+    /// keep up with the frame rate; The number of dropped frames is reported at exit
+    #[structopt(long)]
+    drop_frames: bool,
+    /// Play an audio track along, frames are dropped or delayed to keep up with it
+    ///
+    /// NOTICE: Available only if Shoalart was built with `audio` feature.
+    #[structopt(long, parse(from_os_str))]
+    audio: Option<PathBuf>,
+    /// Show subtitles of a `.srt` file in the bottom row of your terminal, timed by frames;
+    /// Art is cut short to keep the row
+    #[structopt(long, parse(from_os_str))]
+    subs: Option<PathBuf>,
+    /// Color of subtitles
+    ///
+    /// Syntax: `#{rrggbb}` (hexadecimal)
+    #[structopt(long, default_value = "#ffff00", parse(try_from_str = opt_rgb))]
+    subs_color: [u8; 3],
     /// Enable capture function; Take screenshot for each frame then save it
     #[structopt(short, long, parse(from_os_str))]
     capture: Option<PathBuf>,
@@ -109,140 +449,252 @@ pub struct ParamPlay {
     /// Use no color on your terminal
     #[structopt(short, long = "monoch")]
     monoch: bool,
+    /// Colors supported by your terminal, others are approximated
+    #[structopt(long, default_value = "auto", possible_values = art::Colors::NAMES)]
+    colors: art::Colors,
+    /// Width of East Asian ambiguous chars on your terminal, see `art make`
+    #[structopt(long, default_value = "narrow", possible_values = art::Ambiguous::NAMES)]
+    ambiguous: art::Ambiguous,
+    /// Draw frames column by column from right to left, each from top to bottom,
+    /// as vertical CJK text is read, see `art make --vertical`
+    #[structopt(long)]
+    vertical: bool,
+    /// Font of your terminal, to check whether it covers all glyphs of charsets stamped
+    /// by `art make --stamp-charset` before playing
+    #[structopt(long, parse(from_os_str))]
+    font: Option<PathBuf>,
+    /// Do not wrap frames in synchronized updates (DEC mode 2026), which prevent flickering;
+    /// Terminals without it simply ignore them
+    #[structopt(long)]
+    no_sync: bool,
+    /// Show frame number, measured frame rate, dropped frames and file name in a corner
+    #[structopt(long, possible_values = Corner::NAMES)]
+    osd: Option<Option<Corner>>,
 
     /// Specify the start value of OUTPUT filename
     #[structopt(long = "ctr", default_value = "1")]
     i_ctr: u32,
 }
 
-////////////////////////////////////////
+/// Export ASCII art into formats readable without Shoalart
+#[derive(StructOpt, Debug)]
+pub struct ParamExport {
+    /// `.shoala` animation, `.shoal` file, or dir of `.shoal` files
+    #[structopt(parse(from_os_str))]
+    shoal_dir_or_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
 
-const ART_HEADER: &str = "Shoalart.v0 ART";
-const ART_HEADER_LEN: usize = ART_HEADER.len();
+    /// Output format
+    #[structopt(short, long, default_value = "ansi", possible_values = export::Format::NAMES)]
+    format: export::Format,
+    /// Frame to be exported, for formats without animation; Counting from 0
+    #[structopt(long, default_value = "0")]
+    frame: usize,
+    /// Frame rate of frames without delays, for formats with animation; 5 by default
+    #[structopt(long)]
+    fps: Option<f32>,
+}
 
-pub fn read_art<P: AsRef<Path>>(p: P) -> Result<Vec<Vec<([u8; 3], char)>>, String> {
-    let mut file = match File::open(p.as_ref()) {
-        Ok(f) => f,
-        Err(e) => Err(format!("Failed to open art: {:?}", e))?,
-    };
-    let mut buf: [u8; ART_HEADER_LEN] = unsafe_init!();
-    if let Err(e) = file.read_exact(&mut buf) {
-        Err(format!("Failed to read art: {:?}", e))?;
-    }
-    if &buf != ART_HEADER.as_bytes() {
-        Err(format!("Failed to parsing art: Invalid header"))?;
-    }
-    return match || -> io::Result<Vec<Vec<([u8; 3], char)>>> {
-        let mut comp = util::lz4read(file);
-        comp.read_exact(&mut buf[..2])?;
-        let h = u16::from_be_bytes(buf[..2].try_into().unwrap()) as usize;
-        let mut lines = Vec::<Vec<([u8; 3], char)>>::with_capacity(h);
-        for _ in 0..h {
-            comp.read_exact(&mut buf[..2])?;
-            let w = u16::from_be_bytes(buf[..2].try_into().unwrap()) as usize;
-            let mut line = Vec::<([u8; 3], char)>::with_capacity(w);
-            for _ in 0..w {
-                comp.read_exact(&mut buf[..7])?;
-                let rgb: [u8; 3] = (&buf[..3]).try_into().unwrap();
-                let c = unsafe {
-                    char::from_u32_unchecked(u32::from_be_bytes(buf[3..7].try_into().unwrap()))
-                };
-                line.push((rgb, c));
-            }
-            lines.push(line);
-        }
-        Ok(lines)
-    }() {
-        Ok(a) => Ok(a),
-        Err(e) => Err(format!("Failed to parsing art: {:?}", e)),
-    };
+/// Import hand-made ASCII or Unicode art from a text file into a `.shoal` file, to be played
+/// or packed with others
+///
+/// Classic ANSI art of CP437 and SGR colors is also supported, whose own colors are kept.
+#[derive(StructOpt, Debug)]
+pub struct ParamImport {
+    /// UTF-8 text, one line per row; Or ANSI art if suffixed with `.ans`
+    #[structopt(parse(from_os_str))]
+    text_file: PathBuf,
+    /// ANSI art clearing the screen for several frames are packed if suffixed with `.shoala`,
+    /// otherwise only the last frame is kept
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Color cells from the image, which is stretched over the whole art; White by default;
+    /// Ignored for ANSI art
+    #[structopt(long, parse(from_os_str))]
+    color: Option<PathBuf>,
+    /// How colors of cells are taken, see `art make`
+    #[structopt(long, default_value = "average", possible_values = art::CellColor::NAMES)]
+    cell_color: art::CellColor,
+    /// Average colors of cells in linear light, more faithful but slower
+    #[structopt(long)]
+    linear_color: bool,
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
 }
 
-pub fn play_art<W: Write>(
-    out: &mut W,
-    dat: &Vec<Vec<([u8; 3], char)>>,
+/// Rasterize ASCII art into an image with a font, without terminals
+#[derive(StructOpt, Debug)]
+pub struct ParamRender {
+    /// `.shoala` animation, `.shoal` file, or dir of `.shoal` files
+    #[structopt(parse(from_os_str))]
+    shoal_dir_or_file: PathBuf,
+    /// Encoded by the suffix; Sixel if suffixed with `.six` or `.sixel`, or `-` to print it
+    /// on your terminal
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Font to be used, preferably the one generated the charset from
+    #[structopt(short = "F", long, parse(from_os_str))]
+    font: PathBuf,
+    /// Size of a half-width cell
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, default_value = "8x16", parse(try_from_str = opt_cell))]
+    cell: (u32, u32),
+    /// Frame to be rendered; Counting from 0
+    #[structopt(long, default_value = "0")]
+    frame: usize,
+}
+
+/// Convert a live source into ASCII art on your terminal in real time, without saving
+#[derive(StructOpt, Debug)]
+pub struct ParamLive {
+    /// Capture a webcam, by index counting from 0; The first one by default
+    ///
+    /// NOTICE: Available only if Shoalart was built with `camera` feature.
+    #[structopt(long)]
+    camera: Option<Option<usize>>,
+    /// Mirror a display, by index counting from 0; The primary one by default
+    #[structopt(long, conflicts_with = "camera")]
+    screen: Option<Option<usize>>,
+
+    /// Charset to be used; Bulit-in `chars/ASCII+font/Sarasa-Term-SC` by default
+    #[structopt(short, long, parse(from_os_str))]
+    charset: Option<PathBuf>,
+
+    /// Crop frames before resize; No cropping by default
+    ///
+    /// Syntax: `{width}x{height}+{left}+{top}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_crop))]
+    crop: Option<(u32, u32, u32, u32)>,
+    /// Resize frames before process; No resizing by default
+    ///
+    /// Syntax: `{nwidth}x{nheight}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_resize))]
+    resize: Option<(u32, u32)>,
+    /// Conflicted with `resize`, but proportionally; Float
+    #[structopt(short, long)]
+    zoom: Option<f32>,
+    /// Aspect ratio of cells of your terminal, see `art make`
+    ///
+    /// Syntax: `{width}:{height}` (Positive floats only)
+    #[structopt(long, parse(try_from_str = opt_ratio))]
+    cell_aspect: Option<(f32, f32)>,
+    /// Size of blocks each half-width cell comes from, see `art make`
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_cell))]
+    cell: Option<(u32, u32)>,
+    /// Extend the right and bottom of frames with the color to whole cells, see `art make`
+    ///
+    /// Syntax: `#{rrggbb}` (hexadecimal)
+    #[structopt(long, default_value = "#000000", parse(try_from_str = opt_rgb))]
+    pad_color: [u8; 3],
+
+    /// How frames are converted, see `art make`
+    #[structopt(long, default_value = "glyph", possible_values = art::Mode::NAMES)]
+    mode: art::Mode,
+    /// Invert dark and light; Not recommended for use
+    #[structopt(short, long)]
+    negate: bool,
+    /// Diffuse brightness errors of chosen glyphs, see `art make`
+    #[structopt(long)]
+    dither: bool,
+    /// Keep glyphs of the previous frame unless a candidate is better enough, see `art make`
+    #[structopt(long, default_value = "0")]
+    stabilize: f32,
+    /// Weight of gradient orientations in matching, see `art make`
+    #[structopt(long, default_value = "0")]
+    orient_weight: f32,
+    /// Weights of DCT features in matching, see `art make`
+    #[structopt(long, default_value = "uniform")]
+    weights: art::Weights,
+    /// File of multipliers of scores per glyph, see `art make`
+    #[structopt(long, parse(from_os_str))]
+    bias: Option<PathBuf>,
+    /// How blocks are compared with glyphs, see `art make`
+    #[structopt(long, default_value = "weighted", possible_values = algorithm::Metric::NAMES)]
+    metric: algorithm::Metric,
+    /// Match each flat region only once then repeat, see `art make`
+    #[structopt(long, possible_values = &["16", "32"])]
+    quadtree: Option<u32>,
+    /// Standard deviation of luma below which regions are flat, see `art make`
+    #[structopt(long, default_value = "4")]
+    flat_threshold: f32,
+    /// Make cells bold whose blocks have more details than this, see `art make`
+    #[structopt(long)]
+    bold_threshold: Option<f32>,
+    /// Character for empty regions instead of spaces, see `art make`
+    #[structopt(long)]
+    blank_char: Option<char>,
+    /// Blocks darker than this also become `blank-char`, see `art make`
+    #[structopt(long, default_value = "0")]
+    blank_threshold: f32,
+    /// How colors of cells are taken, see `art make`
+    #[structopt(long, default_value = "average", possible_values = art::CellColor::NAMES)]
+    cell_color: art::CellColor,
+    /// Average colors of cells in linear light, more faithful but slower
+    #[structopt(long)]
+    linear_color: bool,
+    /// Smooth colors of cells across frames by the weight, see `art make`
+    #[structopt(long, default_value = "0")]
+    smooth_color: f32,
+    /// Colors changing more than this in any channel are taken at once, without smoothing
+    #[structopt(long, default_value = "32")]
+    smooth_threshold: u8,
+
+    /// Set the left mergin of art
+    #[structopt(short = "x", default_value = "0")]
     sx: u16,
+    /// Set the top mergin of art
+    #[structopt(short = "y", default_value = "0")]
     sy: u16,
+    /// Maximum frame rate
+    #[structopt(short = "f", long = "fps", default_value = "30")]
+    max_fps: f32,
+
+    /// Use no color on your terminal
+    #[structopt(short, long = "monoch")]
     monoch: bool,
-) -> io::Result<()> {
-    // queue!(out, Clear(ClearType::All))?;
-    let mut cc = [0u8, 0, 0];
-    for (y, line) in dat.iter().enumerate() {
-        queue!(out, MoveTo(sx, sy + y as u16))?;
-        for (c, w) in line {
-            if !monoch && *c != cc {
-                cc = c.clone();
-                let [r, g, b] = *c;
-                queue!(out, SetForegroundColor(Color::Rgb { r, g, b }))?;
-            }
-            queue!(out, Print(w))?;
-        }
-    }
-    return Ok(());
+    /// Colors supported by your terminal, others are approximated
+    #[structopt(long, default_value = "auto", possible_values = art::Colors::NAMES)]
+    colors: art::Colors,
+    /// Do not wrap frames in synchronized updates (DEC mode 2026)
+    #[structopt(long)]
+    no_sync: bool,
 }
 
-fn make_art<P: AsRef<Path>>(
-    draft: GrayImage,
-    color: RgbImage,
-    csh: &Vec<(char, [f32; 10])>,
-    csf: &Vec<(char, [f32; 10])>,
-    p: P,
-) -> io::Result<()> {
-    let mut file = File::create(p.as_ref())?;
-    file.write_all(ART_HEADER.as_bytes())?;
-    let w = draft.width();
-    let h = draft.height();
-    let mut comp = util::lz4write(file);
-    comp.write_all(&((h >> 3) as u16).to_be_bytes())?; // lines
-    let mut block: [[f32; 8]; 8] = unsafe_init!();
-    for y in (0..h).step_by(8) {
-        let mut x = 0;
-        let mut cache = Vec::<([u8; 3], char)>::with_capacity(w as usize >> 2);
-        while x < w - 4 {
-            let mut rank = Vec::<(char, bool, f32)>::with_capacity(csh.len() + csf.len());
-            let mut im = GrayImage::new(8, 8);
-            let wider = x < w - 8;
-            imageops::replace(
-                &mut im,
-                &imageops::crop_imm(&draft, x, y, if wider { 8 } else { 4 }, 8),
-                0,
-                0,
-            );
-            unsafe {
-                im.pixels().enumerate().for_each(|(i, Luma([n]))| {
-                    *block.as_mut_ptr().cast::<f32>().add(i) = *n as f32 / 128. - 1.
-                });
-            }
-            if wider {
-                let f = algorithm::dct_8x8_feature(&block);
-                csf.iter()
-                    .for_each(|(c, f2)| rank.push((*c, true, algorithm::similarity(&f, &f2))));
-            }
-            let f = algorithm::dct_4x8_feature(&block);
-            csh.iter()
-                .for_each(|(c, f2)| rank.push((*c, false, algorithm::similarity(&f, &f2))));
-            let &(c, w, _) = rank
-                .iter()
-                .min_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap())
-                .unwrap();
-            let Rgb(rgb) = *imageops::resize(
-                &imageops::crop_imm(&color, x, y, if wider { 8 } else { 4 }, 8).to_image(),
-                1,
-                1,
-                Triangle,
-            )
-            .get_pixel(0, 0);
-            cache.push((rgb, c));
-            x += if w { 8 } else { 4 };
-        }
-        comp.write_all(&(cache.len() as u16).to_be_bytes())?; // each line
-        for (rgb, c) in cache {
-            comp.write_all(&rgb)?;
-            comp.write_all(&(c as u32).to_be_bytes())?;
-        }
-    }
-    comp.finish()?;
-    return Ok(());
+/// Stream ASCII art to TCP/telnet clients in loop, e.g. `telnet localhost 2323`
+#[derive(StructOpt, Debug)]
+pub struct ParamServe {
+    /// `.shoala` animation, `.shoal` file, or dir of `.shoal` files
+    #[structopt(parse(from_os_str))]
+    shoal_dir_or_file: PathBuf,
+
+    /// Address to listen on; `0.0.0.0` for all interfaces
+    #[structopt(long, default_value = "127.0.0.1")]
+    bind: String,
+    /// Port to listen on
+    #[structopt(short, long, default_value = "2323")]
+    port: u16,
+    /// Clients served at once, others are refused
+    #[structopt(long, default_value = "16")]
+    max_clients: usize,
+
+    /// Frame rate; Overridden by delays stored in `.shoala`
+    #[structopt(short = "f", long = "fps", default_value = "10")]
+    fps: f32,
+
+    /// Use no color
+    #[structopt(short, long = "monoch")]
+    monoch: bool,
+    /// Colors sent to clients; `auto` asks telnet clients their terminal types,
+    /// and falls back to `256` for others
+    #[structopt(long, default_value = "auto", possible_values = art::Colors::NAMES)]
+    colors: art::Colors,
 }
 
 ////////////////////////////////////////
@@ -250,7 +702,13 @@ fn make_art<P: AsRef<Path>>(
 pub fn main(param: Param) {
     match param {
         Param::Make(param) => main_make(param),
+        Param::Preview(param) => main_preview(param),
         Param::Play(param) => main_play(param),
+        Param::Export(param) => main_export(param),
+        Param::Render(param) => main_render(param),
+        Param::Live(param) => main_live(param),
+        Param::Serve(param) => main_serve(param),
+        Param::Import(param) => main_import(param),
     }
 }
 
@@ -259,48 +717,226 @@ fn main_make(
         image_dir_or_file,
         output_dir_or_file,
         colorize_dir_or_file,
+        bg_from,
         charset,
+        exclude,
+        only,
         crop,
         resize,
         zoom,
+        fit_terminal,
+        cell_aspect,
+        cell,
+        pad_color,
+        ambiguous,
+        fps,
+        timing,
+        mode,
         negate,
+        dither,
+        brightness,
+        contrast,
+        gamma,
+        auto_contrast,
+        auto_contrast_smooth,
+        equalize,
+        edgedet,
+        edge_weight,
+        sigma,
+        thr_strong,
+        thr_weak,
+        stabilize,
+        orient_weight,
+        weights,
+        bias,
+        metric,
+        quadtree,
+        flat_threshold,
+        bold_threshold,
+        blank_char,
+        blank_threshold,
+        vertical,
+        cell_color,
+        linear_color,
+        saturation,
+        hue_shift,
+        smooth_color,
+        smooth_threshold,
+        palette,
+        alpha,
+        plugins,
+        plugin_dir,
         i_skip,
         i_step,
         i_ctr,
+        jobs,
+        compress,
+        no_color,
+        gray,
+        indexed,
+        stamp_charset,
+        stdout: to_stdout,
+        play,
+        preview,
+        preview_font,
         verbose,
     }: ParamMake,
 ) {
-    let mut csh = Vec::<(char, [f32; 10])>::with_capacity(0);
-    let mut csf = Vec::<(char, [f32; 10])>::with_capacity(0);
-    if let Some(p) = &charset {
-        println!("Use outer charset \"{}\".", p.to_string_lossy());
-        let cs = routine::charset::read_charset(p).unwrap();
-        csh.reserve_exact(cs.len());
-        csf.reserve_exact(cs.len());
-        for (c, (w, f)) in cs.into_iter() {
-            match w {
-                false => csh.push((c, f)),
-                true => csf.push((c, f)),
-            }
+    // Keep stdout clean for results.
+    let note = |msg: &str| match to_stdout {
+        Some(_) => eprint!("{}", msg),
+        None => print!("{}", msg),
+    };
+    if to_stdout.map_or(false, |f| !f.animated())
+        && (!image_dir_or_file.is_file() || video::is_video(&image_dir_or_file))
+    {
+        panic!("Formats without animation take a single image only for `stdout`")
+    }
+    // Each worker loads its own, fail fast here.
+    plugin::Plugins::load(&plugins, &plugin_dir).unwrap();
+    let mut cs = if let Some(p) = &charset {
+        let cs = read_charset(p).unwrap();
+        match &cs.meta {
+            Some(meta) => note(&format!(
+                "Use outer charset \"{}\", {}.\n",
+                p.to_string_lossy(),
+                meta.describe()
+            )),
+            None => note(&format!("Use outer charset \"{}\".\n", p.to_string_lossy())),
         }
+        cs
     } else {
-        println!("Use built-in charset.");
-        csh.reserve_exact(BULITIN_CHARSET.len());
-        csh.extend_from_slice(&BULITIN_CHARSET);
+        note("Use built-in charset.\n");
+        Charset::builtin()
+    };
+    if exclude.is_some() || only.is_some() {
+        let glyphs = |s: &String| cluster::split(s).collect::<AHashSet<_>>();
+        let (exclude, only) = (exclude.as_ref().map(glyphs), only.as_ref().map(glyphs));
+        cs.retain(|c| {
+            exclude.as_ref().map_or(true, |s| !s.contains(&c))
+                && only.as_ref().map_or(true, |s| s.contains(&c))
+        });
+        if cs.is_empty() {
+            panic!("No glyphs left in the charset after `exclude` and `only`");
+        }
+    }
+    // Glyphs generated for another `ambiguous` would break the layout.
+    let misfit = cs
+        .iter()
+        .filter(|(c, w, ..)| glyph_width(*c, ambiguous).map_or(false, |n| (n == 2) != *w))
+        .map(|(c, ..)| c)
+        .collect::<AHashSet<_>>();
+    if !misfit.is_empty() {
+        note(&format!(
+            "Dropped {} glyphs of another width, see `ambiguous`.\n",
+            misfit.len()
+        ));
+        cs.retain(|c| !misfit.contains(&c));
+    }
+    let stamp = (stamp_charset && mode == art::Mode::Glyph).then(|| {
+        let (source, hash) = match &charset {
+            Some(p) => (
+                p.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                util::crc32(&util::purify_err(
+                    &format!("Failed to access charset \"{}\"", p.to_string_lossy()),
+                    std::fs::read(p),
+                )),
+            ),
+            None => (String::from("built-in"), 0),
+        };
+        return art::CharsetStamp::new(&cs, source, hash);
+    });
+    // Blocks of other sizes are resampled, only the proportion matters.
+    if let Some((w, h)) = cell.filter(|(w, h)| w * cs.cell.1 != h * cs.cell.0) {
+        note(&format!(
+            "Cells of {}x{} are not in the proportion of {}x{} the charset was fitted in.\n",
+            w, h, cs.cell.0, cs.cell.1
+        ));
     }
+    let cell = cell.unwrap_or(cs.cell);
+    let bg = bg_from.map(|s| match s.starts_with('#') {
+        true => Background::Color(util::purify_err(
+            &format!("Invalid background \"{}\"", s),
+            opt_rgb(&s),
+        )),
+        false => Background::Image(util::purify_err(
+            &format!("Failed to open background \"{}\"", s),
+            image::open(&s),
+        )),
+    });
+    let preview = preview.map(|p| {
+        let protocol = util::purify_opt(
+            "Unknown whether your terminal shows inline images; Specify `--preview kitty` or `iterm2`",
+            p.or_else(preview::Protocol::detect),
+        );
+        let font = preview_font.map(|p| {
+            util::purify_opt(
+                &format!("Failed to open font \"{}\"", p.to_string_lossy()),
+                Font::try_from_vec(util::purify_err(
+                    &format!("Failed to access font \"{}\"", p.to_string_lossy()),
+                    std::fs::read(&p),
+                )),
+            )
+        });
+        (protocol, font)
+    });
+    // Each cell comes from a block of `cell`.
+    let resize = match fit_terminal {
+        true => {
+            let (cols, rows) = util::purify_err("Failed to query terminal size", terminal::size());
+            let rows = rows.saturating_sub(1);
+            note(&format!("Fit in {}x{} cells.\n", cols, rows));
+            Some((cols as u32 * cell.0, rows as u32 * cell.1))
+        }
+        false => resize,
+    };
+    let durations = timing.as_ref().map_or(Vec::with_capacity(0), |p| {
+        util::purify_err(
+            &format!("Invalid timing \"{}\"", p.to_string_lossy()),
+            art::read_timing(p),
+        )
+    });
+    let palette = palette.as_ref().map(|p| {
+        util::purify_err(
+            &format!("Invalid palette \"{}\"", p.to_string_lossy()),
+            art::read_palette(p),
+        )
+    });
+    let bias = bias.as_ref().map(|p| {
+        util::purify_err(
+            &format!("Invalid bias \"{}\"", p.to_string_lossy()),
+            art::read_bias(p),
+        )
+    });
+    let channels = match (no_color, gray, indexed) {
+        (true, _, _) => art::Channels::None,
+        (_, true, _) => art::Channels::Gray,
+        (_, _, true) => art::Channels::Indexed,
+        _ => art::Channels::Rgb,
+    };
     let verbose = verbose > 0;
-    let srcs: Box<dyn Iterator<Item = Result<PathBuf, String>>>;
-    let dsts: Box<dyn Iterator<Item = PathBuf>>;
-    let clrs: Box<dyn Iterator<Item = Result<PathBuf, String>>>;
-    if image_dir_or_file.is_file() {
+    let video = video::is_video(&image_dir_or_file);
+    let srcs: Box<dyn Iterator<Item = Result<Source, String>> + Send>;
+    let dsts: Box<dyn Iterator<Item = Option<PathBuf>> + Send>;
+    let output_dir_or_file = output_dir_or_file.unwrap_or_default();
+    let pack = to_stdout.is_some() || art::is_anim(&output_dir_or_file);
+    let clrs: Box<dyn Iterator<Item = Result<PathBuf, String>> + Send>;
+    let anim = match image_dir_or_file.is_file() && !video {
+        true => video::decode_animation(&image_dir_or_file).unwrap(),
+        false => None,
+    };
+    if image_dir_or_file.is_file() && !video && anim.is_none() {
         if output_dir_or_file.exists() && !output_dir_or_file.is_file() {
             panic!(
                 "\"{}\" already existed but not suitable as output file",
                 output_dir_or_file.to_string_lossy()
             )
         }
-        srcs = Box::new(vec![Ok(image_dir_or_file)].into_iter());
-        dsts = Box::new(vec![output_dir_or_file].into_iter());
+        srcs = Box::new(vec![Ok(Source::File(image_dir_or_file))].into_iter());
+        dsts = Box::new(vec![(!pack).then(|| output_dir_or_file.clone())].into_iter());
         clrs = Box::new(
             vec![if colorize_dir_or_file.exists() {
                 Ok(colorize_dir_or_file)
@@ -309,20 +945,58 @@ fn main_make(
             }]
             .into_iter(),
         );
-    } else if image_dir_or_file.is_dir() {
-        if output_dir_or_file.exists() && !output_dir_or_file.is_dir() {
+    } else if image_dir_or_file.is_dir() || video || anim.is_some() {
+        if pack {
+            if output_dir_or_file.exists() && !output_dir_or_file.is_file() {
+                panic!(
+                    "\"{}\" already existed but not suitable as output file",
+                    output_dir_or_file.to_string_lossy()
+                )
+            }
+        } else if output_dir_or_file.exists() && !output_dir_or_file.is_dir() {
             panic!(
                 "\"{}\" already existed but not suitable as output dir",
                 output_dir_or_file.to_string_lossy()
             )
+        } else {
+            util::create_dir(&output_dir_or_file);
+            if let (Some(src), Some(dst)) = (&timing, art::timing_path(&output_dir_or_file)) {
+                try_again!(
+                    std::fs::copy(src, &dst).map(|_| ()),
+                    "Failed to copy timing to \"{}\": {:?}",
+                    dst.to_string_lossy(),
+                );
+            }
         }
-        util::create_dir(&output_dir_or_file);
-        srcs = util::whether_dir(image_dir_or_file, "images", "image", verbose);
-        dsts = Box::new(
-            (i_ctr..=u32::MAX)
-                .into_iter()
-                .map(|n| output_dir_or_file.join(format!("{:06}.shoal", n))),
-        );
+        srcs = if let Some(frames) = anim {
+            Box::new(
+                frames
+                    .into_iter()
+                    .map(|(f, delay)| Ok(Source::Frame(f, Some(delay)))),
+            )
+        } else if video {
+            Box::new(
+                video::decode_video(&image_dir_or_file, fps)
+                    .unwrap()
+                    .map(|f| f.map(|f| Source::Frame(f, None))),
+            )
+        } else {
+            Box::new(
+                util::whether_dir(image_dir_or_file, "images", "image", verbose)
+                    .map(|p| p.map(Source::File)),
+            )
+        };
+        dsts = match pack {
+            true => Box::new(std::iter::repeat(None)),
+            false => {
+                let dir = output_dir_or_file.clone();
+                Box::new(
+                    (i_ctr..=u32::MAX)
+                        .into_iter()
+                        .map(move |n| Some(dir.join(format!("{:06}.shoal", n)))),
+                )
+            }
+        };
         clrs = if colorize_dir_or_file.exists() {
             Box::new(
                 util::whether_dir(colorize_dir_or_file, "color images", "color image", verbose)
@@ -340,104 +1014,1075 @@ fn main_make(
             image_dir_or_file.to_string_lossy()
         );
     }
-    for (ctr, ((src, dst), clr)) in srcs.zip(dsts).zip(clrs).enumerate() {
+    let job = Job {
+        cs,
+        crop,
+        resize,
+        zoom,
+        cell_aspect: cell_aspect.map(|a| relative_aspect(a, cell)),
+        pad_color,
+        mode,
+        bg,
+        negate,
+        tone: (brightness, contrast, gamma),
+        auto_contrast: auto_contrast.then(|| auto_contrast_smooth.clamp(0., 1.)),
+        equalize: equalize.map(Option::unwrap_or_default),
+        edgedet,
+        edge_weight: edge_weight.clamp(0., 1.),
+        canny: (sigma, thr_strong, thr_weak),
+        matching: art::Matching {
+            dither,
+            stabilize,
+            orient: orient_weight,
+            metric,
+            weights,
+            quadtree: quadtree.map(|q| (q, flat_threshold)),
+            bias: None,
+            blank: blank(blank_char, blank_threshold, ambiguous),
+            bold: bold_threshold,
+            prev: None,
+            vertical,
+        },
+        bias,
+        sampling: art::Sampling {
+            cell,
+            cell_color,
+            linear: linear_color,
+            ambiguous,
+        },
+        grade: (saturation.max(0.), hue_shift),
+        smooth: (smooth_color > 0.).then(|| (smooth_color, smooth_threshold)),
+        palette,
+        alpha,
+        delay: fps.map_or(Duration::ZERO, |f| Duration::from_secs_f32(1. / f)),
+        durations,
+        compress,
+        channels,
+        stamp,
+        play,
+        preview,
+        verbose,
+    };
+    let pool = util::purify_err(
+        "Failed to create thread pool",
+        rayon::ThreadPoolBuilder::new().num_threads(jobs).build(),
+    );
+    // Set once play is stopped, so that no more frames are made.
+    let stopped = AtomicBool::new(false);
+    let items = srcs
+        .zip(dsts)
+        .zip(clrs)
+        .enumerate()
+        .take_while(|_| !stopped.load(Ordering::Relaxed));
+    let (tx, rx) = mpsc::sync_channel::<(usize, String, Option<(Frame, Duration)>)>(MAKE_QUEUE);
+    let mut packed = Art::default();
+    let mut stream = play.then(|| Stream::start(ambiguous));
+    thread::scope(|s| {
+        s.spawn(|| {
+            if job.sequential() {
+                // Each frame depends on the previous one.
+                let mut plugins = plugin::Plugins::load(&plugins, &plugin_dir).unwrap();
+                let mut state = State::default();
+                for (ctr, ((src, dst), clr)) in items {
+                    let (log, frame) = job.make(ctr, src, dst, clr, &mut plugins, &mut state);
+                    tx.send((ctr, log, frame)).ok();
+                }
+            } else {
+                pool.install(|| {
+                    items.par_bridge().for_each_init(
+                        || {
+                            let plugins = plugin::Plugins::load(&plugins, &plugin_dir).unwrap();
+                            (tx.clone(), plugins)
+                        },
+                        |(tx, plugins), (ctr, ((src, dst), clr))| {
+                            let (log, frame) =
+                                job.make(ctr, src, dst, clr, plugins, &mut State::default());
+                            tx.send((ctr, log, frame)).ok();
+                        },
+                    )
+                });
+            }
+            drop(tx);
+        });
+        // Report progress, or play, in order.
+        let mut next = 0;
+        let mut pending = BTreeMap::new();
+        'report: for (ctr, log, frame) in rx {
+            pending.insert(ctr, (log, frame));
+            while let Some((log, frame)) = pending.remove(&next) {
+                if stream.is_none() {
+                    note(&log);
+                    stdout().flush().ok();
+                }
+                if let Some((f, delay)) = frame {
+                    if let Some(stream) = &mut stream {
+                        if !stream.play(&f, delay) {
+                            stopped.store(true, Ordering::Relaxed);
+                            break 'report;
+                        }
+                    }
+                    if pack {
+                        packed.push(f, delay);
+                    }
+                }
+                next += 1;
+            }
+        }
+    });
+    drop(stream);
+    if let Some(format) = to_stdout {
+        note(&format!("\nTotally {} frames.\n", packed.frames.len()));
+        let mut out = stdout();
+        match (format, packed.frames.first()) {
+            (export::Format::Html, _) => export::export_html(&mut out, &packed),
+            (export::Format::Asciicast, _) => export::export_asciicast(&mut out, &packed),
+            (export::Format::Txt, Some(f)) => export::export_txt(&mut out, f),
+            (export::Format::Ansi, Some(f)) => export::export_ansi(&mut out, f),
+            (export::Format::Svg, Some(f)) => export::export_svg(&mut out, f, ambiguous),
+            (_, None) => Ok(()),
+        }
+        .and_then(|_| out.flush())
+        .unwrap();
+    } else if pack {
+        println!("\nTotally {} frames.", packed.frames.len());
+        try_again!(
+            write_anim_stamped(
+                &output_dir_or_file,
+                &packed,
+                compress,
+                channels,
+                job.stamp.as_ref()
+            ),
+            "Failed to write animation \"{}\": {:?}",
+            output_dir_or_file.to_string_lossy(),
+        );
+    }
+}
+
+/// Rows left for the parameters and the prompt when fitting in terminals.
+const PREVIEW_ROWS: u16 = 4;
+
+fn main_preview(
+    ParamPreview {
+        image_file,
+        charset,
+        crop,
+        resize,
+        zoom,
+        cell_aspect,
+        cell,
+        mode,
+        negate,
+        dither,
+        weights,
+        metric,
+        monoch,
+        colors,
+    }: ParamPreview,
+) {
+    let cs = match &charset {
+        Some(p) => read_charset(p).unwrap(),
+        None => Charset::builtin(),
+    };
+    let cell = cell.unwrap_or(cs.cell);
+    let img = util::purify_err(
+        &format!("Failed to open image \"{}\"", image_file.to_string_lossy()),
+        image::open(&image_file),
+    );
+    let (iw, ih) = img.dimensions();
+    let resize = match (resize, zoom) {
+        (None, None) => terminal::size().ok().map(|(cols, rows)| {
+            let rows = rows.saturating_sub(PREVIEW_ROWS);
+            (cols as u32 * cell.0, rows as u32 * cell.1)
+        }),
+        _ => resize,
+    };
+    let mut img = util::img3(img, crop, resize, zoom, Lanczos3);
+    if let Some(a) = cell_aspect {
+        img = util::squash(img, relative_aspect(a, cell), Lanczos3);
+    }
+    let img = util::pad(img, cell, [0; 3]);
+    let color = img.to_rgb8();
+    let sampling = art::Sampling {
+        cell,
+        ..Default::default()
+    };
+    let made = match mode {
+        art::Mode::Glyph => {
+            let mut draft = img.to_luma8();
+            if negate {
+                draft.pixels_mut().for_each(|Luma([n])| *n = 255 - *n);
+            }
+            let matching = art::Matching {
+                dither,
+                metric,
+                weights,
+                ..Default::default()
+            };
+            make_art(
+                &draft,
+                &color,
+                &cs,
+                matching,
+                sampling,
+                &mut plugin::Plugins::none(),
+            )
+        }
+        art::Mode::HalfBlock => make_halfblock(&color, sampling, &mut plugin::Plugins::none()),
+    };
+    let frame = made.unwrap();
+    let (cols, rows) = frame.dimensions();
+    let mut out = stdout();
+    print_art(&mut out, &frame, (!monoch).then(|| colors.detect())).unwrap();
+    out.flush().unwrap();
+    println!(
+        "Charset: {} ({} glyphs, cell {}x{}); Mode: {:?}; Negate: {}; Dither: {}; Metric: {:?}",
+        charset.map_or(String::from("built-in"), |p| p
+            .to_string_lossy()
+            .into_owned()),
+        cs.len(),
+        cell.0,
+        cell.1,
+        mode,
+        negate,
+        dither,
+        metric,
+    );
+    println!(
+        "Image: {}x{} px; Converted: {}x{} px; Art: {}x{} cells",
+        iw,
+        ih,
+        color.width(),
+        color.height(),
+        cols,
+        rows,
+    );
+}
+
+/// Like `print!`, but into the progress to be reported.
+macro_rules! out {
+    ($log:ident, $($arg:tt)*) => {
+        $log.push_str(&format!($($arg)*))
+    };
+}
+macro_rules! outln {
+    ($log:ident, $($arg:tt)*) => {{
+        $log.push_str(&format!($($arg)*));
+        $log.push('\n')
+    }};
+}
+
+/// An image to be converted.
+enum Source {
+    File(PathBuf),
+    /// Decoded from a video or an animation, with the delay if known
+    Frame(RgbImage, Option<Duration>),
+}
+
+/// Backgrounds of cells.
+enum Background {
+    Color([u8; 3]),
+    Image(DynamicImage),
+}
+
+/// Handling of transparency of images.
+#[derive(Clone, Copy, Debug)]
+enum Alpha {
+    /// Emit spaces for mostly transparent cells
+    Skip,
+    /// Draft is opaque if alpha is at least it, otherwise blank
+    Threshold(u8),
+    /// Composite onto the color
+    Matte([u8; 3]),
+}
+
+impl std::str::FromStr for Alpha {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return if s == "skip" {
+            Ok(Alpha::Skip)
+        } else if let Some(n) = s.strip_prefix("threshold:") {
+            n.parse()
+                .map(Alpha::Threshold)
+                .map_err(|_| format!("Invalid threshold \"{}\"", n))
+        } else if let Some(c) = s.strip_prefix("matte:") {
+            opt_rgb(c)
+                .map(Alpha::Matte)
+                .map_err(|e| format!("{} \"{}\"", e, c))
+        } else {
+            Err(format!("Unknown alpha handling \"{}\"", s))
+        };
+    }
+}
+
+/// Histogram equalization of drafts.
+#[derive(Clone, Copy, Debug, Default)]
+enum Equalize {
+    #[default]
+    Global,
+    Clahe,
+}
+
+impl Equalize {
+    const NAMES: &'static [&'static str] = &["global", "clahe"];
+}
+
+impl std::str::FromStr for Equalize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "global" => Ok(Equalize::Global),
+            "clahe" => Ok(Equalize::Clahe),
+            _ => Err(format!("Unknown equalization \"{}\"", s)),
+        };
+    }
+}
+
+/// Tiles per side, and the clip limit relative to the mean bin, of CLAHE.
+const CLAHE_TILES: u32 = 8;
+const CLAHE_CLIP: f32 = 2.;
+
+/// Blend the image over an opaque color.
+fn matte(rgba: &RgbaImage, [r, g, b]: [u8; 3]) -> RgbImage {
+    return RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let Rgba([pr, pg, pb, a]) = *rgba.get_pixel(x, y);
+        let mix = |p: u8, c: u8| ((p as u32 * a as u32 + c as u32 * (255 - a as u32)) / 255) as u8;
+        Rgb([mix(pr, r), mix(pg, g), mix(pb, b)])
+    });
+}
+
+/// `util::squash` takes blocks as `1:2`, so the aspect of cells is relative to blocks of `cell`.
+fn relative_aspect((w, h): (f32, f32), (cw, ch): (u32, u32)) -> (f32, f32) {
+    return (w * ch as f32, h * cw as f32 * 2.);
+}
+
+/// Settings shared by workers.
+struct Job {
+    cs: Charset,
+    crop: Option<(u32, u32, u32, u32)>,
+    resize: Option<(u32, u32)>,
+    zoom: Option<f32>,
+    cell_aspect: Option<(f32, f32)>,
+    pad_color: [u8; 3],
+    mode: art::Mode,
+    bg: Option<Background>,
+    negate: bool,
+    /// Brightness, contrast and gamma
+    tone: (f32, f32, f32),
+    /// Weight of levels of each frame, see `State`
+    auto_contrast: Option<f32>,
+    equalize: Option<Equalize>,
+    edgedet: bool,
+    edge_weight: f32,
+    /// Sigma, strong and weak thresholds
+    canny: (f32, f32, f32),
+    /// Without the previous frame, which is passed to `make`, and `bias`
+    matching: art::Matching<'static>,
+    bias: Option<AHashMap<cluster::Glyph, f32>>,
+    sampling: art::Sampling,
+    /// Saturation and hue shift of colors of cells
+    grade: (f32, f32),
+    /// Weight and threshold of smoothing colors across frames
+    smooth: Option<(f32, u8)>,
+    palette: Option<Vec<[u8; 3]>>,
+    alpha: Option<Alpha>,
+    /// Used if the source has no delay
+    delay: Duration,
+    /// From timing file, take precedence over delays of sources
+    durations: Vec<Duration>,
+    compress: util::Codec,
+    channels: art::Channels,
+    /// Written into outputs if asked for
+    stamp: Option<art::CharsetStamp>,
+    /// Return frames even if saved, to be played
+    play: bool,
+    /// Protocol to show images inline, and font to rasterize results
+    preview: Option<(preview::Protocol, Option<Font<'static>>)>,
+    verbose: bool,
+}
+
+/// `blank-char` with its threshold, which must be half-width.
+fn blank(
+    c: Option<char>,
+    threshold: f32,
+    ambiguous: art::Ambiguous,
+) -> Option<(cluster::Glyph, f32)> {
+    return c.map(|c| match glyph_width(c.into(), ambiguous) {
+        Some(1) => (c.into(), threshold),
+        _ => panic!("Blank character \"{}\" is not half-width", c),
+    });
+}
+
+/// Maximum width of images shown inline, in px.
+const PREVIEW_WIDTH: u32 = 640;
+/// Size of half-width cells when rasterizing results for preview.
+const PREVIEW_CELL: (u32, u32) = (8, 16);
+
+/// Carried from frame to frame, if made one by one.
+#[derive(Default)]
+struct State {
+    prev: Option<Frame>,
+    /// Smoothed levels for `auto_contrast`
+    levels: Option<(f32, f32)>,
+}
+
+/// Fraction of pixels clipped at each end by `auto_contrast`.
+const AUTO_CONTRAST_CUT: f32 = 0.005;
+
+impl Job {
+    /// Whether each frame depends on previous ones.
+    fn sequential(&self) -> bool {
+        return self.matching.stabilize > 0.
+            || self.smooth.is_some()
+            || self.auto_contrast.map_or(false, |w| w < 1.);
+    }
+
+    /// Returns the progress to be reported, and the frame if not saved to `dst`.
+    fn make(
+        &self,
+        ctr: usize,
+        src: Result<Source, String>,
+        dst: Option<PathBuf>,
+        clr: Result<PathBuf, String>,
+        plugins: &mut plugin::Plugins,
+        state: &mut State,
+    ) -> (String, Option<(Frame, Duration)>) {
+        let Job {
+            cs,
+            crop,
+            resize,
+            zoom,
+            cell_aspect,
+            pad_color,
+            mode,
+            bg,
+            negate,
+            tone,
+            auto_contrast,
+            equalize,
+            edgedet,
+            edge_weight,
+            canny,
+            matching,
+            bias,
+            sampling,
+            grade,
+            smooth,
+            palette,
+            alpha,
+            delay,
+            durations,
+            compress,
+            channels,
+            stamp,
+            play,
+            preview,
+            verbose,
+        } = self;
+        let mut delay = *delay;
+        let (crop, resize, zoom, negate, verbose) = (*crop, *resize, *zoom, *negate, *verbose);
+        let mut log = String::new();
         if verbose {
-            print!("[{:06}] ", ctr);
+            out!(log, "[{:06}] ", ctr);
         }
         #[rustfmt::skip]
         let img = util::img3(
             match src {
-                Ok(p) => {
+                Ok(Source::Frame(f, d)) => {
+                    if let Some(d) = d {
+                        delay = d;
+                        if verbose { out!(log, "({}ms) ", d.as_millis()) }
+                    }
+                    DynamicImage::ImageRgb8(f)
+                },
+                Ok(Source::File(p)) => {
                     if verbose {
-                        print!("\"{}\" ", p.file_name().unwrap().to_string_lossy());
+                        out!(log, "\"{}\" ", p.file_name().unwrap().to_string_lossy());
                     }
                     match image::open(&p) {
                         Ok(i) => i,
                         Err(e) => { match verbose {
-                            true => println!("Failed to open: {:?}", e),
-                            false => print!("F"),
-                        } continue },
+                            true => outln!(log, "Failed to open: {:?}", e),
+                            false => out!(log, "F"),
+                        } return (log, None) },
                     }
                 },
                 Err(e) => { match verbose {
-                    true => println!("{}", e),
-                    false => print!("E"),
-                } continue },
+                    true => outln!(log, "{}", e),
+                    false => out!(log, "E"),
+                } return (log, None) },
             },
             crop,
             resize,
             zoom,
             Lanczos3,
         );
+        let img = match cell_aspect {
+            Some(a) => util::squash(img, *a, Lanczos3),
+            None => img,
+        };
+        let img = util::pad(img, sampling.cell, *pad_color);
+        let rgba = alpha.map(|_| img.to_rgba8());
+        let img = match (alpha, &rgba) {
+            (Some(Alpha::Matte(c)), Some(rgba)) => DynamicImage::ImageRgb8(matte(rgba, *c)),
+            _ => img,
+        };
+        if let Some(d) = durations.get(ctr) {
+            delay = *d;
+        }
+        let source = preview.is_some().then(|| img.clone());
         let mut draft = img.to_luma8();
+        util::adjust(&mut draft, tone.0, tone.1, tone.2);
+        if let Some(weight) = auto_contrast {
+            let (lo, hi) = util::levels(&draft, AUTO_CONTRAST_CUT);
+            let levels = match state.levels {
+                Some((l, h)) => (l + (lo - l) * weight, h + (hi - h) * weight),
+                None => (lo, hi),
+            };
+            util::stretch(&mut draft, levels);
+            state.levels = Some(levels);
+        }
+        match equalize {
+            Some(Equalize::Global) => util::equalize(&mut draft),
+            Some(Equalize::Clahe) => util::clahe(&mut draft, CLAHE_TILES, CLAHE_CLIP),
+            None => (),
+        }
+        let (sigma, strong, weak) = *canny;
+        if *edgedet {
+            draft = edgedet::detect(draft, sigma, strong, weak);
+        }
+        if let (Some(Alpha::Threshold(n)), Some(rgba)) = (alpha, &rgba) {
+            draft
+                .pixels_mut()
+                .zip(rgba.pixels())
+                .for_each(|(Luma([l]), Rgba([.., a]))| *l = if a >= n { 255 } else { 0 });
+        }
         if negate {
             draft.pixels_mut().for_each(|Luma([n])| *n = 255 - *n);
         }
+        if *edge_weight > 0. && !*edgedet {
+            let edges = edgedet::detect(draft.clone(), sigma, strong, weak);
+            draft
+                .pixels_mut()
+                .zip(edges.pixels())
+                .for_each(|(Luma([n]), Luma([e]))| {
+                    *n = (*n as f32 * (1. - edge_weight) + *e as f32 * edge_weight).round() as u8
+                });
+        }
+        if let Err(e) = plugins.preprocess(&mut draft) {
+            match verbose {
+                true => outln!(log, "{}", e),
+                false => out!(log, "P"),
+            }
+            return (log, None);
+        }
         #[rustfmt::skip]
         let color = match clr {
             Ok(p) => match image::open(&p) {
                 Ok(img) => {
-                    if verbose { print!("× \"{}\"", p.file_name().unwrap().to_string_lossy()) }
+                    if verbose { out!(log, "× \"{}\"", p.file_name().unwrap().to_string_lossy()) }
                     util::img3(img, crop, Some(draft.dimensions()), None, Lanczos3)
                 },
-                Err(e) => { if verbose { print!("(Color unopenable: {:?})", e) } img },
+                Err(e) => { if verbose { out!(log, "(Color unopenable: {:?})", e) } img },
             },
             Err(e) => {
                 if verbose { if e.is_empty() {
-                        print!("(No color provided)")
+                        out!(log, "(No color provided)")
                     } else {
-                        print!("(Color inaccessible: {})", e)
+                        out!(log, "(Color inaccessible: {})", e)
                     }
                 }
                 img
             },
         }.to_rgb8();
-        match make_art(draft, color, &csh, &csf, dst) {
+        let matching = art::Matching {
+            bias: bias.as_ref(),
+            prev: state.prev.as_ref(),
+            ..*matching
+        };
+        let made = match mode {
+            art::Mode::Glyph => make_art(&draft, &color, cs, matching, *sampling, plugins),
+            art::Mode::HalfBlock => make_halfblock(&color, *sampling, plugins),
+        };
+        #[rustfmt::skip]
+        let mut frame = match made {
+            Ok(f) => f,
+            Err(e) => { match verbose {
+                true => outln!(log, " - {}", e),
+                false => out!(log, "P"),
+            } return (log, None) },
+        };
+        grade_colors(&mut frame, grade.0, grade.1);
+        match (mode, bg) {
+            (art::Mode::HalfBlock, _) | (_, None) => (),
+            (_, Some(Background::Color(c))) => frame
+                .lines
+                .iter_mut()
+                .flatten()
+                .for_each(|cell| cell.bg = Some(*c)),
+            (_, Some(Background::Image(img))) => paint_bg(
+                &mut frame,
+                &util::img3(img.clone(), crop, Some(draft.dimensions()), None, Lanczos3).to_rgb8(),
+                *sampling,
+            ),
+        }
+        if let (Some((weight, threshold)), Some(old)) = (smooth, state.prev.as_ref()) {
+            smooth_colors(&mut frame, old, *weight, *threshold, sampling.ambiguous);
+        }
+        if matching.stabilize > 0. || smooth.is_some() {
+            state.prev = Some(frame.clone());
+        }
+        if let Some(palette) = palette {
+            snap_colors(&mut frame, palette);
+        }
+        if let (Some((protocol, font)), Some(source)) = (preview, source) {
+            let mut buf = vec![b'\n'];
+            let mut shown = preview::show(&mut buf, &source, PREVIEW_WIDTH, *protocol);
+            if shown.is_ok() {
+                shown = match font {
+                    Some(font) => {
+                        let img = export::render(&frame, font, PREVIEW_CELL, sampling.ambiguous);
+                        preview::show(
+                            &mut buf,
+                            &DynamicImage::ImageRgb8(img),
+                            PREVIEW_WIDTH,
+                            *protocol,
+                        )
+                    }
+                    None => export::export_ansi(&mut buf, &frame),
+                };
+            }
+            if shown.is_ok() {
+                out!(log, "{}", String::from_utf8_lossy(&buf));
+            }
+        }
+        if let (Some(Alpha::Skip), Some(rgba)) = (alpha, &rgba) {
+            let mask = GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                Luma([rgba.get_pixel(x, y)[3]])
+            });
+            clear_transparent(&mut frame, &mask, *sampling);
+        }
+        let saved = match &dst {
+            Some(p) => write_art_stamped(p, &frame, *compress, *channels, stamp.as_ref()),
+            None => Ok(()),
+        };
+        match saved {
             Ok(_) => match verbose {
-                true => println!(" - Ok"),
+                true => outln!(log, " - Ok"),
                 false => {
                     if ctr % 100 == 0 {
-                        print!("[{}]", ctr);
+                        out!(log, "[{}]", ctr);
                     } else {
-                        print!(".");
+                        out!(log, ".");
                     }
                 }
             },
             Err(e) => match verbose {
-                true => println!(" - Failed to save to: {:?}", e),
-                false => print!("S"),
+                true => outln!(log, " - Failed to save to: {:?}", e),
+                false => out!(log, "S"),
             },
         }
+        return (log, (dst.is_none() || *play).then(|| (frame, delay)));
+    }
+}
+
+/// Frames made ahead of reporting, or of play with `--play`, which bounds memory.
+const MAKE_QUEUE: usize = 64;
+
+/// Plays frames of `art make --play` as soon as they are made.
+struct Stream {
+    colors: Option<art::Colors>,
+    ambiguous: art::Ambiguous,
+    sync: bool,
+    /// The frame on screen
+    prev: Option<Frame>,
+    /// When the next frame is due
+    due: Instant,
+}
+
+impl Stream {
+    fn start(ambiguous: art::Ambiguous) -> Self {
+        let mut out = stdout();
+        enable_raw_mode().ok();
+        queue!(out, EnterAlternateScreen, HideCursor).ok();
+        return Stream {
+            colors: Some(art::Colors::Auto.detect()),
+            ambiguous,
+            sync: out.is_tty(),
+            prev: None,
+            due: Instant::now(),
+        };
+    }
+
+    /// Draw the frame when due, those without delays last `export::DEFAULT_DELAY`;
+    /// Returns `false` if stopped by ESC or Ctrl-C.
+    fn play(&mut self, frame: &Frame, delay: Duration) -> bool {
+        use crossterm::event::*;
+        let mut out = stdout();
+        while poll(self.due.saturating_duration_since(Instant::now())).unwrap_or(false) {
+            match read().ok() {
+                Some(Event::Key(k)) => match k.code {
+                    KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return false
+                    }
+                    KeyCode::Esc => return false,
+                    _ => (),
+                },
+                Some(Event::Resize(..)) => {
+                    self.prev = None;
+                    queue!(out, Clear(ClearType::All)).ok();
+                }
+                _ => (),
+            }
+        }
+        if self.sync {
+            queue!(out, Print(art::SYNC_BEGIN)).ok();
+        }
+        match &self.prev {
+            Some(p) => play_diff(&mut out, p, frame, 0, 0, self.colors, self.ambiguous),
+            None => play_art(&mut out, frame, 0, 0, self.colors),
+        }
+        .ok();
+        if self.sync {
+            queue!(out, Print(art::SYNC_END)).ok();
+        }
+        out.flush().ok();
+        self.prev = Some(frame.clone());
+        self.due = Instant::now()
+            + match delay.is_zero() {
+                true => Duration::from_millis(export::DEFAULT_DELAY as u64),
+                false => delay,
+            };
+        return true;
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        queue!(stdout(), LeaveAlternateScreen, ShowCursor, ResetColor).ok();
         stdout().flush().ok();
+        disable_raw_mode().ok();
+    }
+}
+
+/// Frames skipped by PgUp/PgDn during play.
+const SEEK_STEP: usize = 50;
+/// How often input is polled during pause.
+const PAUSED_POLL: Duration = Duration::from_millis(100);
+
+/// A frame to be played, those in dirs are read only when played.
+enum Playable {
+    Frame(Frame, Duration),
+    Path(PathBuf, Duration),
+}
+
+impl Playable {
+    fn load(item: &Result<Playable, String>) -> Result<(Frame, Duration), String> {
+        return match item {
+            Ok(Playable::Frame(f, d)) => Ok((f.clone(), *d)),
+            Ok(Playable::Path(p, d)) => read_art(p).map(|f| (f, *d)),
+            Err(e) => Err(e.clone()),
+        };
+    }
+
+    /// Returns whether it is a single `.shoal` file as well, and the charset stamped if any,
+    /// that of the first file for dirs.
+    fn open(
+        p: &Path,
+    ) -> (
+        Vec<Result<Playable, String>>,
+        bool,
+        Option<art::CharsetStamp>,
+    ) {
+        return if p.is_file() && art::is_anim(p) {
+            let (Art { frames, delays }, stamp) = read_anim_stamped(p).unwrap();
+            let items = frames
+                .into_iter()
+                .zip(delays)
+                .map(|(f, d)| Ok(Playable::Frame(f, d)));
+            (items.collect(), false, stamp)
+        } else if p.is_file() {
+            match read_art_stamped(p) {
+                Ok((f, stamp)) => (vec![Ok(Playable::Frame(f, Duration::ZERO))], true, stamp),
+                Err(e) => (vec![Err(e)], true, None),
+            }
+        } else if p.is_dir() {
+            let paths = art::art_dir_paths(p).unwrap();
+            let delays = art::dir_delays(p, paths.len()).unwrap();
+            let stamp = paths
+                .first()
+                .and_then(|p| read_art_stamped(p).ok())
+                .and_then(|(_, s)| s);
+            let items = paths
+                .into_iter()
+                .zip(delays)
+                .map(|(p, d)| Ok(Playable::Path(p, d)));
+            (items.collect(), false, stamp)
+        } else {
+            panic!("Invalid shoal(s) path \"{}\"", p.to_string_lossy());
+        };
+    }
+}
+
+/// Whether the font has all chars of the glyph, but those of zero width like ZWJ.
+fn font_covers(font: &Font, g: cluster::Glyph) -> bool {
+    return g
+        .as_str()
+        .chars()
+        .filter(|c| glyph_width((*c).into(), art::Ambiguous::Narrow) != Some(0))
+        .all(|c| font.glyph(c).id().0 != 0);
+}
+
+/// Center the text in the bottom row, which is cleared first; Truncated if too wide.
+///
+/// The row is kept from art while subtitles are shown, see `main_play`.
+fn queue_subs<W: Write>(
+    out: &mut W,
+    text: &str,
+    color: Option<Color>,
+    ambiguous: art::Ambiguous,
+) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let mut width = 0;
+    let text = text
+        .chars()
+        .take_while(|c| {
+            width += glyph_width((*c).into(), ambiguous).unwrap_or(0);
+            width <= cols as usize
+        })
+        .collect::<String>();
+    let width = text
+        .chars()
+        .map(|c| glyph_width(c.into(), ambiguous).unwrap_or(0))
+        .sum::<usize>() as u16;
+    let row = rows.saturating_sub(1);
+    queue!(
+        out,
+        ResetColor,
+        MoveTo(0, row),
+        Clear(ClearType::CurrentLine)
+    )?;
+    queue!(out, MoveTo(cols.saturating_sub(width) / 2, row))?;
+    if let Some(c) = color {
+        queue!(out, SetForegroundColor(c))?;
+    }
+    return queue!(out, Print(text), ResetColor);
+}
+
+/// Where the status is shown during play.
+#[derive(Clone, Copy, Debug, Default)]
+enum Corner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    const NAMES: &'static [&'static str] =
+        &["top-left", "top-right", "bottom-left", "bottom-right"];
+}
+
+impl std::str::FromStr for Corner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "top-left" => Ok(Corner::TopLeft),
+            "top-right" => Ok(Corner::TopRight),
+            "bottom-left" => Ok(Corner::BottomLeft),
+            "bottom-right" => Ok(Corner::BottomRight),
+            _ => Err(format!("Unknown corner \"{}\"", s)),
+        };
+    }
+}
+
+/// Draw the status in reverse video, padded to the widest ever shown to cover what was left.
+fn queue_osd<W: Write>(
+    out: &mut W,
+    text: &str,
+    corner: Corner,
+    width: &mut usize,
+    ambiguous: art::Ambiguous,
+) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let w = text
+        .chars()
+        .map(|c| glyph_width(c.into(), ambiguous).unwrap_or(0))
+        .sum::<usize>();
+    *width = (*width).max(w);
+    let (x, y) = match corner {
+        Corner::TopLeft => (0, 0),
+        Corner::TopRight => (cols.saturating_sub(*width as u16), 0),
+        Corner::BottomLeft => (0, rows.saturating_sub(1)),
+        Corner::BottomRight => (cols.saturating_sub(*width as u16), rows.saturating_sub(1)),
+    };
+    let text = match corner {
+        Corner::TopLeft | Corner::BottomLeft => format!("{}{}", text, " ".repeat(*width - w)),
+        Corner::TopRight | Corner::BottomRight => format!("{}{}", " ".repeat(*width - w), text),
+    };
+    queue!(
+        out,
+        MoveTo(x, y),
+        ResetColor,
+        SetAttribute(Attribute::Reverse)
+    )?;
+    return queue!(out, Print(text), SetAttribute(Attribute::Reset));
+}
+
+/// Wall clock of play, paused and seeked along; Used to drop frames without audio.
+struct Clock {
+    origin: Instant,
+    paused: Option<Instant>,
+}
+
+impl Clock {
+    fn start() -> Self {
+        return Clock {
+            origin: Instant::now(),
+            paused: None,
+        };
+    }
+
+    fn position(&self) -> Duration {
+        return self
+            .paused
+            .unwrap_or_else(Instant::now)
+            .saturating_duration_since(self.origin);
     }
+
+    fn pause(&mut self) {
+        self.paused.get_or_insert_with(Instant::now);
+    }
+
+    fn resume(&mut self) {
+        if let Some(p) = self.paused.take() {
+            self.origin += p.elapsed();
+        }
+    }
+
+    fn seek(&mut self, pos: Duration) {
+        let now = self.paused.unwrap_or_else(Instant::now);
+        self.origin = now.checked_sub(pos).unwrap_or(now);
+    }
+}
+
+const PLAYLIST_SUFFIXES: &[&str] = &["m3u", "m3u8"];
+
+/// Paths with their frame rates if specified.
+fn read_playlist(p: &Path) -> Vec<(PathBuf, Option<f32>)> {
+    let text = util::purify_err(
+        &format!("Failed to read playlist \"{}\"", p.to_string_lossy()),
+        std::fs::read_to_string(p),
+    );
+    let dir = p.parent().unwrap_or(Path::new(""));
+    let mut fps = None;
+    let mut entries = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(f) = line.strip_prefix("#FPS:") {
+            fps = Some(util::purify_err(
+                &format!("Invalid frame rate \"{}\" in playlist", f),
+                f.trim().parse::<f32>(),
+            ));
+        } else if !line.starts_with('#') {
+            entries.push((dir.join(line), fps.take()));
+        }
+    }
+    return entries;
 }
 
 fn main_play(
     ParamPlay {
-        shoal_dir_or_file,
+        shoal_dirs_or_files,
         sx,
         sy,
         max_fps,
+        drop_frames,
+        audio,
+        subs,
+        subs_color,
         capture,
         monoch,
+        colors,
+        ambiguous,
+        vertical,
+        font,
+        no_sync,
+        osd,
         i_ctr,
     }: ParamPlay,
 ) {
-    let srcs: Box<dyn Iterator<Item = Result<PathBuf, String>>>;
-    let single: bool;
-    if shoal_dir_or_file.is_file() {
-        srcs = Box::new(vec![Ok(shoal_dir_or_file)].into_iter());
-        single = true;
-    } else if shoal_dir_or_file.is_dir() {
-        srcs = util::whether_dir(shoal_dir_or_file, "shoals", "shoal", false);
-        single = false;
-    } else {
-        panic!(
-            "Invalid shoal(s) path \"{}\"",
-            shoal_dir_or_file.to_string_lossy()
-        );
+    let font = font.map(|p| {
+        util::purify_opt(
+            &format!("Failed to open font \"{}\"", p.to_string_lossy()),
+            Font::try_from_vec(util::purify_err(
+                &format!("Failed to access font \"{}\"", p.to_string_lossy()),
+                std::fs::read(&p),
+            )),
+        )
+    });
+    let mut entries = Vec::new();
+    for p in shoal_dirs_or_files {
+        match p
+            .extension()
+            .map_or(false, |e| PLAYLIST_SUFFIXES.iter().any(|s| e == *s))
+        {
+            true => entries.extend(read_playlist(&p)),
+            false => entries.push((p, None)),
+        }
     }
-    let avg = if max_fps > 0. { 1. / max_fps } else { 0. };
+    // Entry index, frame interval if no delay, frame.
+    let mut items = Vec::<(usize, f32, Result<Playable, String>)>::new();
+    let mut single = false;
+    for (n, (p, fps)) in entries.iter().enumerate() {
+        let fps = fps.unwrap_or(max_fps);
+        let avg = if fps > 0. { 1. / fps } else { 0. };
+        let (frames, s, stamp) = Playable::open(p);
+        if let (Some(font), Some(stamp)) = (&font, &stamp) {
+            let missing = stamp.missing(|c| font_covers(font, c));
+            if !missing.is_empty() {
+                println!(
+                    "\"{}\": {} glyphs of charset \"{}\" are not in the font: {}",
+                    p.to_string_lossy(),
+                    missing.len(),
+                    stamp.source,
+                    missing.iter().map(|c| c.to_string()).collect::<String>()
+                );
+            }
+        }
+        items.extend(frames.into_iter().map(|f| (n, avg, f)));
+        single = s && entries.len() == 1;
+    }
+    // When each frame starts, if played along with audio or dropping frames.
+    let stamps = items
+        .iter()
+        .scan(Duration::ZERO, |t, (_, avg, src)| {
+            let stamp = *t;
+            *t += match src {
+                Ok(Playable::Frame(_, d) | Playable::Path(_, d)) if !d.is_zero() => *d,
+                _ => Duration::from_secs_f32(*avg),
+            };
+            Some(stamp)
+        })
+        .collect::<Vec<_>>();
+    let mut track = audio.map(|p| {
+        util::purify_err(
+            &format!("Failed to play audio \"{}\"", p.to_string_lossy()),
+            audio::Track::play(&p),
+        )
+    });
+    let cues = subs.map(|p| {
+        util::purify_err(
+            &format!("Invalid subtitles \"{}\"", p.to_string_lossy()),
+            subtitle::read_srt(&p),
+        )
+    });
+    let mut clock = (drop_frames && track.is_none()).then(Clock::start);
+    let mut dropped = 0;
+    let colors = (!monoch).then(|| colors.detect());
     let mut out = stdout();
     let mut cap = None;
     let mut caps: Box<dyn Iterator<Item = PathBuf>> = Box::new(std::iter::empty());
@@ -462,13 +2107,49 @@ fn main_play(
         enable_raw_mode().ok();
         queue!(out, EnterAlternateScreen, HideCursor).ok();
     }
+    let sync = !no_sync && out.is_tty();
+    let osd = osd.map(Option::unwrap_or_default);
+    // When the last frame was drawn, measured frame rate, width of status.
+    let mut stat = (None::<Instant>, 0f32, 0);
     let mut now = Instant::now();
-    for src in srcs {
-        src.and_then(|p| read_art(&p))
-            .and_then(|dat| {
-                play_art(&mut out, &dat, sx, sy, monoch).or_else(|e| Err(format!("{:?}", e)))
+    let mut prev = None;
+    let mut paused = false;
+    let mut i = 0;
+    let mut shown = None;
+    let mut shown_subs = None::<String>;
+    'play: while i < items.len() {
+        let (entry, avg, src) = &items[i];
+        let mut delay = Duration::ZERO;
+        if sync {
+            queue!(out, Print(art::SYNC_BEGIN)).ok();
+        }
+        if shown.map_or(false, |n| n != *entry) {
+            prev = None;
+            shown_subs = None;
+            queue!(out, ResetColor, Clear(ClearType::All)).ok();
+        }
+        shown = Some(*entry);
+        // Rows of art above the bottom one, which is kept for subtitles.
+        let rows = cues
+            .as_ref()
+            .and_then(|_| terminal::size().ok())
+            .map(|(_, r)| r.saturating_sub(1).saturating_sub(sy) as usize);
+        Playable::load(src)
+            .and_then(|(mut dat, d)| {
+                delay = d;
+                if let Some(rows) = rows {
+                    dat.lines.truncate(rows);
+                }
+                match &prev {
+                    _ if vertical => play_vertical(&mut out, &dat, sx, sy, colors, ambiguous),
+                    Some(p) => play_diff(&mut out, p, &dat, sx, sy, colors, ambiguous),
+                    None => play_art(&mut out, &dat, sx, sy, colors),
+                }
+                .map(|_| prev = Some(dat))
+                .or_else(|e| Err(format!("{:?}", e)))
             })
             .or_else(|e| {
+                prev = None;
                 queue!(
                     out,
                     MoveTo(sx, sy),
@@ -477,26 +2158,151 @@ fn main_play(
                 )
             })
             .ok();
+        if let Some(cues) = &cues {
+            let text = subtitle::active(cues, stamps[i])
+                .map(|c| c.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            // Redrawn only when changed, which clears the row once cues end.
+            if shown_subs.as_ref() != Some(&text) {
+                let color = colors.map(|c| c.quantize(subs_color));
+                queue_subs(&mut out, &text, color, ambiguous).ok();
+                shown_subs = Some(text);
+            }
+        }
+        if let Some(corner) = osd {
+            if let Some(last) = stat.0 {
+                let fps = 1. / last.elapsed().as_secs_f32().max(1e-3);
+                stat.1 = match stat.1 > 0. {
+                    true => stat.1 * 0.9 + fps * 0.1,
+                    false => fps,
+                };
+            }
+            stat.0 = Some(Instant::now());
+            let name = match src {
+                Ok(Playable::Path(p, _)) => p.file_name(),
+                _ => entries[*entry].0.file_name(),
+            };
+            let text = format!(
+                " {}/{} | {:.1} fps | {} dropped | {} ",
+                i + 1,
+                items.len(),
+                stat.1,
+                dropped,
+                name.map_or("".into(), |n| n.to_string_lossy())
+            );
+            queue_osd(&mut out, &text, corner, &mut stat.2, ambiguous).ok();
+        }
+        if sync {
+            queue!(out, Print(art::SYNC_END)).ok();
+        }
         out.flush().ok();
-        use crossterm::event::*;
-        if poll(Duration::from_millis(1)).unwrap_or(false) {
-            if let Some(e) = read().ok() {
-                if let Event::Key(k) = e {
-                    if (k.code == KeyCode::Char('c') && k.modifiers.contains(KeyModifiers::CONTROL))
-                        || k.code == KeyCode::Esc
-                    {
-                        break;
+        // Delays stored in animation take precedence.
+        let wait = match delay.is_zero() {
+            true => *avg,
+            false => delay.as_secs_f32(),
+        };
+        let pos = match (&track, &clock) {
+            (Some(t), _) => Some(t.position()),
+            (None, Some(c)) => Some(c.position()),
+            (None, None) => None,
+        };
+        let due = match (pos, stamps.get(i + 1)) {
+            (Some(p), Some(s)) => Instant::now() + s.saturating_sub(p),
+            _ => now + Duration::from_secs_f32(wait),
+        };
+        let mut next = i + 1;
+        let mut seek = false;
+        // Keep handling input until the frame is due, or forever if paused.
+        loop {
+            use crossterm::event::*;
+            let timeout = match paused {
+                true => PAUSED_POLL,
+                false => due.saturating_duration_since(Instant::now()),
+            };
+            if !poll(timeout).unwrap_or(false) {
+                match paused {
+                    true => continue,
+                    false => break,
+                }
+            }
+            let k = match read().ok() {
+                Some(Event::Key(k)) => k,
+                // What was on screen is garbled, redraw all of the frame.
+                Some(Event::Resize(..)) => {
+                    prev = None;
+                    queue!(out, Clear(ClearType::All)).ok();
+                    next = i;
+                    break;
+                }
+                _ => continue,
+            };
+            match k.code {
+                KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => break 'play,
+                KeyCode::Esc => break 'play,
+                KeyCode::Char(' ') => {
+                    paused = !paused;
+                    match (&mut track, paused) {
+                        (Some(t), true) => t.pause(),
+                        (Some(t), false) => t.resume(),
+                        (None, _) => (),
                     }
+                    match (&mut clock, paused) {
+                        (Some(c), true) => c.pause(),
+                        (Some(c), false) => c.resume(),
+                        (None, _) => (),
+                    }
+                }
+                KeyCode::Right if paused => {
+                    next = (i + 1).min(items.len() - 1);
+                    seek = true;
+                    break;
+                }
+                KeyCode::Left if paused => {
+                    next = i.saturating_sub(1);
+                    seek = true;
+                    break;
+                }
+                KeyCode::PageDown => {
+                    next = (i + SEEK_STEP).min(items.len() - 1);
+                    seek = true;
+                    break;
                 }
+                KeyCode::PageUp => {
+                    next = i.saturating_sub(SEEK_STEP);
+                    seek = true;
+                    break;
+                }
+                KeyCode::Char(c @ '0'..='9') => {
+                    next = items.len() * (c as usize - '0' as usize) / 10;
+                    seek = true;
+                    break;
+                }
+                _ => (),
             }
         }
-        if max_fps > 0. {
-            let ext = avg - now.elapsed().as_secs_f32();
-            if ext > 0. {
-                std::thread::sleep(Duration::from_secs_f32(ext));
+        if seek {
+            if let Some(t) = &mut track {
+                t.seek(stamps[next]).ok();
+            }
+            if let Some(c) = &mut clock {
+                c.seek(stamps[next]);
+            }
+        } else if next == i + 1 {
+            let pos = match (&track, &clock) {
+                (Some(t), _) => Some(t.position()),
+                (None, Some(c)) => Some(c.position()),
+                (None, None) => None,
+            };
+            // Drop frames which are already late.
+            if let Some(pos) = pos {
+                while next + 1 < items.len() && stamps[next + 1] <= pos {
+                    next += 1;
+                    dropped += 1;
+                }
             }
-            now = Instant::now()
         }
+        now = Instant::now();
         if let Some((w, h, c)) = &mut cap {
             let (w, h) = (*w, *h);
             for _ in 0..10 {
@@ -524,6 +2330,7 @@ fn main_play(
                 break;
             }
         }
+        i = next;
     }
     if !single {
         queue!(out, LeaveAlternateScreen, ShowCursor, ResetColor).ok();
@@ -531,103 +2338,399 @@ fn main_play(
         queue!(out, MoveToNextLine(1), ShowCursor, ResetColor).ok();
     }
     disable_raw_mode().ok();
+    if drop_frames || dropped > 0 {
+        println!("Dropped {} frames.", dropped);
+    }
+}
+
+fn main_export(
+    ParamExport {
+        shoal_dir_or_file,
+        output_file,
+        format,
+        frame,
+        fps,
+    }: ParamExport,
+) {
+    let mut art = art::open_art(&shoal_dir_or_file).unwrap();
+    if let Some(fps) = fps.filter(|f| *f > 0.) {
+        let delay = Duration::from_secs_f32(1. / fps);
+        art.delays
+            .iter_mut()
+            .filter(|d| d.is_zero())
+            .for_each(|d| *d = delay);
+    }
+    if !format.animated() && frame >= art.frames.len() {
+        panic!("Frame {} out of {}", frame, art.frames.len())
+    }
+    let mut buf = Vec::<u8>::with_capacity(1 << 16);
+    match format {
+        export::Format::Txt => export::export_txt(&mut buf, &art.frames[frame]),
+        export::Format::Ansi => export::export_ansi(&mut buf, &art.frames[frame]),
+        export::Format::Html => export::export_html(&mut buf, &art),
+        export::Format::Svg => {
+            export::export_svg(&mut buf, &art.frames[frame], art::Ambiguous::Narrow)
+        }
+        export::Format::Asciicast => export::export_asciicast(&mut buf, &art),
+    }
+    .unwrap();
+    try_again!(
+        std::fs::write(&output_file, &buf),
+        "Failed to write \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
 }
 
-#[rustfmt::skip]
-const BULITIN_CHARSET: [(char, [f32; 10]); 95] = [
-    (' ', [-32.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000,  0.000000]),
-    ('!', [-27.687500,  0.153072,  0.083712, -3.049398,  0.005655, -1.810784, -0.375900, -0.034486, -0.108238, -0.202099]),
-    ('"', [-27.671875,  2.566176,  0.122190, -2.032932,  0.075350, -0.864137, -2.685735, -0.016573, -1.201202,  0.092894]),
-    ('#', [-18.093750, -0.366140,  0.402446, -5.778388, -0.019004, -6.350002,  0.116994, -0.181468,  0.148199,  0.192067]),
-    ('$', [-18.960938,  0.381606,  0.295505, -6.314242,  0.238307, -5.032045,  0.090663, -0.072012, -0.248507, -0.080546]),
-    ('%', [-17.656250,  0.521049,  0.519682, -3.425048,  0.848369, -6.567137, -0.180730, -0.137714, -0.070680,  0.215259]),
-    ('&', [-19.843750, -0.451876,  0.441400, -5.071281,  0.444430, -5.632273,  1.096957, -0.298243, -0.389327, -0.146957]),
-    ('\'',[-29.851562,  1.274990,  0.044846, -1.519175,  0.025806, -0.423975, -1.323533, -0.010575, -0.901554, -0.108267]),
-    ('(', [-25.070312, -0.179685,  0.072743, -4.226068, -0.005199, -1.347678, -0.124397, -1.219271,  0.111271, -0.849313]),
-    (')', [-25.046875, -0.187074,  0.226566, -4.132155,  0.004431, -1.303859, -0.108054,  1.182301,  0.106552,  0.575849]),
-    ('*', [-26.734375,  2.531234,  0.130946, -2.618505,  0.064653, -2.245230, -3.664769, -0.056583, -1.232644, -0.030322]),
-    ('+', [-27.835938, -0.120207,  0.092381, -2.292573, -0.001408, -3.551126,  0.276549, -0.078877,  0.065600, -0.080121]),
-    (',', [-29.164062, -2.126778,  0.086702, -2.005311, -0.080844,  0.519470,  0.864870,  0.067713,  1.503859, -0.209316]),
-    ('-', [-29.851562, -0.065538,  0.056504, -0.867311, -0.001408, -1.984897,  0.186637, -0.052203,  0.026943,  0.006492]),
-    ('.', [-30.421875, -1.011232,  0.029897, -1.115903, -0.019085, -0.223716,  1.142172, -0.004576,  0.715049, -0.072178]),
-    ('/', [-25.585938, -0.203722,  0.162807, -3.297990, -1.539253, -1.539445, -0.069962,  0.099356, -0.031454, -0.025581]),
-    ('0', [-19.375000,  0.446021,  0.460914, -3.159883, -0.047313, -6.233440, -0.185610, -0.262044, -0.115228,  0.275479]),
-    ('1', [-26.125000,  0.926521, -0.079960, -3.800699,  0.296921, -2.481578, -0.942908,  0.181746, -0.450515,  0.846323]),
-    ('2', [-23.171875,  0.132234,  0.233747, -3.491340, -0.537179, -2.804240, -0.016471,  0.114277, -0.068887, -0.013109]),
-    ('3', [-22.796875,  0.200516, -1.060608, -3.513437, -0.036830, -3.416266,  0.128429,  0.920007, -0.247994,  1.192726]),
-    ('4', [-24.132812, -0.268562, -0.194082, -4.148728,  0.111756, -4.893866,  1.009882, -0.287851, -0.095038,  1.162668]),
-    ('5', [-21.843750,  0.542205,  0.842696, -3.325612,  0.645371, -4.266465, -0.197154, -0.243413, -0.125037, -0.217509]),
-    ('6', [-22.726562, -0.949528,  0.758947, -3.574204,  0.094181, -5.027889,  1.275183, -0.584171, -0.096499, -0.607358]),
-    ('7', [-25.476562,  1.450719, -0.662002, -3.507912, -0.459557, -2.083646, -0.762941,  0.515639, -0.318104,  0.985762]),
-    ('8', [-20.421875,  0.288087,  0.342227, -4.618291, -0.004735, -5.274710,  0.013473, -0.161544, -0.315211,  0.133299]),
-    ('9', [-22.765625,  1.573469, -0.187715, -3.646019,  0.169756, -4.681046, -1.681783,  0.286937, -0.125990,  0.759409]),
-    (':', [-28.843750, -0.590174,  0.059794, -2.231806, -0.014166, -1.497428,  0.176183, -0.025056,  0.417316, -0.144356]),
-    (';', [-27.578125, -1.712216,  0.113609, -3.126738, -0.073439, -0.751253, -0.099595,  0.046090,  1.210719, -0.274277]),
-    ('<', [-27.406250, -0.130908, -0.070727, -1.988738, -0.003484, -3.715560,  0.245948, -0.171300,  0.059391,  0.089090]),
-    ('=', [-27.687500, -0.121531,  0.127444, -1.745670,  0.000000, -3.739001,  0.214547, -0.109930,  0.049106,  0.018964]),
-    ('>', [-27.406250, -0.121995,  0.329843, -1.955592, -0.003926, -3.694419,  0.225264, -0.036171,  0.055572, -0.040955]),
-    ('?', [-25.312500,  1.389435, -0.591787, -3.380854, -0.149249, -2.292413, -1.396848,  0.526491, -0.254172,  0.938740]),
-    ('@', [-17.453125, -0.174052,  0.558885, -4.872407, -0.205499, -6.259057, -0.182926, -0.194182,  0.166714,  0.569745]),
-    ('A', [-22.265625, -0.384896,  0.266846, -4.474660, -0.042674, -5.220045,  0.892567, -0.132697, -0.823877,  0.009057]),
-    ('B', [-19.593750,  0.369113,  1.274137, -3.369806,  0.138414, -5.629320, -0.114359, -0.389657, -0.250380,  0.333273]),
-    ('C', [-23.421875,  0.266863,  2.007504, -1.999786,  0.067025, -2.803266,  0.148635, -1.471554, -0.057286, -0.436893]),
-    ('D', [-20.609375,  0.362096,  0.930460, -2.132369,  0.028989, -4.721627,  0.018683, -0.068627, -0.062999,  0.080986]),
-    ('E', [-22.460938,  0.362861,  2.488972, -2.911291,  0.086686, -3.342021, -0.091548, -1.516960, -0.112359, -1.252205]),
-    ('F', [-24.476562,  1.371431,  2.149274, -2.988631, -0.179298, -3.216630, -0.914221, -1.225676, -0.686668, -2.187794]),
-    ('G', [-21.179688, -0.061635,  0.881086, -2.458301,  0.273389, -4.603578,  0.726455, -0.593764, -0.140191,  0.322677]),
-    ('H', [-21.117188,  0.414544,  0.444939, -1.375544,  0.012675, -5.888223, -0.366809, -0.233001, -0.077484,  0.395705]),
-    ('I', [-24.187500,  0.239964,  0.180533, -4.474660,  0.010479, -1.988788,  0.202812, -0.040681, -0.145335, -0.170450]),
-    ('J', [-24.789062, -0.365011, -1.542677, -2.867097, -0.688413, -2.470557,  0.909462,  0.996825,  0.051710,  2.295300]),
-    ('K', [-21.664062,  0.356033,  1.886853, -3.872515,  0.117591, -5.208298, -0.138047, -1.445287, -0.177452, -0.655992]),
-    ('L', [-25.593750, -0.923295,  2.244432, -2.220757,  0.346405, -2.359938,  0.814594, -1.297573,  0.481206, -2.376696]),
-    ('M', [-18.406250,  1.350361,  0.542274, -1.668330,  0.040686, -7.238306, -1.589606, -0.276051, -0.514539,  0.385285]),
-    ('N', [-18.929688,  0.447730,  0.479065, -2.867097,  0.416399, -6.752536, -0.215339, -0.192991, -0.080750,  0.333734]),
-    ('O', [-21.007812,  0.379488,  0.421322, -2.104748,  0.013769, -4.736576, -0.031906, -0.222900, -0.066026,  0.309816]),
-    ('P', [-22.664062,  1.837856,  1.596265, -2.568786, -0.420267, -4.502777, -1.876660, -0.679979, -0.694728, -1.097693]),
-    ('Q', [-19.468750, -0.978253,  0.010881, -2.949961,  0.389398, -3.853688, -0.320380, -0.504509,  0.678237,  0.892407]),
-    ('R', [-20.382812,  0.924624,  1.261365, -2.977582,  0.100143, -5.571790, -0.417643, -0.659433, -0.405686,  0.547843]),
-    ('S', [-22.718750,  0.274411,  0.224990, -3.513437,  0.329904, -3.469781,  0.059243, -0.018861, -0.147018,  0.110106]),
-    ('T', [-25.460938,  1.561839,  0.154652, -3.817272,  0.050030, -1.979819, -0.818250, -0.034012, -0.494736, -0.189627]),
-    ('U', [-21.765625, -0.054476,  0.385958, -1.568893,  0.028607, -4.795132,  0.245321, -0.198041,  0.659680,  0.354362]),
-    ('V', [-23.179688,  0.715139,  0.262105, -3.817272,  0.052421, -4.416215, -0.553110, -0.114981,  0.747905,  0.040918]),
-    ('W', [-19.906250,  0.003179,  0.422861, -3.458194,  0.050549, -6.621926,  0.340759, -0.215284,  1.178285,  0.183611]),
-    ('X', [-22.546875,  0.327342,  0.250659, -4.331029,  0.002333, -4.304321, -0.067554, -0.091347, -0.158510,  0.027721]),
-    ('Y', [-24.539062,  1.377002,  0.195181, -3.839369,  0.062966, -3.559084, -1.686085, -0.064870, -0.030778, -0.062908]),
-    ('Z', [-23.351562,  0.244906,  0.236737, -4.115582, -0.651134, -2.315642,  0.225567, -0.125914, -0.182088, -0.020327]),
-    ('[', [-22.742188, -0.214890,  2.780140, -3.806223, -0.075267,  0.151677, -0.322796, -1.158647,  0.085283, -3.118804]),
-    ('\\',[-25.593750, -0.103387,  0.170025, -3.303514,  1.522461, -1.556870, -0.099181, -0.126896,  0.212933, -0.022591]),
-    (']', [-22.906250, -0.206061, -2.268702, -4.231592,  0.058555,  0.218389, -0.308281,  1.148073,  0.089876,  3.415210]),
-    ('^', [-28.789062,  1.792508,  0.091355, -1.508126,  0.049317, -0.982663, -2.421526, -0.030383, -0.932512,  0.004016]),
-    ('_', [-29.820312, -1.786733,  0.070940, -0.889408, -0.060062,  0.777963,  0.427701,  0.031054,  0.728649,  0.012472]),
-    ('`', [-30.273438,  1.281634,  0.377341, -1.055136,  0.319417,  0.247545, -0.709477,  0.167055, -0.763505, -0.604757]),
-    ('a', [-22.882812, -2.044169, -0.272188, -3.054922,  0.009604, -5.289057,  2.390256,  0.304261,  0.504303,  0.310065]),
-    ('b', [-21.710938, -0.863091,  1.505796, -2.005311,  0.467237, -5.362862,  0.770163, -0.420108,  0.370968, -0.103511]),
-    ('c', [-24.835938, -1.384317,  1.023141, -2.126844, -0.211549, -3.988225,  1.157078, -0.787506,  0.383002, -0.142766]),
-    ('d', [-21.843750, -0.897785, -0.736569, -2.209709, -0.517137, -5.339458,  0.811523,  0.006587,  0.275580,  0.675821]),
-    ('e', [-22.929688, -1.706877,  0.622571, -2.933388, -0.179431, -5.714520,  2.020030, -0.422397,  0.553496, -0.012721]),
-    ('f', [-24.554688,  1.447436,  0.245120, -4.192922, -0.454684, -3.534565, -1.783444, -0.509512, -0.522445, -1.183809]),
-    ('g', [-20.007812, -4.601786, -0.017301, -3.297990,  0.028617, -2.984084,  0.471007, -0.078044,  1.550148,  0.347994]),
-    ('h', [-22.726562, -0.212798,  1.406198, -1.287155,  0.578000, -5.268217,  0.139215, -0.471167, -0.185641, -0.026379]),
-    ('i', [-25.296875, -0.768052,  0.373025, -3.988524,  0.101222, -2.478750,  0.719876, -0.210152,  0.247224, -0.369772]),
-    ('j', [-23.765625, -2.434944, -0.225871, -4.607243, -0.643026, -0.477951, -1.323584,  1.036065,  0.780308,  2.341824]),
-    ('k', [-22.882812, -0.675447,  2.144093, -3.198553,  0.252043, -5.122835,  1.051329, -1.171199,  0.464536, -0.929968]),
-    ('l', [-24.992188, -0.381748,  0.338387, -4.192922,  0.224972, -2.141264,  0.534718,  0.095957,  0.073721, -0.265732]),
-    ('m', [-20.914062, -1.815744,  0.523698, -2.292573, -0.030414, -7.030564,  2.053936, -0.340365,  0.172793,  0.123905]),
-    ('n', [-24.117188, -1.096497,  0.497728, -1.287155,  0.000696, -5.072948,  1.095973, -0.343601, -0.185641,  0.349921]),
-    ('o', [-23.687500, -1.601338,  0.289438, -2.154466, -0.058681, -4.941063,  1.688719, -0.187663,  0.378737,  0.179082]),
-    ('p', [-21.664062, -3.041747,  1.546626, -1.994262, -0.962356, -4.267579,  1.348060,  0.296564,  0.367452, -0.120423]),
-    ('q', [-21.781250, -2.949914, -0.777399, -2.209709,  0.730617, -4.305420,  1.344067, -0.622644,  0.518247,  0.692734]),
-    ('r', [-26.875000, -0.231429,  0.824861, -2.463825, -0.467170, -3.490445, -0.180412, -0.409141,  0.158152, -1.848484]),
-    ('s', [-24.343750, -1.529653,  0.195394, -3.303514,  0.097704, -4.396863,  1.499120, -0.220578,  0.625398,  0.038654]),
-    ('t', [-24.179688, -0.243258,  0.343143, -3.960903,  0.730226, -3.701440, -0.123235, -0.513858, -0.129830, -1.726683]),
-    ('u', [-24.132812, -1.990543,  0.124154, -1.309253,  0.058266, -4.429024,  2.333935, -0.174800,  0.694964,  0.169813]),
-    ('v', [-25.507812, -1.060046,  0.182197, -2.845000, -0.006952, -4.296026,  1.360911, -0.133564,  1.038488,  0.050100]),
-    ('w', [-23.234375, -1.954892,  0.297169, -2.508019, -0.040788, -5.579309,  2.802272, -0.204906,  1.194064,  0.140004]),
-    ('x', [-24.820312, -1.406142,  0.194155, -3.375330, -0.037798, -4.350866,  1.577819, -0.106498,  0.685004,  0.021228]),
-    ('y', [-24.085938, -2.333444,  0.411166, -4.038242, -0.221094, -3.441071,  1.018816,  0.042772,  1.898888, -0.523097]),
-    ('z', [-24.726562, -1.385258,  0.200135, -3.375330, -0.324105, -3.633438,  0.749318,  0.132319,  0.634341,  0.006793]),
-    ('{', [-23.804688, -0.203985,  0.034213, -5.098903, -0.000923, -1.509071, -0.122314, -0.880701,  0.121482, -0.307164]),
-    ('|', [-25.820312, -0.151578,  0.128558, -4.369699, -0.002932, -1.530989, -0.072253, -0.032672,  0.107182, -0.310366]),
-    ('}', [-23.812500, -0.210658,  0.306226, -5.038136, -0.004737, -1.456795, -0.130692,  0.826880,  0.126706, -0.126843]),
-    ('~', [-27.789062, -0.437989,  0.137138, -1.265058, -0.016172, -3.733959,  1.073460, -0.124296,  0.129772,  0.056805]),
-];
+const SIXEL_SUFFIXES: &[&str] = &["six", "sixel"];
+
+fn main_render(
+    ParamRender {
+        shoal_dir_or_file,
+        output_file,
+        font,
+        cell,
+        frame,
+    }: ParamRender,
+) {
+    let font = util::purify_opt(
+        &format!("Failed to open font \"{}\"", font.to_string_lossy()),
+        Font::try_from_vec(util::purify_err(
+            &format!("Failed to access font \"{}\"", font.to_string_lossy()),
+            std::fs::read(&font),
+        )),
+    );
+    let art = art::open_art(&shoal_dir_or_file).unwrap();
+    let frame = util::purify_opt(
+        &format!("Frame {} out of {}", frame, art.frames.len()),
+        art.frames.get(frame),
+    );
+    let img = export::render(frame, &font, cell, art::Ambiguous::Narrow);
+    if output_file == Path::new("-") {
+        let mut out = stdout();
+        export::export_sixel(&mut out, &img).unwrap();
+        out.flush().unwrap();
+        return;
+    }
+    println!("Rendered {}x{}.", img.width(), img.height());
+    let sixel = output_file
+        .extension()
+        .map_or(false, |e| SIXEL_SUFFIXES.iter().any(|s| e == *s));
+    match sixel {
+        true => {
+            let mut buf = Vec::<u8>::with_capacity(1 << 16);
+            export::export_sixel(&mut buf, &img).unwrap();
+            try_again!(
+                std::fs::write(&output_file, &buf),
+                "Failed to write \"{}\": {:?}",
+                output_file.to_string_lossy(),
+            );
+        }
+        false => try_again!(
+            img.save(&output_file),
+            "Failed to save \"{}\": {:?}",
+            output_file.to_string_lossy(),
+        ),
+    }
+}
+
+fn main_live(
+    ParamLive {
+        camera,
+        screen,
+        charset,
+        crop,
+        resize,
+        zoom,
+        cell_aspect,
+        cell,
+        pad_color,
+        mode,
+        negate,
+        dither,
+        stabilize,
+        orient_weight,
+        weights,
+        bias,
+        metric,
+        quadtree,
+        flat_threshold,
+        bold_threshold,
+        blank_char,
+        blank_threshold,
+        cell_color,
+        linear_color,
+        smooth_color,
+        smooth_threshold,
+        sx,
+        sy,
+        max_fps,
+        monoch,
+        colors,
+        no_sync,
+    }: ParamLive,
+) {
+    let cs = match &charset {
+        Some(p) => read_charset(p).unwrap(),
+        None => Charset::builtin(),
+    };
+    let cell = cell.unwrap_or(cs.cell);
+    let cell_aspect = cell_aspect.map(|a| relative_aspect(a, cell));
+    let bias = bias.as_ref().map(|p| {
+        util::purify_err(
+            &format!("Invalid bias \"{}\"", p.to_string_lossy()),
+            art::read_bias(p),
+        )
+    });
+    let mut src = match (camera, screen) {
+        (Some(n), _) => live::Camera::open(n.unwrap_or(0)).map(live::Source::Camera),
+        (_, Some(n)) => live::Screen::open(n).map(live::Source::Screen),
+        (None, None) => panic!("No live source, specify `--camera` or `--screen`"),
+    }
+    .unwrap();
+    let avg = if max_fps > 0. { 1. / max_fps } else { 0. };
+    let colors = (!monoch).then(|| colors.detect());
+    let matching = art::Matching {
+        dither,
+        stabilize,
+        orient: orient_weight,
+        metric,
+        weights,
+        quadtree: quadtree.map(|q| (q, flat_threshold)),
+        bias: bias.as_ref(),
+        blank: blank(blank_char, blank_threshold, art::Ambiguous::Narrow),
+        bold: bold_threshold,
+        prev: None,
+        vertical: false,
+    };
+    let sampling = art::Sampling {
+        cell,
+        cell_color,
+        linear: linear_color,
+        ambiguous: art::Ambiguous::Narrow,
+    };
+    let mut plugins = plugin::Plugins::none();
+    let mut out = stdout();
+    enable_raw_mode().ok();
+    queue!(out, EnterAlternateScreen, HideCursor).ok();
+    let sync = !no_sync && out.is_tty();
+    let mut now = Instant::now();
+    let mut prev = None;
+    'live: loop {
+        let made = src.frame().and_then(|f| {
+            let mut img = util::img3(DynamicImage::ImageRgb8(f), crop, resize, zoom, Triangle);
+            if let Some(a) = cell_aspect {
+                img = util::squash(img, a, Triangle);
+            }
+            img = util::pad(img, cell, pad_color);
+            let color = img.to_rgb8();
+            match mode {
+                art::Mode::Glyph => {
+                    let mut draft = img.to_luma8();
+                    if negate {
+                        draft.pixels_mut().for_each(|Luma([n])| *n = 255 - *n);
+                    }
+                    let matching = art::Matching {
+                        prev: prev.as_ref(),
+                        ..matching
+                    };
+                    make_art(&draft, &color, &cs, matching, sampling, &mut plugins)
+                }
+                art::Mode::HalfBlock => make_halfblock(&color, sampling, &mut plugins),
+            }
+        });
+        if sync {
+            queue!(out, Print(art::SYNC_BEGIN)).ok();
+        }
+        match made {
+            Ok(mut frame) => {
+                if let (true, Some(old)) = (smooth_color > 0., &prev) {
+                    let (weight, threshold) = (smooth_color, smooth_threshold);
+                    smooth_colors(&mut frame, old, weight, threshold, sampling.ambiguous);
+                }
+                match &prev {
+                    Some(p) => play_diff(&mut out, p, &frame, sx, sy, colors, sampling.ambiguous),
+                    None => play_art(&mut out, &frame, sx, sy, colors),
+                }
+                .ok();
+                prev = Some(frame);
+            }
+            Err(e) => {
+                prev = None;
+                queue!(
+                    out,
+                    MoveTo(sx, sy),
+                    ResetColor,
+                    Print(format!("Invalid frame: {}", e))
+                )
+                .ok();
+            }
+        }
+        if sync {
+            queue!(out, Print(art::SYNC_END)).ok();
+        }
+        out.flush().ok();
+        let due = now + Duration::from_secs_f32(avg);
+        loop {
+            use crossterm::event::*;
+            if !poll(due.saturating_duration_since(Instant::now())).unwrap_or(false) {
+                break;
+            }
+            match read().ok() {
+                Some(Event::Key(k)) => {
+                    if (k.code == KeyCode::Char('c') && k.modifiers.contains(KeyModifiers::CONTROL))
+                        || k.code == KeyCode::Esc
+                    {
+                        break 'live;
+                    }
+                }
+                Some(Event::Resize(..)) => {
+                    prev = None;
+                    queue!(out, Clear(ClearType::All)).ok();
+                }
+                _ => (),
+            }
+        }
+        now = Instant::now();
+    }
+    queue!(out, LeaveAlternateScreen, ShowCursor, ResetColor).ok();
+    out.flush().ok();
+    disable_raw_mode().ok();
+}
+
+/// How long telnet clients have to tell their terminal types.
+const NEGOTIATE_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn main_serve(
+    ParamServe {
+        shoal_dir_or_file,
+        bind,
+        port,
+        max_clients,
+        fps,
+        monoch,
+        colors,
+    }: ParamServe,
+) {
+    let art = Arc::new(art::open_art(&shoal_dir_or_file).unwrap());
+    if art.frames.is_empty() {
+        panic!("No inputs")
+    }
+    let avg = Duration::from_secs_f32(if fps > 0. { 1. / fps } else { 0. });
+    let listener = util::purify_err(
+        &format!("Failed to listen on {}:{}", bind, port),
+        TcpListener::bind((bind.as_str(), port)),
+    );
+    println!(
+        "Serving {} frames on {}:{}, press CTRL-C to stop.",
+        art.frames.len(),
+        bind,
+        port
+    );
+    let clients = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                println!("Failed to accept: {:?}", e);
+                continue;
+            }
+        };
+        if clients.fetch_add(1, Ordering::SeqCst) >= max_clients {
+            clients.fetch_sub(1, Ordering::SeqCst);
+            let peer = stream.peer_addr().map_or(String::new(), |a| a.to_string());
+            println!("[{}] Refused, {} clients already", peer, max_clients);
+            stream
+                .write_all(b"Too many clients, try again later.\r\n")
+                .ok();
+            continue;
+        }
+        let art = Arc::clone(&art);
+        let clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map_or(String::new(), |a| a.to_string());
+            let colors = match (monoch, colors) {
+                (true, _) => None,
+                (false, art::Colors::Auto) => Some(
+                    serve::terminal_type(&mut stream, NEGOTIATE_TIMEOUT)
+                        .map_or(art::Colors::Ansi256, |t| art::Colors::from_term(&t)),
+                ),
+                (false, c) => Some(c),
+            };
+            println!("[{}] Connected, colors: {:?}", peer, colors);
+            let e = serve::stream_art(&mut io::BufWriter::new(stream), &art, colors, avg);
+            println!("[{}] Disconnected: {:?}", peer, e);
+            clients.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+fn main_import(
+    ParamImport {
+        text_file,
+        output_file,
+        color,
+        cell_color,
+        linear_color,
+        compress,
+    }: ParamImport,
+) {
+    if ansi::is_ans(&text_file) {
+        let bytes = util::purify_err(
+            &format!(
+                "Failed to read ANSI art \"{}\"",
+                text_file.to_string_lossy()
+            ),
+            std::fs::read(&text_file),
+        );
+        let mut frames = ansi::import_ans(&bytes);
+        let (w, h) = frames.last().unwrap().dimensions();
+        println!("{}x{}, {} frame(s).", w, h, frames.len());
+        if art::is_anim(&output_file) {
+            let mut anim = Art::default();
+            frames
+                .into_iter()
+                .for_each(|f| anim.push(f, Duration::ZERO));
+            try_again!(
+                write_anim_as(&output_file, &anim, compress, art::Channels::Rgb),
+                "Failed to write art \"{}\": {:?}",
+                output_file.to_string_lossy(),
+            );
+        } else {
+            try_again!(
+                write_art_as(
+                    &output_file,
+                    &frames.pop().unwrap(),
+                    compress,
+                    art::Channels::Rgb
+                ),
+                "Failed to write art \"{}\": {:?}",
+                output_file.to_string_lossy(),
+            );
+        }
+        return;
+    }
+    let text = util::purify_err(
+        &format!("Failed to read text \"{}\"", text_file.to_string_lossy()),
+        std::fs::read_to_string(&text_file),
+    );
+    let color = color.map(|p| {
+        util::purify_err(
+            &format!("Failed to open image \"{}\"", p.to_string_lossy()),
+            image::open(&p),
+        )
+        .to_rgb8()
+    });
+    let sampling = art::Sampling {
+        cell_color,
+        linear: linear_color,
+        ..Default::default()
+    };
+    let frame = art::import_text(&text, color.as_ref(), sampling);
+    let (w, h) = frame.dimensions();
+    println!("{}x{}.", w, h);
+    try_again!(
+        write_art_as(&output_file, &frame, compress, art::Channels::Rgb),
+        "Failed to write art \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
@@ -1,31 +1,91 @@
 use crate::*;
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont, VariableFont};
+use crossterm::{
+    cursor::{Hide as HideCursor, MoveTo, Show as ShowCursor},
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
 use image::{
     imageops::{self, Nearest, Triangle},
-    GrayImage, Luma,
+    GrayImage, Luma, Rgb,
 };
+use rayon::prelude::*;
 use rusttype::{point, Font, Scale};
+use shoalart::charset::{
+    brightness, expand_chars, read_charset, write_charset, Charset, GenMode, Meta, Orient,
+    MAX_COEFFS,
+};
+use shoalart::{
+    art::{glyph_width, Ambiguous, Cell, Frame},
+    cluster::{self, Glyph},
+    export,
+    interchange::{self, Table},
+    live,
+};
 use std::{
-    fs::{self, File},
-    io::{self, stdout, Read, Write},
+    fs,
+    io::{self, stdin, stdout, BufReader, BufWriter, Write},
+    sync::mpsc,
+    thread,
+    time::Duration,
 };
-use unicode_width::UnicodeWidthChar;
 
 /// Routines about charset
 #[derive(StructOpt, Debug)]
 pub enum Param {
     Gen(ParamGen),
+    Atlas(ParamAtlas),
+    Calibrate(ParamCalibrate),
     Merge(ParamMerge),
+    Subtract(ParamSubtract),
+    Intersect(ParamIntersect),
+    Dedupe(ParamDedupe),
+    Analyze(ParamAnalyze),
+    Sheet(ParamSheet),
     Read(ParamRead),
+    Export(ParamExport),
+    Import(ParamImport),
+    EmitRust(ParamEmitRust),
 }
 
 /// Custom your own charset
 #[derive(StructOpt, Debug)]
 pub struct ParamGen {
-    chars: String,
-    #[structopt(parse(from_os_str))]
-    font_file: PathBuf,
+    /// Chars to be included, or comma separated ranges of them
+    ///
+    /// Syntax: `U+{hex}..U+{hex}`, `U+{hex}`, or names of Unicode blocks, e.g.
+    /// `U+0020..U+007E,U+3000,hiragana`; Names are ascii, latin1, greek, cyrillic, box, block,
+    /// geometric, braille, cjk-punct, hiragana, katakana, cjk, hangul and fullwidth.
+    /// Taken literally if any item is not of them.
+    ///
+    /// Omitted with `--chars-file`, then the arguments after are shifted here.
+    #[structopt(required_unless = "chars-file")]
+    chars: Option<String>,
+    /// Omitted with `--font-name`, then the path given here is taken as the output
+    #[structopt(parse(from_os_str), required_unless_one = &["font-name", "chars-file"])]
+    font_file: Option<PathBuf>,
     #[structopt(default_value = "Shoalart-Charset.bin", parse(from_os_str))]
     output_file: PathBuf,
+    /// Find the font installed in the system by its family name, e.g. `"Sarasa Term SC"`
+    #[structopt(long)]
+    font_name: Option<String>,
+    /// Face to be used in a TrueType Collection (`.ttc`), counting from 0; Ignored with `--font-name`
+    #[structopt(long, default_value = "0")]
+    face_index: u32,
+    /// Set axes of variable fonts, e.g. `wght=600,wdth=90`
+    ///
+    /// Syntax: `{tag}={value}`, separated by commas; Tags are of 4 letters.
+    #[structopt(long, use_delimiter = true, parse(try_from_str = opt_axis))]
+    variation: Vec<([u8; 4], f32)>,
+    /// Read chars from a UTF-8 file instead, duplicates are ignored
+    ///
+    /// Each line is either chars or ranges of them, like `chars`; Whitespaces are ignored.
+    #[structopt(long, parse(from_os_str))]
+    chars_file: Option<PathBuf>,
+    /// Keep whitespaces other than line breaks in `--chars-file`, e.g. `U+3000`
+    #[structopt(long)]
+    keep_whitespace: bool,
 
     /// Use `Compatibility` with optional specified offsets instead of `Adaptive` mode
     #[structopt(short = "C", long = "compat")]
@@ -35,6 +95,108 @@ pub struct ParamGen {
     /// SYNTAX: {width}x{height}+{left}+{top} (unit: px; Negatives are available for offsets)
     #[structopt(short = "A", long = "off", default_value = "64x64+0+0", parse(try_from_str = opt_crop))]
     compat_area: (i32, i32, i32, i32),
+    /// Use `Hybrid` instead of `Adaptive` mode: Glyphs are fitted by metrics of the font,
+    /// i.e. the advance and the ascent to the descent, so that small ones like `.` stay small;
+    /// Ink beyond them widens the area by up to half, the rest is cut
+    #[structopt(short = "H", long = "hybrid", conflicts_with = "compat-mode")]
+    hybrid_mode: bool,
+    /// Size of half-width cells of your terminal, glyphs are fitted in the proportion of cells
+    /// in `Adaptive` and `Hybrid` modes; `art make` divides images into blocks of this size
+    /// by default
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, default_value = "4x8", parse(try_from_str = opt_cell))]
+    cell: (u32, u32),
+    /// Width of East Asian ambiguous chars on your terminal, e.g. `○` and `─`;
+    /// `wide` for CJK terminals drawing them in two columns
+    #[structopt(long, default_value = "narrow", possible_values = Ambiguous::NAMES)]
+    ambiguous: Ambiguous,
+
+    /// Drop glyphs of almost the same features, see `charset dedupe`
+    ///
+    /// Syntax: The threshold, e.g. `0.5`
+    #[structopt(long)]
+    dedupe: Option<f32>,
+    /// Keep glyphs downsampled to `8x8` in the charset, for inspection without the font
+    #[structopt(long)]
+    bitmaps: bool,
+    /// Leading DCT coefficients of features to keep, within `1..=10`; Fewer make the charset
+    /// smaller and compare fewer terms, but match less accurately
+    #[structopt(long, default_value = "10")]
+    coeffs: usize,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+
+    /// (For debugging)
+    #[structopt(long)]
+    dump: bool,
+}
+
+/// Custom a charset from glyphs drawn in an image, e.g. of pixel fonts or by hand
+#[derive(StructOpt, Debug)]
+pub struct ParamAtlas {
+    /// Glyphs are light on dark, or opaque on transparent
+    #[structopt(parse(from_os_str))]
+    atlas_image: PathBuf,
+    /// Where each glyph is in the atlas, one per line
+    ///
+    /// Syntax: `{char} {width}x{height}+{left}+{top}` (unit: px), e.g. `A 8x16+8+0`;
+    /// Chars can also be written as `U+{hex}`, e.g. `U+2588 16x16+0+16`.
+    /// Rects are taken as whole cells, full width glyphs are of double width.
+    #[structopt(parse(from_os_str))]
+    map_file: PathBuf,
+    #[structopt(default_value = "Shoalart-Charset.bin", parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Glyphs are dark on light instead
+    #[structopt(long)]
+    invert: bool,
+    /// Size of half-width cells of your terminal; Taken from the first half width glyph by default
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_cell))]
+    cell: Option<(u32, u32)>,
+    /// Keep glyphs downsampled to `8x8` in the charset, see `charset gen`
+    #[structopt(long)]
+    bitmaps: bool,
+    /// Leading DCT coefficients of features to keep, see `charset gen`
+    #[structopt(long, default_value = "10")]
+    coeffs: usize,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// Custom a charset from glyphs drawn by your terminal, which are captured from the screen,
+/// as rendering of terminals differs from fonts rasterized by `gen` (hinting, padding of cells)
+///
+/// Keep the terminal uncovered on the display while glyphs are flashing.
+#[derive(StructOpt, Debug)]
+pub struct ParamCalibrate {
+    /// Chars to be included, or comma separated ranges of them, see `charset gen`
+    chars: String,
+    #[structopt(default_value = "Shoalart-Charset.bin", parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Display the terminal is on, counting from 0; The primary one by default
+    #[structopt(long)]
+    display: Option<usize>,
+    /// How long to wait for the terminal to draw before capturing (unit: ms)
+    #[structopt(long, default_value = "300")]
+    delay: u64,
+    /// Keep glyphs downsampled to `8x8` in the charset, see `charset gen`
+    #[structopt(long)]
+    bitmaps: bool,
+    /// Leading DCT coefficients of features to keep, see `charset gen`
+    #[structopt(long, default_value = "10")]
+    coeffs: usize,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
 
     /// (For debugging)
     #[structopt(long)]
@@ -48,6 +210,113 @@ pub struct ParamMerge {
     output_file: PathBuf,
     #[structopt(required = true, parse(from_os_str))]
     charset_files: Vec<PathBuf>,
+
+    /// What to do with a char in several charsets: keep the `first` or the `last` one,
+    /// `average` features of them, or stop with an `error`
+    #[structopt(long, default_value = "last", possible_values = Conflict::NAMES)]
+    on_conflict: Conflict,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// Drop glyphs which are also in the other charset
+#[derive(StructOpt, Debug)]
+pub struct ParamSubtract {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+    /// Only chars of it matter, features are of the former
+    #[structopt(parse(from_os_str))]
+    other_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// Keep only glyphs which are also in the other charset
+#[derive(StructOpt, Debug)]
+pub struct ParamIntersect {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+    /// Only chars of it matter, features are of the former
+    #[structopt(parse(from_os_str))]
+    other_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// Drop glyphs of almost the same features, which slow matching down without making
+/// arts better, e.g. `O` for `0`
+#[derive(StructOpt, Debug)]
+pub struct ParamDedupe {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Maximum sum of absolute differences of features to be taken as the same
+    #[structopt(short, long, default_value = "0.5")]
+    threshold: f32,
+    /// Print glyphs dropped, with those kept for them
+    #[structopt(short, long)]
+    verbose: bool,
+
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// Report how well glyphs of a charset can be told apart: The closest pairs, coverage of
+/// brightness, and glyphs to add from another charset
+#[derive(StructOpt, Debug)]
+pub struct ParamAnalyze {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+
+    /// Pairs within this sum of absolute differences of features are nearly indistinguishable,
+    /// see `charset dedupe`
+    #[structopt(short, long, default_value = "0.5")]
+    threshold: f32,
+    /// Pairs and suggestions to list at most
+    #[structopt(long, default_value = "10")]
+    top: usize,
+    /// Ranges the brightness `0..=255` is divided into for coverage
+    #[structopt(long, default_value = "16")]
+    bins: usize,
+    /// Charset to suggest glyphs from, e.g. generated from all chars of the font;
+    /// The built-in charset by default
+    #[structopt(long, parse(from_os_str))]
+    from: Option<PathBuf>,
+}
+
+/// Render all glyphs of a charset into one image, each with the code point, the width
+/// (`H`alf or `F`ull), the first three features, and the block reconstructed from features
+#[derive(StructOpt, Debug)]
+pub struct ParamSheet {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+    #[structopt(default_value = "Shoalart-Sheet.png", parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Font to be used, preferably the one generated the charset from
+    #[structopt(short = "F", long, parse(from_os_str))]
+    font: PathBuf,
+    /// Size of a half-width cell
+    ///
+    /// Syntax: `{width}x{height}` (unit: px; Positive numbers only)
+    #[structopt(long, default_value = "16x32", parse(try_from_str = opt_cell))]
+    cell: (u32, u32),
+    /// Glyphs per row
+    #[structopt(long, default_value = "16")]
+    columns: usize,
 }
 
 /// Open a charset
@@ -55,92 +324,134 @@ pub struct ParamMerge {
 pub struct ParamRead {
     #[structopt(parse(from_os_str))]
     charset_file: PathBuf,
+
+    /// Verify the font or atlas image is the one the charset was generated from
+    #[structopt(long, parse(from_os_str))]
+    verify: Option<PathBuf>,
+    /// Draw blocks reconstructed from features with shades instead of printing features
+    #[structopt(long)]
+    render: bool,
+}
+
+/// Export a charset as JSON or CSV, to be edited or audited
+#[derive(StructOpt, Debug)]
+#[structopt(after_help = interchange::CHARSET_SCHEMA)]
+pub struct ParamExport {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
+    /// Printed to stdout if `-`
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    #[structopt(short, long, default_value = "json", possible_values = Table::NAMES)]
+    format: Table,
 }
 
-const CST_HEADER: &str = "Shoalart.v0 CHR";
-const CST_HEADER_LEN: usize = CST_HEADER.len();
-/// `width/bool`; `glyph/char`; `feature/f32*10`
-const CST_ITEM_LEN: usize = 1 + 4 + 10 * 4;
+/// Import a charset exported by `charset export`, or made by other programs
+#[derive(StructOpt, Debug)]
+#[structopt(after_help = interchange::CHARSET_SCHEMA)]
+pub struct ParamImport {
+    /// Read from stdin if `-`
+    #[structopt(parse(from_os_str))]
+    input_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
 
-const CANVAS_SIZE: u32 = 96;
-const FONT_SCALE: Scale = Scale { x: 64., y: 64. };
-const GLYPH_OFFSET: f32 = 16.;
+    #[structopt(short, long, default_value = "json", possible_values = Table::NAMES)]
+    format: Table,
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
 
-const BLACK: Luma<u8> = Luma([0]);
+/// Print half width glyphs of a charset as a Rust `const` array to stdout, for regenerating
+/// the built-in charset when features change
+#[derive(StructOpt, Debug)]
+pub struct ParamEmitRust {
+    #[structopt(parse(from_os_str))]
+    charset_file: PathBuf,
 
-////////////////////////////////////////
+    /// Name of the `const`
+    #[structopt(long, default_value = "BULITIN_CHARSET")]
+    name: String,
+}
 
-pub fn read_charset<P: AsRef<Path>>(p: P) -> Result<AHashMap<char, (bool, [f32; 10])>, String> {
-    let mut file = match File::open(p.as_ref()) {
-        Ok(f) => f,
-        Err(e) => Err(format!("Failed to open charset: {:?}", e))?,
-    };
-    let mut buf: [u8; CST_ITEM_LEN] = unsafe_init!();
-    if let Err(e) = file.read_exact(&mut buf[..CST_HEADER_LEN]) {
-        Err(format!("Failed to read charset: {:?}", e))?;
-    }
-    if &buf[..CST_HEADER_LEN] != CST_HEADER.as_bytes() {
-        Err(format!("Failed to parse charset: Invalid header"))?;
-    }
-    let mut comp = util::lz4read(file);
-    return match || -> io::Result<AHashMap<char, (bool, [f32; 10])>> {
-        let mut cs = AHashMap::with_capacity(384);
-        let mut n = comp.read(&mut buf)?;
-        while n == CST_ITEM_LEN {
-            let c = match char::from_u32(u32::from_be_bytes(buf[0..4].try_into().unwrap())) {
-                Some(c) => c,
-                None => continue,
-            };
-            let w = buf[4] != 0;
-            cs.insert(
-                c,
-                (
-                    w,
-                    (5..CST_ITEM_LEN)
-                        .step_by(4)
-                        .map(|i| f32::from_be_bytes(buf[i..i + 4].try_into().unwrap()))
-                        .collect::<Vec<_>>()
-                        .try_into()
-                        .unwrap(),
-                ),
-            );
-            n = comp.read(&mut buf)?;
-        }
-        Ok(cs)
-    }() {
-        Ok(cs) => Ok(cs),
-        Err(e) => Err(format!("Failed to parse charset: {:?}", e)),
-    };
+/// How `charset merge` resolves a char in several charsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Conflict {
+    First,
+    Last,
+    Average,
+    Error,
 }
 
-fn write_charset<'a, I, P: AsRef<Path>>(p: P, cs: I) -> io::Result<()>
-where
-    I: Iterator<Item = (&'a char, &'a bool, &'a [f32; 10])>,
-{
-    let mut file = File::create(p.as_ref())?;
-    file.write(CST_HEADER.as_bytes())?;
-    let mut comp = util::lz4write(file);
-    comp.write_all(b"\x00\x00\x00\x20\x00")?;
-    // 别特么忘了我们的值域是`[-1, 1)`！
-    comp.write_all(&(-32f32).to_be_bytes())?;
-    (1..10).try_for_each(|_| comp.write_all(&0f32.to_be_bytes()))?;
-    for (c, w, feat) in cs {
-        comp.write_all(&(*c as u32).to_be_bytes())?;
-        comp.write_all(&(*w as u8).to_be_bytes())?;
-        feat.iter()
-            .try_for_each(|f| comp.write_all(&f.to_be_bytes()))?;
+impl Conflict {
+    const NAMES: &'static [&'static str] = &["first", "last", "average", "error"];
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "first" => Ok(Conflict::First),
+            "last" => Ok(Conflict::Last),
+            "average" => Ok(Conflict::Average),
+            "error" => Ok(Conflict::Error),
+            _ => Err(format!("Unknown strategy \"{}\"", s)),
+        };
     }
-    comp.finish()?;
-    return Ok(());
 }
 
+/// Shades of `charset read --render`, from dark to light.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+/// Columns of `charset read --render`.
+const RENDER_WIDTH: usize = 80;
+
+/// Cells of each glyph in a row of `charset sheet`.
+const SHEET_ENTRY: usize = 16;
+const SHEET_GLYPH: [u8; 3] = [255, 255, 255];
+const SHEET_GLYPH_BG: [u8; 3] = [48, 48, 48];
+const SHEET_LABEL: [u8; 3] = [160, 160, 160];
+
+/// Cells between the two marks of `charset calibrate`, the size of cells is measured by them.
+const CALIBRATE_MARK: u16 = 8;
+/// Minimum difference of brightness to be taken as drawn, in captures.
+const CALIBRATE_THRESHOLD: u8 = 128;
+
+const CANVAS_SIZE: u32 = 96;
+const FONT_SCALE: Scale = Scale { x: 64., y: 64. };
+const GLYPH_OFFSET: f32 = 16.;
+
+const BLACK: Luma<u8> = Luma([0]);
+
 ////////////////////////////////////////
 
 pub fn main(param: Param) {
     match param {
         Param::Gen(param) => main_gen(param),
+        Param::Atlas(param) => main_atlas(param),
+        Param::Calibrate(param) => main_calibrate(param),
         Param::Merge(param) => main_merge(param),
+        Param::Subtract(ParamSubtract {
+            charset_file,
+            other_file,
+            output_file,
+            compress,
+        }) => main_filter(charset_file, other_file, output_file, compress, false),
+        Param::Intersect(ParamIntersect {
+            charset_file,
+            other_file,
+            output_file,
+            compress,
+        }) => main_filter(charset_file, other_file, output_file, compress, true),
+        Param::Dedupe(param) => main_dedupe(param),
+        Param::Analyze(param) => main_analyze(param),
+        Param::Sheet(param) => main_sheet(param),
         Param::Read(param) => main_read(param),
+        Param::Export(param) => main_export(param),
+        Param::Import(param) => main_import(param),
+        Param::EmitRust(param) => main_emit_rust(param),
     }
 }
 
@@ -149,157 +460,798 @@ fn main_gen(
         chars,
         font_file,
         output_file,
+        font_name,
+        face_index,
+        variation,
+        chars_file,
+        keep_whitespace,
         compat_mode,
         compat_area,
+        hybrid_mode,
+        cell,
+        ambiguous,
+        dedupe,
+        bitmaps,
+        coeffs,
+        compress,
         dump,
     }: ParamGen,
 ) {
-    let font = util::purify_opt(
-        &format!("Failed to open font \"{}\"", font_file.to_string_lossy()),
-        Font::try_from_vec(util::purify_err(
-            &format!("Failed to access font \"{}\"", font_file.to_string_lossy()),
-            fs::read(&font_file),
-        )),
-    );
+    // Positional arguments are shifted for those given by options.
+    let mut args = chars
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(font_file)
+        .chain(Some(output_file));
+    let chars = match chars_file {
+        Some(p) => util::purify_err(
+            &format!("Failed to read chars \"{}\"", p.to_string_lossy()),
+            fs::read_to_string(&p),
+        )
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| match expand_chars(line.trim()) {
+            Some(chars) => chars,
+            None => line
+                .chars()
+                .filter(|c| !c.is_whitespace() || (keep_whitespace && *c != '\r'))
+                .collect(),
+        })
+        .collect::<String>(),
+        None => {
+            let chars = args.next().unwrap().to_string_lossy().into_owned();
+            expand_chars(&chars).unwrap_or(chars)
+        }
+    };
+    let (face, mut source, hash) = match font_name {
+        Some(name) => {
+            let (data, index) = util::purify_err("Failed to find font", find_font(&name));
+            let hash = util::crc32(&data);
+            let face = util::purify_err(
+                &format!("Failed to open font \"{}\"", name),
+                open_face(data, index, &variation),
+            );
+            (face, name, hash)
+        }
+        None => {
+            let font_file = util::purify_opt("No font", args.next());
+            let data = util::purify_err(
+                &format!("Failed to access font \"{}\"", font_file.to_string_lossy()),
+                fs::read(&font_file),
+            );
+            let hash = util::crc32(&data);
+            let face = util::purify_err(
+                &format!("Failed to open font \"{}\"", font_file.to_string_lossy()),
+                open_face(data, face_index, &variation),
+            );
+            let name = font_file.file_name().unwrap_or_default();
+            (face, name.to_string_lossy().into_owned(), hash)
+        }
+    };
+    for (tag, value) in &variation {
+        source += &format!(" {}={}", String::from_utf8_lossy(tag), value);
+    }
+    let output_file = args.next().unwrap();
     let dump = util::whether_dump(dump, "ShoalartDump-Charset");
-    let ascent = {
-        let v = font.v_metrics(FONT_SCALE);
-        v.ascent + v.line_gap
+    let mode = match (compat_mode, hybrid_mode) {
+        (true, _) => GenMode::Compat,
+        (_, true) => GenMode::Hybrid,
+        _ => GenMode::Adaptive,
     };
-    let mut block: [[f32; 8]; 8] = unsafe_init!();
-    let set_cs = AHashSet::<_>::from_iter(chars.chars());
-    let mut cs = Vec::<(char, bool, [f32; 10])>::with_capacity(set_cs.len());
-    for (ctr, c) in set_cs.into_iter().enumerate() {
-        if ctr % 20 == 0 {
+    let set_cs = AHashSet::<_>::from_iter(cluster::split(&chars));
+    let (tx, rx) = mpsc::channel::<&str>();
+    let cs = thread::scope(|s| {
+        // Progress is printed by one thread, so that marks are not torn apart.
+        s.spawn(move || {
+            for (n, mark) in rx.into_iter().enumerate() {
+                print!("{}", mark);
+                if n % 20 == 0 {
+                    stdout().flush().ok();
+                }
+            }
             stdout().flush().ok();
+        });
+        return Vec::from_iter(set_cs)
+            .into_par_iter()
+            .map_with(tx, |tx, c| {
+                #[rustfmt::skip]
+                let w = match glyph_width(c, ambiguous) {
+                    Some(w) => w - 1 != 0, // false for half & true for full
+                    None => { tx.send("K").ok(); return None } // Skipped
+                };
+                let mut canvas = GrayImage::new(CANVAS_SIZE, CANVAS_SIZE);
+                let offset = match mode {
+                    GenMode::Compat => (compat_area.2, compat_area.3),
+                    _ => (0, 0),
+                };
+                if face.paint(c, offset, &mut canvas).is_none() {
+                    tx.send("K").ok(); // Skipped
+                    return None;
+                }
+                let img = match mode {
+                    GenMode::Compat => {
+                        let (width, height, ..) = compat_area;
+                        let real = imageops::crop_imm(
+                            &canvas,
+                            GLYPH_OFFSET as u32,
+                            GLYPH_OFFSET as u32,
+                            width as u32,
+                            height as u32,
+                        )
+                        .to_image();
+                        imageops::resize(&real, 8, 8, Triangle)
+                    }
+                    GenMode::Hybrid => {
+                        let area = hybrid_area(face.em_box(c), ink_bounds(&canvas));
+                        fit_cell(&canvas, area, w, cell)
+                    }
+                    _ => fit_cell(&canvas, ink_bounds(&canvas), w, cell),
+                };
+                let (feat, orient) = features(&img, w);
+                if let Some(p) = &dump {
+                    canvas.save(p.join(format!("_{}.png", dump_name(c)))).ok();
+                    if !w {
+                        imageops::resize(&imageops::crop_imm(&img, 0, 0, 4, 8), 24, 48, Nearest)
+                    } else {
+                        imageops::resize(&img, 48, 48, Nearest)
+                    }
+                    .save(p.join(format!("{}.png", dump_name(c))))
+                    .ok();
+                }
+                tx.send(".").ok(); // OK!
+                return Some(((c, w, feat, orient), bitmap(&img)));
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+    });
+    println!("\nTotally {} chars.", cs.len() + 1);
+    let maps = match bitmaps {
+        true => cs.iter().map(|((c, ..), bitmap)| (*c, *bitmap)).collect(),
+        false => AHashMap::new(),
+    };
+    let mut cs = Charset {
+        cell,
+        meta: Some(Meta::new(mode, source, hash, FONT_SCALE.y)),
+        bitmaps: maps,
+        ..cs.into_iter().map(|(glyph, _)| glyph).collect()
+    };
+    cs.truncate_coeffs(coeffs);
+    if let Some(threshold) = dedupe {
+        let dropped = cs.dedupe(threshold);
+        println!("Dropped {} near duplicates.", dropped.len());
+    }
+    try_again!(
+        write_charset(&output_file, &cs, compress),
+        "Failed to write charset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+/// Fonts are read by `rusttype`, except variable ones with axes set, by `ab_glyph`.
+enum Face {
+    Fixed(Font<'static>),
+    Variable(FontVec),
+}
+
+impl Face {
+    /// Paint the glyph at `GLYPH_OFFSET` plus the offset; `None` if it's blank.
+    ///
+    /// Chars of a cluster follow each other without shaping, combining marks do not advance.
+    fn paint(&self, c: Glyph, (left, top): (i32, i32), canvas: &mut GrayImage) -> Option<()> {
+        let text = c.as_str();
+        let mut blank = true;
+        let mut put = |x: i32, y: i32, a: f32| {
+            if (x >= 0 && x < CANVAS_SIZE as i32) && (y >= 0 && y < CANVAS_SIZE as i32) {
+                // Overlapping chars of a cluster are not to erase each other.
+                let Luma([p]) = canvas.get_pixel_mut(x as u32, y as u32);
+                *p = (*p).max((255. * a) as u8);
+            }
+        };
+        match self {
+            Face::Fixed(font) => {
+                let v = font.v_metrics(FONT_SCALE);
+                let origin = point(GLYPH_OFFSET, GLYPH_OFFSET + v.ascent + v.line_gap);
+                for glyph in font.layout(text, FONT_SCALE, origin) {
+                    let bound = match glyph.pixel_bounding_box() {
+                        Some(bound) => bound,
+                        None => continue,
+                    };
+                    glyph.draw(|x, y, a| {
+                        put(
+                            x as i32 + bound.min.x + left,
+                            y as i32 + bound.min.y + top,
+                            a,
+                        )
+                    });
+                    blank = false;
+                }
+            }
+            Face::Variable(font) => {
+                let scale = PxScale {
+                    x: FONT_SCALE.x,
+                    y: FONT_SCALE.y,
+                };
+                let v = font.as_scaled(scale);
+                let mut origin =
+                    ab_glyph::point(GLYPH_OFFSET, GLYPH_OFFSET + v.ascent() + v.line_gap());
+                for c in text.chars() {
+                    let id = font.glyph_id(c);
+                    let glyph = id.with_scale_and_position(scale, origin);
+                    origin.x += v.h_advance(id);
+                    let outline = match font.outline_glyph(glyph) {
+                        Some(outline) => outline,
+                        None => continue,
+                    };
+                    let bound = outline.px_bounds();
+                    outline.draw(|x, y, a| {
+                        put(
+                            x as i32 + bound.min.x as i32 + left,
+                            y as i32 + bound.min.y as i32 + top,
+                            a,
+                        )
+                    });
+                    blank = false;
+                }
+            }
+        }
+        return (!blank).then(|| ());
+    }
+
+    /// Where `paint` puts the glyph by metrics of the font, without offsets: From the origin
+    /// to the advance, summed over chars of a cluster, and from the ascent to the descent;
+    /// `(left, top, width, height)` in px.
+    fn em_box(&self, c: Glyph) -> (u32, u32, u32, u32) {
+        let text = c.as_str();
+        let (advance, height, line_gap) = match self {
+            Face::Fixed(font) => {
+                let v = font.v_metrics(FONT_SCALE);
+                let advance = text
+                    .chars()
+                    .map(|c| font.glyph(c).scaled(FONT_SCALE).h_metrics().advance_width)
+                    .sum::<f32>();
+                (advance, v.ascent - v.descent, v.line_gap)
+            }
+            Face::Variable(font) => {
+                let v = font.as_scaled(PxScale {
+                    x: FONT_SCALE.x,
+                    y: FONT_SCALE.y,
+                });
+                let advance = text
+                    .chars()
+                    .map(|c| v.h_advance(font.glyph_id(c)))
+                    .sum::<f32>();
+                (advance, v.ascent() - v.descent(), v.line_gap())
+            }
+        };
+        let top = (GLYPH_OFFSET + line_gap).max(0.) as u32;
+        let clamp = |n: f32, at: u32| (n.round().max(1.) as u32).min(CANVAS_SIZE - at);
+        return (
+            GLYPH_OFFSET as u32,
+            top,
+            clamp(advance, GLYPH_OFFSET as u32),
+            clamp(height, top),
+        );
+    }
+}
+
+/// Bounds of the ink of a painted glyph, `(left, top, width, height)` in px.
+fn ink_bounds(canvas: &GrayImage) -> (u32, u32, u32, u32) {
+    let (mut sx, mut sy, mut ex, mut ey) = (CANVAS_SIZE, CANVAS_SIZE, 0, 0);
+    for (x, y, p) in canvas.enumerate_pixels() {
+        if *p != BLACK {
+            (sx, sy) = (sx.min(x), sy.min(y));
+            (ex, ey) = (ex.max(x), ey.max(y));
+        }
+    }
+    return match sx <= ex {
+        true => (sx, sy, ex - sx + 1, ey - sy + 1),
+        false => (0, 0, CANVAS_SIZE, CANVAS_SIZE),
+    };
+}
+
+/// The box by metrics, widened to take in the ink by up to half of its size on each axis.
+fn hybrid_area(
+    (bx, by, bw, bh): (u32, u32, u32, u32),
+    (ix, iy, iw, ih): (u32, u32, u32, u32),
+) -> (u32, u32, u32, u32) {
+    let span = |b: u32, l: u32, i: u32, j: u32| {
+        let grow = l / 4;
+        let s = i.max(b.saturating_sub(grow)).min(b);
+        let e = (i + j).min(b + l + grow).max(b + l).min(CANVAS_SIZE);
+        return (s, e - s);
+    };
+    let (x, w) = span(bx, bw, ix, iw);
+    let (y, h) = span(by, bh, iy, ih);
+    return (x, y, w, h);
+}
+
+/// The area of the canvas fitted in the proportion of the cell, centered, then resized to `8x8`;
+/// Half width ones are on the left `4x8`.
+fn fit_cell(
+    canvas: &GrayImage,
+    (x, y, lx, ly): (u32, u32, u32, u32),
+    w: bool,
+    cell: (u32, u32),
+) -> GrayImage {
+    let real = imageops::crop_imm(canvas, x, y, lx, ly);
+    let (cw, ch) = if w { (cell.0 * 2, cell.1) } else { cell };
+    let lm = lx.max(ly);
+    let (ax, ay) = match lm * ch / cw {
+        ay if ay >= ly => (lm, ay),
+        _ => ((ly * cw + ch - 1) / ch, ly),
+    };
+    let mut canvas = GrayImage::new(if w { ax } else { ax * 2 }, ay);
+    imageops::replace(&mut canvas, &real, (ax - lx) >> 1, (ay - ly) >> 1);
+    return imageops::resize(&canvas, 8, 8, Triangle);
+}
+
+/// `{tag}={value}`
+fn opt_axis(s: &str) -> Result<([u8; 4], f32), &'static str> {
+    let (tag, value) = s.split_once('=').ok_or("Invalid syntax")?;
+    let tag = <[u8; 4]>::try_from(tag.trim().as_bytes()).map_err(|_| "Invalid tag")?;
+    return Ok((tag, value.trim().parse().map_err(|_| "Invalid number")?));
+}
+
+/// The face of the index with axes set, faces available are listed if failed.
+fn open_face(data: Vec<u8>, index: u32, variation: &[([u8; 4], f32)]) -> Result<Face, String> {
+    if !variation.is_empty() {
+        let mut font = FontVec::try_from_vec_and_index(data, index).map_err(|e| e.to_string())?;
+        for (tag, value) in variation {
+            if !font.set_variation(tag, *value) {
+                Err(format!(
+                    "No axis \"{}\" in the font",
+                    String::from_utf8_lossy(tag)
+                ))?;
+            }
         }
+        return Ok(Face::Variable(font));
+    }
+    if Font::try_from_bytes_and_index(&data, index).is_some() {
+        let font = Font::try_from_vec_and_index(data, index);
+        return font.map(Face::Fixed).ok_or(format!("Not a font"));
+    }
+    let mut db = fontdb::Database::new();
+    db.load_font_data(data);
+    let faces = db
+        .faces()
+        .iter()
+        .map(|f| format!("{}: {} ({})", f.index, f.family, f.post_script_name))
+        .collect::<Vec<_>>();
+    return match faces.is_empty() {
+        true => Err(format!("Not a font")),
+        false => Err(format!(
+            "No face {}, available ones are: {}",
+            index,
+            faces.join("; ")
+        )),
+    };
+}
+
+/// The regular face of the family installed in the system; Similar names are suggested if not found.
+fn find_font(name: &str) -> Result<(Vec<u8>, u32), String> {
+    let mut db = fontdb::Database::new();
+    db.load_system_fonts();
+    let query = fontdb::Query {
+        families: &[fontdb::Family::Name(name)],
+        ..Default::default()
+    };
+    let id = match db.query(&query) {
+        Some(id) => id,
+        None => {
+            let lower = name.to_lowercase();
+            let mut similar = db
+                .faces()
+                .iter()
+                .map(|f| f.family.as_str())
+                .filter(|f| f.to_lowercase().contains(&lower))
+                .collect::<Vec<_>>();
+            similar.sort_unstable();
+            similar.dedup();
+            return match similar.is_empty() {
+                true => Err(format!("No font named \"{}\"", name)),
+                false => Err(format!(
+                    "No font named \"{}\", similar ones are: {}",
+                    name,
+                    similar.join(", ")
+                )),
+            };
+        }
+    };
+    return db
+        .with_face_data(id, |data, index| (data.to_vec(), index))
+        .ok_or(format!("Font \"{}\" is not accessible", name));
+}
+
+fn main_atlas(
+    ParamAtlas {
+        atlas_image,
+        map_file,
+        output_file,
+        invert,
+        cell,
+        bitmaps,
+        coeffs,
+        compress,
+    }: ParamAtlas,
+) {
+    let data = util::purify_err(
+        &format!(
+            "Failed to access image \"{}\"",
+            atlas_image.to_string_lossy()
+        ),
+        fs::read(&atlas_image),
+    );
+    let atlas = util::purify_err(
+        &format!("Failed to open image \"{}\"", atlas_image.to_string_lossy()),
+        image::load_from_memory(&data),
+    )
+    .to_luma_alpha8();
+    let atlas = GrayImage::from_fn(atlas.width(), atlas.height(), |x, y| {
+        let [l, a] = atlas.get_pixel(x, y).0;
+        let l = if invert { 255 - l } else { l };
+        return Luma([(l as u32 * a as u32 / 255) as u8]);
+    });
+    let map = util::purify_err(
+        &format!("Failed to read map \"{}\"", map_file.to_string_lossy()),
+        fs::read_to_string(&map_file),
+    );
+    let map = util::purify_err(
+        &format!("Invalid map \"{}\"", map_file.to_string_lossy()),
+        parse_map(&map),
+    );
+    let mut first = None;
+    let mut cs = Vec::<(Glyph, bool, [f32; 10], Orient)>::with_capacity(map.len());
+    let mut maps = AHashMap::new();
+    for (c, (width, height, left, top)) in map {
+        let c = Glyph::new(c);
         #[rustfmt::skip]
-        let w = match c.width() {
+        let w = match glyph_width(c, Ambiguous::Narrow) {
             Some(w) => w - 1 != 0, // false for half & true for full
             None => { print!("K"); continue } // Skipped
         };
-        let glyph = font
-            .layout(
-                &c.to_string(),
-                FONT_SCALE,
-                point(GLYPH_OFFSET, GLYPH_OFFSET + ascent),
+        if width == 0 || height == 0 {
+            println!();
+            panic!("Glyph '{}' is empty", c);
+        } else if left + width > atlas.width() || top + height > atlas.height() {
+            println!();
+            panic!("Glyph '{}' is out of the atlas", c);
+        }
+        let real = imageops::crop_imm(&atlas, left, top, width, height);
+        first.get_or_insert(if w {
+            (width / 2, height)
+        } else {
+            (width, height)
+        });
+        // Half width ones are on the left, as `gen` does.
+        let mut canvas = GrayImage::new(if w { width } else { width * 2 }, height);
+        imageops::replace(&mut canvas, &real, 0, 0);
+        let img = imageops::resize(&canvas, 8, 8, Triangle);
+        let (feat, orient) = features(&img, w);
+        cs.push((c, w, feat, orient));
+        if bitmaps {
+            maps.insert(c, bitmap(&img));
+        }
+        print!(".") // OK!
+    }
+    println!("\nTotally {} chars.", cs.len());
+    let cell = cell.or(first).unwrap_or(shoalart::art::DEFAULT_CELL);
+    let source = atlas_image.file_name().unwrap_or_default();
+    let source = source.to_string_lossy().into_owned();
+    let mut cs = Charset {
+        cell,
+        meta: Some(Meta::new(
+            GenMode::Atlas,
+            source,
+            util::crc32(&data),
+            cell.1 as f32,
+        )),
+        bitmaps: maps,
+        ..cs.into_iter().collect()
+    };
+    cs.truncate_coeffs(coeffs);
+    try_again!(
+        write_charset(&output_file, &cs, compress),
+        "Failed to write charset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+fn main_calibrate(
+    ParamCalibrate {
+        chars,
+        output_file,
+        display,
+        delay,
+        bitmaps,
+        coeffs,
+        compress,
+        dump,
+    }: ParamCalibrate,
+) {
+    let chars = expand_chars(&chars).unwrap_or(chars);
+    let mut glyphs = AHashSet::<_>::from_iter(cluster::split(&chars))
+        .into_iter()
+        .filter_map(|c| Some((c, glyph_width(c, Ambiguous::Narrow)? - 1 != 0))) // false for half & true for full
+        .collect::<Vec<_>>();
+    glyphs.sort_unstable();
+    let mut screen = util::purify_err("Failed to capture", live::Screen::open(display));
+    let (cols, rows) = util::purify_err("Failed to get terminal size", terminal::size());
+    println!("Calibrating, keep the terminal uncovered...");
+    thread::sleep(Duration::from_millis(delay));
+    let mut out = stdout();
+    queue!(
+        out,
+        EnterAlternateScreen,
+        HideCursor,
+        SetForegroundColor(Color::Rgb {
+            r: 255,
+            g: 255,
+            b: 255
+        }),
+        SetBackgroundColor(Color::Rgb { r: 0, g: 0, b: 0 }),
+    )
+    .ok();
+    // Only what the terminal draws is left after the blank capture is subtracted.
+    let mut capture = |draw: &mut dyn FnMut(&mut std::io::Stdout)| {
+        queue!(out, Clear(ClearType::All)).ok();
+        draw(&mut out);
+        out.flush().ok();
+        thread::sleep(Duration::from_millis(delay));
+        return screen.frame().map(|f| imageops::grayscale(&f));
+    };
+    let calibrated = || -> Result<_, String> {
+        let blank = capture(&mut |_| ())?;
+        let marks = capture(&mut |out| {
+            queue!(
+                out,
+                SetBackgroundColor(Color::Rgb {
+                    r: 255,
+                    g: 255,
+                    b: 255
+                })
             )
-            .next()
-            .unwrap();
-        #[rustfmt::skip]
-        let bound = match glyph.pixel_bounding_box() {
-            Some(b) => b,
-            None => { print!("K"); continue } // Skipped
+            .ok();
+            for n in [0, CALIBRATE_MARK] {
+                queue!(out, MoveTo(n, n), Print(' ')).ok();
+            }
+            queue!(out, SetBackgroundColor(Color::Rgb { r: 0, g: 0, b: 0 })).ok();
+        })?;
+        let diff = |img: &GrayImage| {
+            return GrayImage::from_fn(img.width(), img.height(), |x, y| {
+                Luma([img.get_pixel(x, y)[0].saturating_sub(blank.get_pixel(x, y)[0])])
+            });
         };
-        let mut canvas = GrayImage::new(CANVAS_SIZE, CANVAS_SIZE);
-        let mut paint = |left, top| {
-            glyph.draw(|x, y, a| {
-                let x = x as i32 + bound.min.x + left;
-                let y = y as i32 + bound.min.y + top;
-                if (x >= 0 && x < CANVAS_SIZE as i32) && (y >= 0 && y < CANVAS_SIZE as i32) {
-                    canvas.put_pixel(x as u32, y as u32, Luma([(255. * a) as u8]));
-                }
-            })
+        // The bounding box of both marks spans `CALIBRATE_MARK + 1` cells.
+        let drawn = diff(&marks)
+            .enumerate_pixels()
+            .filter(|(.., Luma([n]))| *n >= CALIBRATE_THRESHOLD)
+            .map(|(x, y, _)| (x, y))
+            .collect::<Vec<_>>();
+        let left = drawn.iter().map(|p| p.0).min();
+        let top = drawn.iter().map(|p| p.1).min();
+        let (left, top) = match left.zip(top) {
+            Some(p) => p,
+            None => Err(format!("The terminal is not found on the display"))?,
         };
-        #[rustfmt::skip]
-        let img = if compat_mode {
-            let (width, height, left, top) = compat_area;
-            paint(left, top);
-            let real = imageops::crop_imm(
-                &canvas,
-                GLYPH_OFFSET as u32,
-                GLYPH_OFFSET as u32,
-                width as u32,
-                height as u32,
-            ).to_image();
-            imageops::resize(&real, 8, 8, Triangle)
-        } else {
-            paint(0, 0);
-            let mut sx = 0;
-            let mut ex = CANVAS_SIZE - 1;
-            let mut sy = 0;
-            let mut ey = CANVAS_SIZE - 1;
-            let mut succ = false;
-            for x in  0..CANVAS_SIZE        { for y in 0..CANVAS_SIZE {
-                if *canvas.get_pixel(x, y) == BLACK { continue }
-                else { sx += x; succ = true; break }
-            } if succ { break } } succ = false;
-            for x in (0..CANVAS_SIZE).rev() { for y in 0..CANVAS_SIZE {
-                if *canvas.get_pixel(x, y) == BLACK { continue }
-                else { ex -= x; succ = true; break }
-            } if succ { break } } succ = false;
-            for y in  0..CANVAS_SIZE        { for x in 0..CANVAS_SIZE {
-                if *canvas.get_pixel(x, y) == BLACK { continue }
-                else { sy += y; succ = true; break }
-            } if succ { break } } succ = false;
-            for y in (0..CANVAS_SIZE).rev() { for x in 0..CANVAS_SIZE {
-                if *canvas.get_pixel(x, y) == BLACK { continue }
-                else { ey -= y; succ = true; break }
-            } if succ { break } }
-            let lx = CANVAS_SIZE - sx - ex;
-            let ly = CANVAS_SIZE - sy - ey;
-            let real = imageops::crop_imm(&canvas, sx, sy, lx, ly);
-            let mut lm = if lx > ly {
-                sy = (lx - ly) >> 1;
-                sx = 0;
-                lx
-            } else {
-                sx = (ly - lx) >> 1;
-                sy = 0;
-                ly
-            };
-            if !w {
-                lm <<= 1;
-                sy = (lm - ly) >> 1;
+        let right = drawn.iter().map(|p| p.0).max().unwrap() + 1;
+        let bottom = drawn.iter().map(|p| p.1).max().unwrap() + 1;
+        let span = (CALIBRATE_MARK + 1) as f32;
+        let cw = (right - left) as f32 / span;
+        let ch = (bottom - top) as f32 / span;
+        if cw < 1. || ch < 1. {
+            Err(format!("The terminal is not found on the display"))?;
+        }
+        // Two columns for each glyph, and the last row is left for no scrolling.
+        let per_row = (cols / 2).max(1) as usize;
+        let per_page = per_row * rows.saturating_sub(1).max(1) as usize;
+        let mut cs = Vec::with_capacity(glyphs.len());
+        for page in glyphs.chunks(per_page) {
+            let img = diff(&capture(&mut |out| {
+                for (i, (c, _)) in page.iter().enumerate() {
+                    let (x, y) = ((i % per_row * 2) as u16, (i / per_row) as u16);
+                    queue!(out, MoveTo(x, y), Print(*c)).ok();
+                }
+            })?);
+            for (i, &(c, w)) in page.iter().enumerate() {
+                let (x, y) = ((i % per_row * 2) as f32, (i / per_row) as f32);
+                let (x, y) = (left + (x * cw) as u32, top + (y * ch) as u32);
+                let width = (if w { cw * 2. } else { cw }) as u32;
+                if x + width > img.width() || y + ch as u32 > img.height() {
+                    Err(format!("The terminal is partly out of the display"))?;
+                }
+                let real = imageops::crop_imm(&img, x, y, width, ch as u32);
+                // Half width ones are on the left, as `gen` does.
+                let mut canvas = GrayImage::new(if w { width } else { width * 2 }, ch as u32);
+                imageops::replace(&mut canvas, &real, 0, 0);
+                cs.push((c, w, canvas));
             }
-            let mut canvas = GrayImage::new(lm, lm);
-            imageops::replace(&mut canvas, &real, sx, sy);
-            imageops::resize(&canvas, 8, 8, Triangle)
-        };
-        unsafe {
-            img.pixels().enumerate().for_each(|(i, Luma([n]))| {
-                *block.as_mut_ptr().cast::<f32>().add(i) = *n as f32 / 128. - 1.
-            });
         }
-        let feat = if !w {
-            algorithm::dct_4x8_feature(&block)
-        } else {
-            algorithm::dct_8x8_feature(&block)
-        };
-        cs.push((c, w, feat));
+        return Ok(((cw.round() as u32, ch.round() as u32), cs));
+    }();
+    queue!(out, ResetColor, LeaveAlternateScreen, ShowCursor).ok();
+    out.flush().ok();
+    let (cell, cs) = util::purify_err("Failed to calibrate", calibrated);
+    println!("Cells are {}x{}.", cell.0, cell.1);
+    let dump = util::whether_dump(dump, "ShoalartDump-Calibrate");
+    let mut maps = AHashMap::new();
+    let mut glyphs = Vec::with_capacity(cs.len());
+    for (c, w, canvas) in cs {
+        if canvas.pixels().all(|p| *p == BLACK) {
+            print!("K"); // Skipped
+            continue;
+        }
         if let Some(p) = &dump {
-            canvas
-                .save(p.join(format!("_U{:04X}.png", u32::from(c))))
-                .ok();
-            if !w {
-                imageops::resize(&imageops::crop_imm(&img, 0, 0, 4, 8), 24, 48, Nearest)
-            } else {
-                imageops::resize(&img, 48, 48, Nearest)
-            }
-            .save(p.join(format!("U{:04X}.png", u32::from(c))))
-            .ok();
+            canvas.save(p.join(format!("{}.png", dump_name(c)))).ok();
         }
-        print!(".") // OK!
+        let img = imageops::resize(&canvas, 8, 8, Triangle);
+        let (feat, orient) = features(&img, w);
+        glyphs.push((c, w, feat, orient));
+        if bitmaps {
+            maps.insert(c, bitmap(&img));
+        }
+        print!("."); // OK!
     }
-    println!("\nTotally {} chars.", cs.len() + 1);
+    println!("\nTotally {} chars.", glyphs.len());
+    let source = std::env::var("TERM_PROGRAM")
+        .or_else(|_| std::env::var("TERM"))
+        .unwrap_or(format!("terminal"));
+    let mut cs = Charset {
+        cell,
+        meta: Some(Meta::new(GenMode::Calibrated, source, 0, cell.1 as f32)),
+        bitmaps: maps,
+        ..glyphs.into_iter().collect()
+    };
+    cs.truncate_coeffs(coeffs);
     try_again!(
-        write_charset(&output_file, cs.iter().map(|(c, w, f)| (c, w, f))),
+        write_charset(&output_file, &cs, compress),
         "Failed to write charset \"{}\": {:?}",
         output_file.to_string_lossy(),
     );
 }
 
+/// Name of dumped images, e.g. `U0041`, or `U0065_0301` for clusters.
+fn dump_name(c: Glyph) -> String {
+    let codes = c.as_str().chars().map(|c| format!("{:04X}", c as u32));
+    return format!("U{}", codes.collect::<Vec<_>>().join("_"));
+}
+
+/// Lines of `{char} {width}x{height}+{left}+{top}`, empty ones are skipped.
+fn parse_map(map: &str) -> Result<Vec<(char, (u32, u32, u32, u32))>, String> {
+    let mut glyphs = Vec::new();
+    for (i, line) in map.lines().enumerate() {
+        let line = line.trim_end();
+        let mut chars = line.chars();
+        let c = match chars.next() {
+            None => continue,
+            Some('U') if line.starts_with("U+") && line.len() > 2 => {
+                let (hex, _) = line[2..].split_once(' ').unwrap_or((&line[2..], ""));
+                let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+                chars = line[2 + hex.len()..].chars();
+                c.ok_or(format!("Line {}: Invalid char \"U+{}\"", i + 1, hex))?
+            }
+            Some(c) => c,
+        };
+        let rect = opt_crop(chars.as_str().trim()).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        glyphs.push((c, rect));
+    }
+    return Ok(glyphs);
+}
+
+/// Pixels of an `8x8` glyph, row by row.
+fn bitmap(img: &GrayImage) -> [u8; 64] {
+    return img.as_raw()[..64].try_into().unwrap();
+}
+
+/// Features of an `8x8` glyph, half width ones are on the left `4x8`.
+fn features(img: &GrayImage, w: bool) -> ([f32; 10], Orient) {
+    let mut block = [[0f32; 8]; 8];
+    unsafe {
+        img.pixels().enumerate().for_each(|(i, Luma([n]))| {
+            *block.as_mut_ptr().cast::<f32>().add(i) = *n as f32 / 128. - 1.
+        });
+    }
+    return if !w {
+        (
+            algorithm::dct_4x8_feature(&block),
+            algorithm::orient_4x8_feature(&block),
+        )
+    } else {
+        (
+            algorithm::dct_8x8_feature(&block),
+            algorithm::orient_8x8_feature(&block),
+        )
+    };
+}
+
 fn main_merge(
     ParamMerge {
         output_file,
         charset_files,
+        on_conflict,
+        compress,
     }: ParamMerge,
 ) {
-    let mut cs = AHashMap::<char, (bool, [f32; 10])>::with_capacity(2048);
+    // Features are summed up for `average`, with the count.
+    let mut cs = AHashMap::<Glyph, (bool, [f32; 10], Orient, u32)>::with_capacity(2048);
+    let mut conflicts = 0;
+    let mut cell = None;
+    let mut metas = Vec::<Meta>::new();
+    let mut maps = AHashMap::<Glyph, [u8; 64]>::new();
+    let mut coeffs = MAX_COEFFS;
+    // Glyphs from fixed areas are not comparable with those fitted in cells.
+    let compat = |c: &Charset| c.meta.as_ref().map(|m| m.mode == GenMode::Compat);
     for p in charset_files {
         print!("File \"{}\": ", p.to_string_lossy());
         match read_charset(&p) {
-            Ok(c) => cs.extend(c),
+            Ok(c) if cell.map_or(false, |cell| cell != c.cell) => {
+                println!(
+                    "Skipped: Cells are {:?} instead of {:?}",
+                    c.cell,
+                    cell.unwrap()
+                );
+                continue;
+            }
+            Ok(c)
+                if metas.first().map_or(false, |m| {
+                    compat(&c).map_or(false, |compat| compat != (m.mode == GenMode::Compat))
+                }) =>
+            {
+                println!(
+                    "Skipped: Glyphs are {} instead of {}",
+                    c.meta.unwrap().mode.name(),
+                    metas[0].mode.name()
+                );
+                continue;
+            }
+            Ok(c) => {
+                cell = Some(c.cell);
+                if c.coeffs != coeffs {
+                    print!("Features of {} coefficients; ", c.coeffs);
+                    coeffs = coeffs.min(c.coeffs);
+                }
+                // The blank is in every charset.
+                for (c, w, f, o) in c.iter().filter(|(c, ..)| *c != ' ') {
+                    let old = match cs.get_mut(&c) {
+                        Some(old) => old,
+                        None => {
+                            cs.insert(c, (w, *f, *o, 1));
+                            continue;
+                        }
+                    };
+                    conflicts += 1;
+                    match on_conflict {
+                        Conflict::First => (),
+                        Conflict::Last => *old = (w, *f, *o, 1),
+                        Conflict::Average if old.0 != w => {
+                            print!("'{}' of another width is not averaged; ", c)
+                        }
+                        Conflict::Average => {
+                            old.1.iter_mut().zip(f).for_each(|(a, b)| *a += b);
+                            old.2.iter_mut().zip(o).for_each(|(a, b)| *a += b);
+                            old.3 += 1;
+                        }
+                        Conflict::Error => {
+                            println!();
+                            panic!("'{}' is also in a former charset", c);
+                        }
+                    }
+                }
+                // Bitmaps are never averaged, the first one is kept instead.
+                for (c, bitmap) in c.bitmaps {
+                    if on_conflict == Conflict::Last || !maps.contains_key(&c) {
+                        maps.insert(c, bitmap);
+                    }
+                }
+                if let Some(meta) = c.meta {
+                    if metas.first().map_or(false, |m| m.scale != meta.scale) {
+                        print!(
+                            "Rasterized at {}px instead of {}px; ",
+                            meta.scale, metas[0].scale
+                        );
+                    }
+                    metas.push(meta);
+                }
+            }
             Err(e) => {
                 println!("{}", e);
                 continue;
@@ -310,26 +1262,411 @@ fn main_merge(
     if cs.is_empty() {
         panic!("No inputs")
     }
-    println!("Totally {} chars.", cs.len());
+    let meta = metas.first().map(|first| {
+        let mut sources = metas.iter().map(|m| m.source.as_str()).collect::<Vec<_>>();
+        sources.dedup();
+        let scale = match metas.iter().all(|m| m.scale == first.scale) {
+            true => first.scale,
+            false => 0.,
+        };
+        return Meta::new(GenMode::Merged, sources.join(", "), 0, scale);
+    });
+    let cs = cs.into_iter().map(|(c, (w, mut f, mut o, n))| {
+        f.iter_mut().chain(&mut o).for_each(|v| *v /= n as f32);
+        return (c, w, f, o);
+    });
+    let mut cs = Charset {
+        cell: cell.unwrap_or(shoalart::art::DEFAULT_CELL),
+        meta,
+        bitmaps: maps,
+        ..cs.collect()
+    };
+    // Features are compared by the fewest coefficients.
+    cs.truncate_coeffs(coeffs);
+    println!(
+        "Totally {} chars, {} in several charsets.",
+        cs.len(),
+        conflicts
+    );
     try_again!(
-        write_charset(&output_file, cs.iter().map(|(c, (w, f))| (c, w, f))),
+        write_charset(&output_file, &cs, compress),
         "Failed to write charset \"{}\": {:?}",
         output_file.to_string_lossy(),
     );
 }
 
+/// Subtract, or intersect if `keep`; Cells and metadata are of the former.
+fn main_filter(
+    charset_file: PathBuf,
+    other_file: PathBuf,
+    output_file: PathBuf,
+    compress: util::Codec,
+    keep: bool,
+) {
+    let mut cs = read_charset(&charset_file).unwrap();
+    let other = read_charset(&other_file).unwrap();
+    let chars = other.iter().map(|(c, ..)| c).collect::<AHashSet<_>>();
+    let len = cs.len();
+    cs.retain(|c| chars.contains(&c) == keep);
+    println!("Totally {} chars, {} dropped.", cs.len(), len - cs.len());
+    try_again!(
+        write_charset(&output_file, &cs, compress),
+        "Failed to write charset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+fn main_dedupe(
+    ParamDedupe {
+        charset_file,
+        output_file,
+        threshold,
+        verbose,
+        compress,
+    }: ParamDedupe,
+) {
+    let mut cs = read_charset(&charset_file).unwrap();
+    let dropped = cs.dedupe(threshold);
+    if verbose {
+        dropped
+            .iter()
+            .for_each(|(c, k)| println!("'{}' -> '{}'", c, k));
+    }
+    println!("Totally {} chars, {} dropped.", cs.len(), dropped.len());
+    try_again!(
+        write_charset(&output_file, &cs, compress),
+        "Failed to write charset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+fn main_analyze(
+    ParamAnalyze {
+        charset_file,
+        threshold,
+        top,
+        bins,
+        from,
+    }: ParamAnalyze,
+) {
+    let cs = read_charset(&charset_file).unwrap();
+    let from = match from {
+        Some(p) => read_charset(&p).unwrap(),
+        None => Charset::builtin(),
+    };
+    let bins = bins.clamp(1, 256);
+    println!(
+        "{} half and {} full width glyphs.",
+        cs.half.len(),
+        cs.full.len()
+    );
+    let (pairs, close) = cs.closest_pairs(top, threshold);
+    println!("\nClosest pairs:");
+    for (c, k, d) in &pairs {
+        println!("  '{}' ~ '{}'  {:.4}", c, k, d);
+    }
+    println!(
+        "{} pairs within {}, nearly indistinguishable; See `charset dedupe`.",
+        close, threshold
+    );
+    for (name, wide, glyphs, candidates) in [
+        ("Half", false, &cs.half, &from.half),
+        ("Full", true, &cs.full, &from.full),
+    ] {
+        if glyphs.is_empty() {
+            continue;
+        }
+        let bin = |f: &[f32; 10]| brightness(f, wide) as usize * bins / 256;
+        let mut hist = vec![Vec::new(); bins];
+        glyphs.iter().for_each(|(c, f, _)| hist[bin(f)].push(*c));
+        println!("\n{} width glyphs by brightness:", name);
+        for (i, chars) in hist.iter().enumerate() {
+            println!(
+                "  {:>3}..{:<3} {:>5}  {}",
+                i * 256 / bins,
+                (i + 1) * 256 / bins,
+                chars.len(),
+                chars
+                    .iter()
+                    .take(16)
+                    .map(|c| c.to_string())
+                    .collect::<String>(),
+            );
+        }
+        // Candidates not in the charset, by how far they are from the closest glyph.
+        let mut suggested = candidates
+            .iter()
+            .filter(|(c, ..)| !glyphs.iter().any(|g| g.0 == *c))
+            .map(|(c, f, _)| {
+                let d = glyphs
+                    .iter()
+                    .map(|(_, gf, _)| algorithm::similarity(f, gf))
+                    .fold(f32::INFINITY, f32::min);
+                (*c, bin(f), d)
+            })
+            .filter(|(.., d)| *d > threshold)
+            .collect::<Vec<_>>();
+        // Those filling empty ranges first.
+        suggested.sort_unstable_by(|a, b| {
+            let gap = |s: &(Glyph, usize, f32)| hist[s.1].is_empty();
+            return gap(b).cmp(&gap(a)).then(b.2.total_cmp(&a.2));
+        });
+        let gaps = hist.iter().filter(|h| h.is_empty()).count();
+        match suggested.is_empty() {
+            true => println!("{} empty ranges, nothing to suggest.", gaps),
+            false => println!(
+                "{} empty ranges, suggested to add: {}",
+                gaps,
+                suggested
+                    .iter()
+                    .take(top)
+                    .map(|(c, ..)| format!("'{}'", c))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+    }
+}
+
+fn main_sheet(
+    ParamSheet {
+        charset_file,
+        output_file,
+        font,
+        cell,
+        columns,
+    }: ParamSheet,
+) {
+    let cs = read_charset(&charset_file).unwrap();
+    let font = util::purify_opt(
+        &format!("Failed to open font \"{}\"", font.to_string_lossy()),
+        Font::try_from_vec(util::purify_err(
+            &format!("Failed to access font \"{}\"", font.to_string_lossy()),
+            fs::read(&font),
+        )),
+    );
+    let columns = columns.max(1);
+    let glyphs = cs.iter().collect::<Vec<_>>();
+    // Each row of glyphs is the glyph with the reconstructed block, two lines of labels, and a gap.
+    let mut lines = Vec::new();
+    for row in glyphs.chunks(columns) {
+        let mut entries = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+        for (c, w, f, _) in row {
+            let label = |color, text: String| {
+                let text = format!("{:<1$}", text, SHEET_ENTRY);
+                return text
+                    .chars()
+                    .map(|glyph| Cell {
+                        color,
+                        bg: None,
+                        glyph: glyph.into(),
+                        attrs: 0,
+                    })
+                    .collect::<Vec<_>>();
+            };
+            entries[0].push(Cell {
+                color: SHEET_GLYPH,
+                bg: Some(SHEET_GLYPH_BG),
+                glyph: *c,
+                attrs: 0,
+            });
+            entries[0].extend(
+                label(SHEET_LABEL, String::new())
+                    .into_iter()
+                    .skip(1 + *w as usize),
+            );
+            let code = format!("U+{:04X} {}", c.first() as u32, if *w { 'F' } else { 'H' });
+            entries[1].extend(label(SHEET_LABEL, code));
+            let feat = format!("{:.1} {:.1} {:.1}", f[0], f[1], f[2]);
+            entries[2].extend(label(SHEET_LABEL, feat));
+        }
+        lines.extend(entries);
+    }
+    let frame = Frame { lines };
+    let mut img = export::render(&frame, &font, cell, Ambiguous::Narrow);
+    // Blocks reconstructed are put right after glyphs, in cells of their own width.
+    let (cw, ch) = cell;
+    for (i, (_, w, f, _)) in glyphs.iter().enumerate() {
+        let block = algorithm::reconstruct(f, *w);
+        let (bw, pw) = if *w { (8, cw * 2) } else { (4, cw) };
+        let x0 = ((i % columns) * SHEET_ENTRY + 3) as u32 * cw;
+        let y0 = (i / columns * 4) as u32 * ch;
+        for py in 0..ch {
+            for px in 0..pw {
+                let v = block[(py * 8 / ch) as usize][(px * bw / pw) as usize];
+                let v = ((v + 1.) * 127.5).clamp(0., 255.) as u8;
+                img.put_pixel(x0 + px, y0 + py, Rgb([v; 3]));
+            }
+        }
+    }
+    println!(
+        "Rendered {} glyphs, {}x{}.",
+        glyphs.len(),
+        img.width(),
+        img.height()
+    );
+    try_again!(
+        img.save(&output_file),
+        "Failed to write sheet \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+/// Blocks reconstructed from features, labelled and in rows as many as fit.
+fn print_blocks(glyphs: &[(Glyph, bool, &[f32; 10], &Orient)]) {
+    // Two chars a pixel for squares, and a space between blocks.
+    let width = |w: bool| if w { 8 * 2 + 1 } else { 4 * 2 + 1 };
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    for g in glyphs {
+        if used > 0 && used + width(g.1) > RENDER_WIDTH {
+            rows.push(Vec::new());
+            used = 0;
+        }
+        used += width(g.1);
+        rows.last_mut().unwrap().push(g);
+    }
+    for row in rows {
+        for (c, w, ..) in &row {
+            // Clusters are labeled with their first char.
+            let text = c.as_str();
+            let label = format!("{} {:04X}", text, c.first() as u32);
+            let len = glyph_width(*c, Ambiguous::Narrow).unwrap_or(1) + label.len() - text.len();
+            print!("{}{}", label, " ".repeat(width(*w).saturating_sub(len)));
+        }
+        println!();
+        for y in 0..8 {
+            for (_, w, f, _) in &row {
+                let block = algorithm::reconstruct(f, *w);
+                for v in &block[y][..if *w { 8 } else { 4 }] {
+                    let i = (v + 1.) / 2. * SHADES.len() as f32;
+                    let shade = SHADES[(i.max(0.) as usize).min(SHADES.len() - 1)];
+                    print!("{}{}", shade, shade);
+                }
+                print!(" ");
+            }
+            println!();
+        }
+    }
+}
+
 #[rustfmt::skip]
-fn main_read(ParamRead { charset_file }: ParamRead) {
-    let mut cs = read_charset(&charset_file)
-        .unwrap()
-        .into_iter()
-        .collect::<Vec<_>>();
+fn main_read(ParamRead { charset_file, verify, render }: ParamRead) {
+    let cs = read_charset(&charset_file).unwrap();
+    println!("Cells are {}x{}.", cs.cell.0, cs.cell.1);
+    match &cs.meta {
+        Some(meta) => println!("Generated {}.", meta.describe()),
+        None => println!("Generated from unknown sources."),
+    }
+    if !cs.bitmaps.is_empty() {
+        println!("With bitmaps of {} glyphs.", cs.bitmaps.len());
+    }
+    if cs.coeffs < MAX_COEFFS {
+        println!("Features of {} coefficients.", cs.coeffs);
+    }
+    if let Some(p) = &verify {
+        let hash = util::crc32(&util::purify_err(
+            &format!("Failed to access \"{}\"", p.to_string_lossy()),
+            fs::read(p),
+        ));
+        match cs.meta.as_ref().map(|m| m.hash).filter(|h| *h != 0) {
+            Some(h) if h == hash => println!("Verified: \"{}\" is the source.", p.to_string_lossy()),
+            Some(h) => println!("Mismatched: CRC-32 of \"{}\" is {:08x} instead of {:08x}.", p.to_string_lossy(), hash, h),
+            None => println!("Unverifiable: No CRC-32 of the source."),
+        }
+    }
+    let mut cs = cs.iter().collect::<Vec<_>>();
     cs.sort_unstable_by_key(|v| v.0);
-    cs.iter().for_each(|(c, (w, f))| println!(
-        "{} / ('{}', [{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06}]),",
+    if render {
+        print_blocks(&cs);
+        println!("Totally {} chars.", cs.len());
+        return;
+    }
+    cs.iter().for_each(|(c, w, f, o)| println!(
+        "{} / ('{}', [{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06},{:>10.06}]) / [{:>10.06},{:>10.06},{:>10.06},{:>10.06}],",
         *w as u8, c,
         f[0], f[1], f[2], f[3], f[4],
         f[5], f[6], f[7], f[8], f[9],
+        o[0], o[1], o[2], o[3],
     ));
     println!("Totally {} chars.", cs.len());
 }
+
+fn main_export(
+    ParamExport {
+        charset_file,
+        output_file,
+        format,
+    }: ParamExport,
+) {
+    let cs = read_charset(&charset_file).unwrap();
+    let exported = match output_file.to_str() {
+        Some("-") => interchange::export_charset(stdout().lock(), &cs, format),
+        _ => match fs::File::create(&output_file) {
+            Ok(f) => interchange::export_charset(BufWriter::new(f), &cs, format),
+            Err(e) => Err(format!("Failed to create output: {:?}", e)),
+        },
+    };
+    exported.unwrap();
+}
+
+fn main_import(
+    ParamImport {
+        input_file,
+        output_file,
+        format,
+        compress,
+    }: ParamImport,
+) {
+    let cs = match input_file.to_str() {
+        Some("-") => interchange::import_charset(stdin().lock(), format),
+        _ => match fs::File::open(&input_file) {
+            Ok(f) => interchange::import_charset(BufReader::new(f), format),
+            Err(e) => Err(format!("Failed to open input: {:?}", e)),
+        },
+    };
+    let cs = cs.unwrap();
+    println!(
+        "Totally {} chars, cells are {}x{}.",
+        cs.len(),
+        cs.cell.0,
+        cs.cell.1
+    );
+    try_again!(
+        write_charset(&output_file, &cs, compress),
+        "Failed to write charset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+fn main_emit_rust(ParamEmitRust { charset_file, name }: ParamEmitRust) {
+    let cs = read_charset(&charset_file).unwrap();
+    // Orientations are derived from features, clusters cannot be `char`s.
+    let mut glyphs = cs
+        .half
+        .iter()
+        .filter(|(c, ..)| !c.is_cluster())
+        .collect::<Vec<_>>();
+    glyphs.sort_unstable_by_key(|v| v.0);
+    let skipped = cs.len() - glyphs.len();
+    if skipped > 0 {
+        eprintln!("Left out {} full width glyphs and clusters.", skipped);
+    }
+    let mut out = stdout().lock();
+    let emitted = || -> io::Result<()> {
+        writeln!(out, "#[rustfmt::skip]")?;
+        writeln!(
+            out,
+            "const {}: [(char, [f32; 10]); {}] = [",
+            name,
+            glyphs.len()
+        )?;
+        for (c, f, _) in &glyphs {
+            let f = f.iter().map(|v| format!("{:>10.6}", v));
+            writeln!(out, "    ({:?}, [{}]),", c, f.collect::<Vec<_>>().join(","))?;
+        }
+        writeln!(out, "];")?;
+        return out.flush();
+    }();
+    emitted.unwrap();
+}
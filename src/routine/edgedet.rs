@@ -1,12 +1,31 @@
 use crate::*;
 use edge_detection::canny;
-use image::{imageops::Lanczos3, DynamicImage, Luma};
+use image::{imageops::Lanczos3, DynamicImage, GrayImage, Luma};
+use shoalart::preview;
 use std::{
     io::{stdout, Write},
+    str::FromStr,
     time::Instant,
 };
 
-/// Use Canny detect edges for images
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_otsu() {
+        // Weak responses around 0.1, and fewer strong ones around 0.8, with zeros ignored.
+        let mut values = vec![0f32; 500];
+        values.extend((0..200).map(|i| 0.08 + (i % 5) as f32 * 0.01));
+        values.extend((0..50).map(|i| 0.78 + (i % 5) as f32 * 0.01));
+        let t = otsu(&values).unwrap();
+        assert!(t > 0.12 && t < 0.78, "{}", t);
+        assert_eq!(otsu(&[0., 0., -1.]), None);
+        assert_eq!(otsu(&[]), None);
+    }
+}
+
+/// Use Canny or others detect edges for images
 ///
 /// Parallel acceleration is enabled by default!
 #[derive(StructOpt, Debug)]
@@ -16,15 +35,55 @@ pub struct Param {
     #[structopt(parse(from_os_str))]
     output_dir_or_file: PathBuf,
 
-    /// Set the sigma
+    /// Algorithm to detect edges: `canny` and `sobel` by gradients, `dog` and `xdog` for
+    /// line art by differences of Gaussians
+    #[structopt(long, default_value = "canny", possible_values = Algorithm::NAMES)]
+    algorithm: Algorithm,
+    /// Set the sigma; Of the narrower Gaussian for `dog` and `xdog`
     #[structopt(short = "s", long, default_value = "2.35")]
     sigma: f32,
-    /// Set the strong threshold
+    /// Set the strong threshold; The only one for `sobel`, relative to the largest gradient
     #[structopt(short = "S", long = "strong", default_value = "0.18")]
     thr_strong: f32,
     /// Set the weak threshold
     #[structopt(short = "w", long = "weak", default_value = "0.08")]
     thr_weak: f32,
+    /// Derive thresholds of each image from magnitudes of gradients along edges by Otsu's method,
+    /// the weak one being 0.4 of the strong one; `strong` and `weak` are kept for images
+    /// without edges; Not for `dog` and `xdog`
+    #[structopt(short = "a", long)]
+    auto_threshold: bool,
+
+    /// Ratio of sigmas of the wider Gaussian to the narrower one, for `dog` and `xdog`
+    #[structopt(long, default_value = "1.6")]
+    ratio: f32,
+    /// Weight of the wider Gaussian, for `xdog`
+    #[structopt(long, default_value = "0.98")]
+    tau: f32,
+    /// Lines are where differences of Gaussians fall below, within `-1..=1`; For `dog` and `xdog`
+    #[structopt(long, default_value = "-0.01", allow_hyphen_values = true)]
+    epsilon: f32,
+    /// Sharpness of lines fading in, for `xdog`
+    #[structopt(long, default_value = "200")]
+    phi: f32,
+
+    /// Thin edges into strokes of one pixel, before dilation; Those at least half white are
+    /// taken as edges
+    #[structopt(long)]
+    thin: bool,
+    /// Dilate edges by N pixels on each side, strokes are `2 * N + 1` px wide if thinned
+    #[structopt(long, default_value = "0")]
+    dilate: u32,
+
+    /// Add to luma before detection, within `-1..=1`
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    brightness: f32,
+    /// Scale luma around the middle gray before detection
+    #[structopt(long, default_value = "1")]
+    contrast: f32,
+    /// Gamma of luma before detection, larger is brighter
+    #[structopt(long, default_value = "1")]
+    gamma: f32,
 
     /// Crop images before resize; No cropping by default
     ///
@@ -50,27 +109,316 @@ pub struct Param {
     #[structopt(long = "ctr", default_value = "1")]
     i_ctr: u32,
 
+    /// Show sources and edges inline on your terminal, for tuning parameters;
+    /// By the protocol guessed from environment variables if not specified
+    #[structopt(long, possible_values = preview::Protocol::NAMES)]
+    preview: Option<Option<preview::Protocol>>,
+
     /// Verbose mode (-v, -vv, -vvv, etc.)
     #[structopt(short, long, parse(from_occurrences))]
     verbose: u8,
 }
 
+/// Algorithms to detect edges.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Thin edges by hysteresis thresholds of gradients
+    Canny,
+    /// Magnitudes of gradients reaching the strong threshold
+    Sobel,
+    /// Difference of Gaussians, thresholded hard
+    Dog,
+    /// Extended difference of Gaussians, with lines fading in like strokes
+    Xdog,
+}
+
+impl Algorithm {
+    pub const NAMES: &'static [&'static str] = &["canny", "sobel", "dog", "xdog"];
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "canny" => Ok(Algorithm::Canny),
+            "sobel" => Ok(Algorithm::Sobel),
+            "dog" => Ok(Algorithm::Dog),
+            "xdog" => Ok(Algorithm::Xdog),
+            _ => Err(format!("Unknown algorithm \"{}\"", s)),
+        };
+    }
+}
+
+/// Canny, then edges are white and others black.
+pub fn detect(img: GrayImage, sigma: f32, thr_strong: f32, thr_weak: f32) -> GrayImage {
+    let mut img = canny(img, sigma, thr_strong, thr_weak)
+        .as_image()
+        .to_luma8();
+    img.pixels_mut().for_each(|Luma([n])| {
+        if *n != 0 {
+            *n = 255;
+        }
+    });
+    return img;
+}
+
+/// Luma within `0..=1` blurred by a Gaussian, row by row.
+fn gaussian(img: &GrayImage, sigma: f32) -> Vec<f32> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let src = img.iter().map(|n| *n as f32 / 255.).collect::<Vec<_>>();
+    if sigma <= 0. {
+        return src;
+    }
+    let r = (sigma * 3.).ceil() as isize;
+    let kernel = (-r..=r)
+        .map(|i| (-(i * i) as f32 / (2. * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total = kernel.iter().sum::<f32>();
+    // Borders are extended.
+    let at = |i: usize, d: isize, n: usize| (i as isize + d).clamp(0, n as isize - 1) as usize;
+    let mut tmp = vec![0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            tmp[y * w + x] = kernel
+                .iter()
+                .zip(-r..)
+                .map(|(k, d)| k * src[y * w + at(x, d, w)])
+                .sum::<f32>()
+                / total;
+        }
+    }
+    let mut out = vec![0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            out[y * w + x] = kernel
+                .iter()
+                .zip(-r..)
+                .map(|(k, d)| k * tmp[at(y, d, h) * w + x])
+                .sum::<f32>()
+                / total;
+        }
+    }
+    return out;
+}
+
+/// Magnitudes of Sobel gradients of the image blurred by `sigma`, relative to the largest.
+pub fn gradients(img: &GrayImage, sigma: f32) -> Vec<f32> {
+    let (w, h) = (img.width() as isize, img.height() as isize);
+    let g = gaussian(img, sigma);
+    let at = |x: isize, y: isize| g[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize];
+    let mut mags = Vec::with_capacity(g.len());
+    for y in 0..h {
+        for x in 0..w {
+            let gx = at(x + 1, y - 1) + 2. * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2. * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2. * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2. * at(x, y - 1)
+                - at(x + 1, y - 1);
+            mags.push(gx.hypot(gy));
+        }
+    }
+    let max = mags.iter().copied().fold(0f32, f32::max);
+    if max > 0. {
+        mags.iter_mut().for_each(|m| *m /= max);
+    }
+    return mags;
+}
+
+/// Sobel, edges are white where gradients reach the threshold.
+pub fn sobel(img: &GrayImage, mags: &[f32], thr: f32) -> GrayImage {
+    let bytes = mags.iter().map(|m| (*m >= thr) as u8 * 255).collect();
+    return GrayImage::from_raw(img.width(), img.height(), bytes).unwrap();
+}
+
+/// Differences of Gaussians of `sigma` and `sigma * ratio`, lines are white where
+/// `narrower - tau * wider` falls below `epsilon`: Hard, or fading in by `tanh` of `phi` times.
+pub fn dog(
+    img: &GrayImage,
+    sigma: f32,
+    ratio: f32,
+    tau: f32,
+    epsilon: f32,
+    phi: Option<f32>,
+) -> GrayImage {
+    let (narrower, wider) = (gaussian(img, sigma), gaussian(img, sigma * ratio));
+    let mut out = GrayImage::new(img.width(), img.height());
+    out.pixels_mut()
+        .zip(narrower.iter().zip(&wider))
+        .for_each(|(Luma([n]), (a, b))| {
+            let d = a - tau * b - epsilon;
+            let line = match phi {
+                _ if d >= 0. => 0.,
+                Some(phi) => -(phi * d).tanh(),
+                None => 1.,
+            };
+            *n = (line * 255.).round() as u8;
+        });
+    return out;
+}
+
+/// Thinned into strokes of one pixel by Zhang-Suen, edges being at least half white.
+pub fn thin(img: &mut GrayImage) {
+    let (w, h) = (img.width() as isize, img.height() as isize);
+    let mut on = img.iter().map(|n| *n >= 128).collect::<Vec<_>>();
+    let at = |on: &[bool], x: isize, y: isize| {
+        return (0..w).contains(&x) && (0..h).contains(&y) && on[(y * w + x) as usize];
+    };
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let mut off = Vec::new();
+            for y in 0..h {
+                for x in 0..w {
+                    if !on[(y * w + x) as usize] {
+                        continue;
+                    }
+                    // Neighbors clockwise from the north.
+                    #[rustfmt::skip]
+                    let p = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)]
+                        .map(|(dx, dy)| at(&on, x + dx, y + dy));
+                    let neighbors = p.iter().filter(|v| **v).count();
+                    let transitions = (0..8).filter(|i| !p[*i] && p[(i + 1) % 8]).count();
+                    let (north, east, south, west) = (p[0], p[2], p[4], p[6]);
+                    let outer = match step {
+                        0 => !(north && east && south) && !(east && south && west),
+                        _ => !(north && east && west) && !(north && south && west),
+                    };
+                    if (2..=6).contains(&neighbors) && transitions == 1 && outer {
+                        off.push((y * w + x) as usize);
+                    }
+                }
+            }
+            changed |= !off.is_empty();
+            off.into_iter().for_each(|i| on[i] = false);
+        }
+        if !changed {
+            break;
+        }
+    }
+    img.iter_mut()
+        .zip(&on)
+        .for_each(|(n, on)| *n = *on as u8 * 255);
+}
+
+/// Dilated by squares of `2 * r + 1` px, i.e. each pixel is the brightest around.
+pub fn dilate(img: &mut GrayImage, r: u32) {
+    if r == 0 {
+        return;
+    }
+    let (w, h) = img.dimensions();
+    let src = img.clone();
+    let mut tmp = img.clone();
+    tmp.enumerate_pixels_mut().for_each(|(x, y, Luma([n]))| {
+        *n = (x.saturating_sub(r)..=(x + r).min(w - 1))
+            .map(|x| src.get_pixel(x, y).0[0])
+            .max()
+            .unwrap();
+    });
+    img.enumerate_pixels_mut().for_each(|(x, y, Luma([n]))| {
+        *n = (y.saturating_sub(r)..=(y + r).min(h - 1))
+            .map(|y| tmp.get_pixel(x, y).0[0])
+            .max()
+            .unwrap();
+    });
+}
+
+/// Weak thresholds derived by `auto_thresholds` are this fraction of strong ones.
+const AUTO_WEAK_RATIO: f32 = 0.4;
+
+/// Strong and weak thresholds of Canny for the image: The strong one splits magnitudes of
+/// gradients along edges into two classes by Otsu's method; `None` if there are no edges.
+pub fn auto_thresholds(img: &GrayImage, sigma: f32) -> Option<(f32, f32)> {
+    // Nearly all edges are kept by tiny thresholds, with their magnitudes.
+    let all = canny(img.clone(), sigma, 2e-3, 1e-3);
+    let mut mags = Vec::new();
+    for x in 0..all.width() {
+        for y in 0..all.height() {
+            mags.push(all[(x, y)].magnitude());
+        }
+    }
+    let strong = otsu(&mags)?.clamp(2e-3, 0.99);
+    return Some((strong, strong * AUTO_WEAK_RATIO));
+}
+
+/// Threshold splitting positive values into two classes by Otsu's method, i.e. maximizing
+/// the variance between them; `None` if there are no positive values.
+pub fn otsu(values: &[f32]) -> Option<f32> {
+    let mags = values
+        .iter()
+        .copied()
+        .filter(|m| *m > 0.)
+        .collect::<Vec<_>>();
+    let max = mags.iter().copied().fold(0f32, f32::max);
+    if max <= 0. {
+        return None;
+    }
+    let mut hist = [0usize; 256];
+    mags.iter()
+        .for_each(|m| hist[((m / max) * 255.) as usize] += 1);
+    let total = mags.len() as f64;
+    let sum = hist
+        .iter()
+        .enumerate()
+        .map(|(i, n)| i as f64 * *n as f64)
+        .sum::<f64>();
+    // Weight and sum of the lower class, and the largest variance between classes.
+    let (mut w0, mut sum0, mut best) = (0f64, 0f64, (0f64, 0usize));
+    for (i, n) in hist.iter().enumerate() {
+        w0 += *n as f64;
+        sum0 += i as f64 * *n as f64;
+        let w1 = total - w0;
+        if w0 == 0. || w1 == 0. {
+            continue;
+        }
+        let between = w0 * w1 * (sum0 / w0 - (sum - sum0) / w1).powi(2);
+        if between > best.0 {
+            best = (between, i);
+        }
+    }
+    return Some((best.1 + 1) as f32 / 256. * max);
+}
+
+/// Maximum width of images shown inline, in px.
+const PREVIEW_WIDTH: u32 = 640;
+
 pub fn main(
     Param {
         image_dir_or_file,
         output_dir_or_file,
+        algorithm,
         sigma,
         thr_weak,
         thr_strong,
+        auto_threshold,
+        ratio,
+        tau,
+        epsilon,
+        phi,
+        thin: thinning,
+        dilate: dilation,
+        brightness,
+        contrast,
+        gamma,
         crop,
         resize,
         zoom,
         i_skip,
         i_step,
         i_ctr,
+        preview,
         verbose,
     }: Param,
 ) {
+    let preview = preview.map(|p| {
+        util::purify_opt(
+            "Unknown whether your terminal shows inline images; Specify `--preview kitty` or `iterm2`",
+            p.or_else(preview::Protocol::detect),
+        )
+    });
     let verbose = verbose > 0;
     let srcs: Box<dyn Iterator<Item = Result<PathBuf, String>>>;
     let dsts: Box<dyn Iterator<Item = PathBuf>>;
@@ -137,14 +485,47 @@ pub fn main(
             zoom,
             Lanczos3,
         ).to_luma8();
-        img = canny(img, sigma, thr_strong, thr_weak)
-            .as_image()
-            .to_luma8();
-        img.pixels_mut().for_each(|Luma([n])| {
-            if *n != 0 {
-                *n = 255;
+        util::adjust(&mut img, brightness, contrast, gamma);
+        let source = preview.map(|_| DynamicImage::ImageLuma8(img.clone()));
+        img = match algorithm {
+            Algorithm::Canny => {
+                let (strong, weak) = match auto_threshold {
+                    true => auto_thresholds(&img, sigma).unwrap_or((thr_strong, thr_weak)),
+                    false => (thr_strong, thr_weak),
+                };
+                if verbose && auto_threshold {
+                    print!("{:.3}/{:.3} ", strong, weak);
+                }
+                detect(img, sigma, strong, weak)
             }
-        });
+            Algorithm::Sobel => {
+                let mags = gradients(&img, sigma);
+                let thr = match auto_threshold {
+                    true => otsu(&mags).unwrap_or(thr_strong),
+                    false => thr_strong,
+                };
+                if verbose && auto_threshold {
+                    print!("{:.3} ", thr);
+                }
+                sobel(&img, &mags, thr)
+            }
+            Algorithm::Dog => dog(&img, sigma, ratio, 1., epsilon, None),
+            Algorithm::Xdog => dog(&img, sigma, ratio, tau, epsilon, Some(phi)),
+        };
+        if thinning {
+            thin(&mut img);
+        }
+        dilate(&mut img, dilation);
+        if let (Some(protocol), Some(source)) = (preview, source) {
+            let mut out = stdout();
+            println!();
+            preview::show(&mut out, &source, PREVIEW_WIDTH, protocol)
+                .and_then(|_| {
+                    let edges = DynamicImage::ImageLuma8(img.clone());
+                    preview::show(&mut out, &edges, PREVIEW_WIDTH, protocol)
+                })
+                .ok();
+        }
         match img.save(&dst) {
             Ok(_) => match verbose {
                 true => {
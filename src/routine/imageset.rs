@@ -1,4 +1,7 @@
 use crate::*;
+use image::imageops::{Lanczos3, Nearest, Triangle};
+use shoalart::imageset::*;
+use std::io::{stdout, Write};
 
 /// Custom your own imageset
 #[derive(StructOpt, Debug)]
@@ -27,6 +30,8 @@ pub struct Param {
     dump: bool,
 }
 
+////////////////////////////////////////
+
 pub fn main(
     Param {
         image_dir,
@@ -37,4 +42,53 @@ pub fn main(
         dump,
     }: Param,
 ) {
+    let dump = util::whether_dump(dump, "ShoalartDump-Imageset");
+    let mut ims = Imageset {
+        gray,
+        items: Vec::with_capacity(256),
+    };
+    let mut srcs = util::whether_dir(&image_dir, "images", "image", false)
+        .filter_map(|p| p.ok())
+        .collect::<Vec<_>>();
+    srcs.sort_unstable();
+    for (ctr, p) in srcs.into_iter().enumerate() {
+        if ctr % 20 == 0 {
+            stdout().flush().ok();
+        }
+        #[rustfmt::skip]
+        let name = match p.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n.to_string(),
+            None => { print!("K"); continue } // Skipped
+        };
+        #[rustfmt::skip]
+        let img = match image::open(&p) {
+            Ok(i) => util::img3(i, crop, resize, None, Lanczos3),
+            Err(_) => { print!("F"); continue } // Failed
+        };
+        let feat = feature(&img, gray);
+        if let Some(d) = &dump {
+            img.resize_exact(48, 48, Triangle)
+                .save(d.join(format!("_{}.png", ctr)))
+                .ok();
+            let small = img.resize_exact(8, 8, Triangle);
+            match gray {
+                true => small.grayscale(),
+                false => small,
+            }
+            .resize_exact(48, 48, Nearest)
+            .save(d.join(format!("{}.png", ctr)))
+            .ok();
+        }
+        ims.items.push((name, feat));
+        print!(".") // OK!
+    }
+    if ims.items.is_empty() {
+        panic!("No inputs")
+    }
+    println!("\nTotally {} images.", ims.items.len());
+    try_again!(
+        write_imageset(&output_file, &ims),
+        "Failed to write imageset \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
 }
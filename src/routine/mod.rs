@@ -3,3 +3,5 @@ pub mod charset;
 pub mod edgedet;
 pub mod imageset;
 pub mod photon;
+pub mod shoal;
+pub mod util;
@@ -1,4 +1,10 @@
 use crate::*;
+use image::{
+    imageops::{self, Lanczos3, Triangle},
+    DynamicImage, GenericImageView, RgbImage,
+};
+use shoalart::imageset;
+use std::io::{stdout, Write};
 
 /// Create Photomosaic for images from Imageset
 #[derive(StructOpt, Debug)]
@@ -50,4 +56,99 @@ pub fn main(
         negate,
     }: Param,
 ) {
+    let ims = imageset::read_imageset(&imageset).unwrap();
+    if ims.items.is_empty() {
+        panic!("Empty imageset")
+    }
+    println!(
+        "Use {} imageset with {} images.",
+        if ims.gray { "Gray" } else { "RGB" },
+        ims.items.len()
+    );
+    let srcs: Box<dyn Iterator<Item = Result<PathBuf, String>>>;
+    let dsts: Box<dyn Iterator<Item = PathBuf>>;
+    if image_dir_or_file.is_file() {
+        if output_dir_or_file.exists() && !output_dir_or_file.is_file() {
+            panic!(
+                "\"{}\" already existed but not suitable as output file",
+                output_dir_or_file.to_string_lossy()
+            )
+        }
+        srcs = Box::new(vec![Ok(image_dir_or_file)].into_iter());
+        dsts = Box::new(vec![output_dir_or_file].into_iter());
+    } else if image_dir_or_file.is_dir() {
+        if output_dir_or_file.exists() && !output_dir_or_file.is_dir() {
+            panic!(
+                "\"{}\" already existed but not suitable as output dir",
+                output_dir_or_file.to_string_lossy()
+            )
+        }
+        util::create_dir(&output_dir_or_file);
+        srcs = util::whether_dir(image_dir_or_file, "images", "image", false);
+        dsts = Box::new(
+            (1..=u32::MAX)
+                .into_iter()
+                .map(|n| output_dir_or_file.join(format!("{:06}.png", n))),
+        );
+    } else {
+        panic!(
+            "Invalid image(s) path \"{}\"",
+            image_dir_or_file.to_string_lossy()
+        );
+    }
+    // Enlarged tiles are loaded on demand; `None` if unopenable.
+    let mut tiles = AHashMap::<usize, Option<RgbImage>>::with_capacity(ims.items.len());
+    let (ew, eh) = enlarge;
+    for (ctr, (src, dst)) in srcs.zip(dsts).enumerate() {
+        #[rustfmt::skip]
+        let img = match src.map(|p| image::open(&p)) {
+            Ok(Ok(i)) => util::img3(i, crop, resize, None, Lanczos3),
+            Ok(Err(_)) => { print!("F"); continue }
+            Err(_) => { print!("E"); continue }
+        };
+        let mut img = img.to_rgb8();
+        if negate {
+            imageops::invert(&mut img);
+        }
+        let img = DynamicImage::ImageRgb8(img);
+        let (bw, bh) = (img.width() >> 3, img.height() >> 3);
+        let mut canvas = RgbImage::new(bw * ew, bh * eh);
+        for by in 0..bh {
+            for bx in 0..bw {
+                let block = img.crop_imm(bx << 3, by << 3, 8, 8);
+                let f = imageset::feature(&block, ims.gray);
+                let mut rank = ims
+                    .items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, f2))| (i, imageset::similarity(&f, f2)))
+                    .collect::<Vec<(usize, f32)>>();
+                rank.sort_unstable_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+                // Fall back to the next best if the best one is unopenable.
+                for (i, _) in rank {
+                    let tile = tiles.entry(i).or_insert_with(|| {
+                        image::open(imageset_dir.join(&ims.items[i].0))
+                            .map(|t| imageops::resize(&t.to_rgb8(), ew, eh, Triangle))
+                            .ok()
+                    });
+                    if let Some(t) = tile {
+                        imageops::replace(&mut canvas, t, bx * ew, by * eh);
+                        break;
+                    }
+                }
+            }
+        }
+        match canvas.save(&dst) {
+            Ok(_) => {
+                if ctr % 50 == 0 {
+                    print!("[{}]", ctr);
+                } else {
+                    print!(".");
+                }
+            }
+            Err(_) => print!("S"),
+        }
+        stdout().flush().ok();
+    }
+    println!();
 }
@@ -0,0 +1,773 @@
+use crate::*;
+use shoalart::art::{
+    self, print_art, read_anim, read_art, read_art_dir, write_anim_as, write_art_as, Ambiguous,
+    Cell, Channels, Frame, MONO_COLOR,
+};
+use shoalart::cluster::Glyph;
+use shoalart::interchange::{self, Interchange};
+use std::{
+    io::{stdin, stdout, BufReader, BufWriter, Write},
+    time::Duration,
+};
+
+/// Routines about shoal files
+#[derive(StructOpt, Debug)]
+pub enum Param {
+    Pack(ParamPack),
+    Unpack(ParamUnpack),
+    Validate(ParamValidate),
+    Info(ParamInfo),
+    Cat(ParamCat),
+    Transform(ParamTransform),
+    Diff(ParamDiff),
+    Export(ParamExport),
+    Import(ParamImport),
+}
+
+/// Pack a dir of `.shoal` files into one `.shoala` animation
+#[derive(StructOpt, Debug)]
+pub struct ParamPack {
+    #[structopt(parse(from_os_str))]
+    shoal_dir: PathBuf,
+    #[structopt(default_value = "Shoalart-Animation.shoala", parse(from_os_str))]
+    output_file: PathBuf,
+
+    /// Frame rate to be stored; Unspecified by default, then decided by player
+    #[structopt(short, long)]
+    fps: Option<f32>,
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+    /// Save no colors, see `art make`
+    #[structopt(long)]
+    no_color: bool,
+    /// Save colors as gray, see `art make`
+    #[structopt(long, conflicts_with = "no-color")]
+    gray: bool,
+    /// Save colors as indices into palettes, see `art make`
+    #[structopt(long, conflicts_with_all = &["no-color", "gray"])]
+    indexed: bool,
+}
+
+/// Unpack a `.shoala` animation into a dir of `.shoal` files
+///
+/// NOTICE: Delays of frames will be lost.
+#[derive(StructOpt, Debug)]
+pub struct ParamUnpack {
+    #[structopt(parse(from_os_str))]
+    anim_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_dir: PathBuf,
+
+    /// Specify the start value of OUTPUT filename
+    #[structopt(long = "ctr", default_value = "1")]
+    i_ctr: u32,
+    /// Compression of outputs; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+    /// Save no colors, see `art make`
+    #[structopt(long)]
+    no_color: bool,
+    /// Save colors as gray, see `art make`
+    #[structopt(long, conflicts_with = "no-color")]
+    gray: bool,
+    /// Save colors as indices into palettes, see `art make`
+    #[structopt(long, conflicts_with_all = &["no-color", "gray"])]
+    indexed: bool,
+}
+
+/// Check a `.shoal` or `.shoala` file, reporting where it is corrupt
+///
+/// Headers, checksums, cells and chars are checked, and also widths of lines,
+/// which are not errors but look broken when played.
+#[derive(StructOpt, Debug)]
+pub struct ParamValidate {
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+}
+
+/// Print a summary of a `.shoal` or `.shoala` file, or a dir of `.shoal` files
+///
+/// Formats, compressions and dimensions are counted per file, to find mismatched ones in batches.
+#[derive(StructOpt, Debug)]
+pub struct ParamInfo {
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+}
+
+/// Concatenate dirs of `.shoal` files, `.shoala` animations and `.shoal` files into one
+///
+/// The output is a `.shoala` animation if it ends with `.shoala`, otherwise a dir of `.shoal`
+/// files, whose names are kept unless `--renumber`; Frames of animations are named
+/// `{stem}-{n:06}.shoal`.
+#[derive(StructOpt, Debug)]
+pub struct ParamCat {
+    #[structopt(parse(from_os_str))]
+    output_dir_or_file: PathBuf,
+    #[structopt(required = true, parse(from_os_str))]
+    shoal_dirs_or_files: Vec<PathBuf>,
+
+    /// Pad or crop frames of different dimensions to the largest or the smallest ones;
+    /// Refused by default
+    #[structopt(long, possible_values = Fit::NAMES)]
+    fit: Option<Fit>,
+    /// Name outputs `{n:06}.shoal` contiguously, instead of keeping names of inputs
+    #[structopt(long)]
+    renumber: bool,
+    /// Specify the start value of OUTPUT filename, with `--renumber`
+    #[structopt(long = "ctr", default_value = "1")]
+    i_ctr: u32,
+    /// Compression of outputs; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+    /// Save no colors, see `art make`
+    #[structopt(long)]
+    no_color: bool,
+    /// Save colors as gray, see `art make`
+    #[structopt(long, conflicts_with = "no-color")]
+    gray: bool,
+    /// Save colors as indices into palettes, see `art make`
+    #[structopt(long, conflicts_with_all = &["no-color", "gray"])]
+    indexed: bool,
+}
+
+/// Crop or scale a `.shoal` file, a dir of `.shoal` files or a `.shoala` animation by cells,
+/// without making it again
+///
+/// The output is of the same kind as the input; Cropping is done before scaling.
+#[derive(StructOpt, Debug)]
+pub struct ParamTransform {
+    #[structopt(parse(from_os_str))]
+    shoal_dir_or_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_dir_or_file: PathBuf,
+
+    /// Crop frames; No cropping by default
+    ///
+    /// Syntax: `{width}x{height}+{left}+{top}` (unit: columns and rows; Positive numbers only)
+    #[structopt(long, parse(try_from_str = opt_crop))]
+    crop: Option<(usize, usize, usize, usize)>,
+    /// Scale frames by dropping or duplicating cells and lines, e.g. `0.5` halves both dimensions
+    #[structopt(long)]
+    scale: Option<f32>,
+    /// Compression of outputs; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+    /// Save no colors, see `art make`
+    #[structopt(long)]
+    no_color: bool,
+    /// Save colors as gray, see `art make`
+    #[structopt(long, conflicts_with = "no-color")]
+    gray: bool,
+    /// Save colors as indices into palettes, see `art make`
+    #[structopt(long, conflicts_with_all = &["no-color", "gray"])]
+    indexed: bool,
+}
+
+/// Compare two `.shoal` files cell by cell, reporting where glyphs, colors or attributes differ
+///
+/// Cells missing in either one differ too. Exits with `1` if any differ.
+#[derive(StructOpt, Debug)]
+pub struct ParamDiff {
+    #[structopt(parse(from_os_str))]
+    old_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    new_file: PathBuf,
+
+    /// Colors differing by at most so much in each channel are considered the same
+    #[structopt(long, default_value = "0")]
+    tolerance: u8,
+    /// List at most so many differing cells
+    #[structopt(long, default_value = "100")]
+    limit: usize,
+    /// Also print the new one, with differing cells highlighted
+    #[structopt(long)]
+    show: bool,
+    /// Colors supported by your terminal, others are approximated
+    #[structopt(long, default_value = "auto", possible_values = art::Colors::NAMES)]
+    colors: art::Colors,
+}
+
+/// Export a `.shoal` file, a dir of `.shoal` files or a `.shoala` animation as a document,
+/// whose schema is below
+#[derive(StructOpt, Debug)]
+#[structopt(after_help = interchange::SCHEMA)]
+pub struct ParamExport {
+    #[structopt(parse(from_os_str))]
+    shoal_dir_or_file: PathBuf,
+    /// Printed to stdout if `-`
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    #[structopt(short, long, default_value = "json", possible_values = Interchange::NAMES)]
+    format: Interchange,
+}
+
+/// Import a document exported by `shoal export`, or made by other programs
+///
+/// The output is a `.shoala` animation if it ends with `.shoala`, otherwise a `.shoal` file of
+/// the first frame.
+#[derive(StructOpt, Debug)]
+#[structopt(after_help = interchange::SCHEMA)]
+pub struct ParamImport {
+    /// Read from stdin if `-`
+    #[structopt(parse(from_os_str))]
+    input_file: PathBuf,
+    #[structopt(parse(from_os_str))]
+    output_file: PathBuf,
+
+    #[structopt(short, long, default_value = "json", possible_values = Interchange::NAMES)]
+    format: Interchange,
+    /// Compression of the output; `zstd` is smaller, `none` is faster
+    #[structopt(long, default_value = "lz4", possible_values = util::Codec::NAMES)]
+    compress: util::Codec,
+}
+
+/// How `shoal cat` makes dimensions of frames the same.
+#[derive(Clone, Copy, Debug)]
+enum Fit {
+    Pad,
+    Crop,
+}
+
+impl Fit {
+    const NAMES: &'static [&'static str] = &["pad", "crop"];
+}
+
+impl std::str::FromStr for Fit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "pad" => Ok(Fit::Pad),
+            "crop" => Ok(Fit::Crop),
+            _ => Err(format!("Unknown fitting \"{}\"", s)),
+        };
+    }
+}
+
+////////////////////////////////////////
+
+fn channels(no_color: bool, gray: bool, indexed: bool) -> Channels {
+    return match (no_color, gray, indexed) {
+        (true, _, _) => Channels::None,
+        (_, true, _) => Channels::Gray,
+        (_, _, true) => Channels::Indexed,
+        _ => Channels::Rgb,
+    };
+}
+
+pub fn main(param: Param) {
+    match param {
+        Param::Pack(param) => main_pack(param),
+        Param::Unpack(param) => main_unpack(param),
+        Param::Validate(param) => main_validate(param),
+        Param::Info(param) => main_info(param),
+        Param::Cat(param) => main_cat(param),
+        Param::Transform(param) => main_transform(param),
+        Param::Diff(param) => main_diff(param),
+        Param::Export(param) => main_export(param),
+        Param::Import(param) => main_import(param),
+    }
+}
+
+fn main_pack(
+    ParamPack {
+        shoal_dir,
+        output_file,
+        fps,
+        compress,
+        no_color,
+        gray,
+        indexed,
+    }: ParamPack,
+) {
+    let mut art = read_art_dir(&shoal_dir).unwrap();
+    if art.frames.is_empty() {
+        panic!("No inputs")
+    }
+    if let Some(fps) = fps {
+        art.delays.fill(Duration::from_secs_f32(1. / fps));
+    }
+    let (w, h) = art.dimensions();
+    println!("Totally {} frames, {}x{} at most.", art.frames.len(), w, h);
+    try_again!(
+        write_anim_as(
+            &output_file,
+            &art,
+            compress,
+            channels(no_color, gray, indexed)
+        ),
+        "Failed to write animation \"{}\": {:?}",
+        output_file.to_string_lossy(),
+    );
+}
+
+fn main_unpack(
+    ParamUnpack {
+        anim_file,
+        output_dir,
+        i_ctr,
+        compress,
+        no_color,
+        gray,
+        indexed,
+    }: ParamUnpack,
+) {
+    let art = read_anim(&anim_file).unwrap();
+    if output_dir.exists() && !output_dir.is_dir() {
+        panic!(
+            "\"{}\" already existed but not suitable as output dir",
+            output_dir.to_string_lossy()
+        )
+    }
+    util::create_dir(&output_dir);
+    for (n, frame) in (i_ctr..=u32::MAX).zip(&art.frames) {
+        let p = output_dir.join(format!("{:06}.shoal", n));
+        match write_art_as(&p, frame, compress, channels(no_color, gray, indexed)) {
+            Ok(_) => print!("."),
+            Err(_) => print!("S"),
+        }
+        stdout().flush().ok();
+    }
+    println!("\nTotally {} frames.", art.frames.len());
+}
+
+fn main_validate(ParamValidate { path }: ParamValidate) {
+    match art::validate(&path, Ambiguous::Narrow) {
+        Ok(problems) if problems.is_empty() => println!("OK."),
+        Ok(problems) => {
+            for p in &problems {
+                println!("{}", p);
+            }
+            println!("\nTotally {} problems.", problems.len());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Count occurrences of `k`, in order of first appearance.
+fn count(v: &mut Vec<(String, usize)>, k: String) {
+    match v.iter_mut().find(|e| e.0 == k) {
+        Some(e) => e.1 += 1,
+        None => v.push((k, 1)),
+    }
+}
+
+fn main_info(ParamInfo { path }: ParamInfo) {
+    let paths = match path.is_dir() {
+        true => art::art_dir_paths(&path).unwrap(),
+        false => vec![path],
+    };
+    if paths.is_empty() {
+        panic!("No inputs")
+    }
+    let mut headers = Vec::<(String, usize)>::new();
+    let mut channels = Vec::<(String, usize)>::new();
+    let mut codecs = Vec::<(String, usize)>::new();
+    let mut dims = Vec::<(String, usize)>::new();
+    let mut charsets = Vec::<(String, usize)>::new();
+    let (mut frames, mut sizes) = (0, (0, 0));
+    let mut chars = AHashSet::new();
+    let mut colors = AHashSet::new();
+    for p in &paths {
+        let info = match art::inspect(p) {
+            Ok(info) => info,
+            Err(e) => panic!("\"{}\": {}", p.to_string_lossy(), e),
+        };
+        count(&mut headers, info.header);
+        count(&mut channels, format!("{:?}", info.channels).to_lowercase());
+        count(
+            &mut codecs,
+            info.codec.map_or(format!("lz4, without codec byte"), |c| {
+                format!("{:?}", c).to_lowercase()
+            }),
+        );
+        for frame in &info.art.frames {
+            let (w, h) = frame.dimensions();
+            count(&mut dims, format!("{}x{}", w, h));
+            for cell in frame.lines.iter().flatten() {
+                chars.insert(cell.glyph);
+                colors.insert(cell.color);
+                colors.extend(cell.bg);
+            }
+        }
+        count(
+            &mut charsets,
+            info.charset.map_or(format!("unknown"), |s| {
+                format!("{} ({:08x}, {} glyphs)", s.source, s.hash, s.glyphs.len())
+            }),
+        );
+        frames += info.art.frames.len();
+        sizes = (sizes.0 + info.sizes.0, sizes.1 + info.sizes.1);
+    }
+    let join = |v: &[(String, usize)]| match v {
+        [(k, _)] => k.clone(),
+        _ => v
+            .iter()
+            .map(|(k, n)| format!("{} ({})", k, n))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    println!("Files:       {}", paths.len());
+    println!("Formats:     {}", join(&headers));
+    println!("Channels:    {}", join(&channels));
+    println!("Compression: {}", join(&codecs));
+    println!("Dimensions:  {} cells", join(&dims));
+    println!("Frames:      {}", frames);
+    println!("Chars:       {} distinct", chars.len());
+    println!("Charsets:    {}", join(&charsets));
+    println!("Colors:      {} distinct", colors.len());
+    println!(
+        "Sizes:       {} bytes compressed, {} bytes uncompressed ({:.1}%)",
+        sizes.0,
+        sizes.1,
+        sizes.0 as f64 * 100. / sizes.1.max(1) as f64
+    );
+}
+
+fn main_cat(
+    ParamCat {
+        output_dir_or_file,
+        shoal_dirs_or_files,
+        fit,
+        renumber,
+        i_ctr,
+        compress,
+        no_color,
+        gray,
+        indexed,
+    }: ParamCat,
+) {
+    fn name(p: &Path) -> String {
+        return p
+            .file_name()
+            .map_or(String::new(), |n| n.to_string_lossy().into());
+    }
+    // `(name, frame, delay)`
+    let mut frames = Vec::<(String, Frame, Duration)>::new();
+    for p in &shoal_dirs_or_files {
+        if p.is_dir() {
+            let paths = art::art_dir_paths(p).unwrap();
+            let delays = art::dir_delays(p, paths.len()).unwrap();
+            for (p, delay) in paths.iter().zip(delays) {
+                frames.push((name(p), read_art(p).unwrap(), delay));
+            }
+        } else if art::is_anim(p) {
+            let stem = p
+                .file_stem()
+                .map_or(String::new(), |n| n.to_string_lossy().into());
+            let art = read_anim(p).unwrap();
+            for (n, (frame, delay)) in art.frames.into_iter().zip(art.delays).enumerate() {
+                frames.push((format!("{}-{:06}.shoal", stem, n + 1), frame, delay));
+            }
+        } else {
+            frames.push((name(p), read_art(p).unwrap(), Duration::ZERO));
+        }
+    }
+    if frames.is_empty() {
+        panic!("No inputs")
+    }
+
+    let dims = frames.iter().map(|(_, f, _)| f.dimensions());
+    let dims = match fit {
+        Some(Fit::Pad) => Some(dims.fold((0, 0), |(w, h), (w2, h2)| (w.max(w2), h.max(h2)))),
+        Some(Fit::Crop) => Some(dims.fold((usize::MAX, usize::MAX), |(w, h), (w2, h2)| {
+            (w.min(w2), h.min(h2))
+        })),
+        None => {
+            let (first, dims) = (frames[0].1.dimensions(), dims.collect::<Vec<_>>());
+            if let Some(i) = dims.iter().position(|d| *d != first) {
+                panic!(
+                    "\"{}\" is {}x{}, but \"{}\" is {}x{}; Use `--fit` to pad or crop",
+                    frames[i].0, dims[i].0, dims[i].1, frames[0].0, first.0, first.1
+                )
+            }
+            None
+        }
+    };
+    if let Some(dims) = dims {
+        let blank = Cell {
+            color: MONO_COLOR,
+            bg: None,
+            glyph: Glyph::new(' '),
+            attrs: 0,
+        };
+        frames
+            .iter_mut()
+            .for_each(|(_, f, _)| f.fit(dims, blank, Ambiguous::Narrow));
+    }
+    let channels = channels(no_color, gray, indexed);
+
+    if art::is_anim(&output_dir_or_file) {
+        let mut art = art::Art::default();
+        for (_, frame, delay) in frames {
+            art.push(frame, delay);
+        }
+        let (w, h) = art.dimensions();
+        println!("Totally {} frames, {}x{} at most.", art.frames.len(), w, h);
+        try_again!(
+            write_anim_as(&output_dir_or_file, &art, compress, channels),
+            "Failed to write animation \"{}\": {:?}",
+            output_dir_or_file.to_string_lossy(),
+        );
+        return;
+    }
+
+    if renumber {
+        for ((name, _, _), n) in frames.iter_mut().zip(i_ctr..=u32::MAX) {
+            *name = format!("{:06}.shoal", n);
+        }
+    } else {
+        let mut names = AHashSet::new();
+        if let Some((name, _, _)) = frames.iter().find(|(n, _, _)| !names.insert(n.clone())) {
+            panic!("Duplicate name \"{}\"; Use `--renumber` to rename", name)
+        }
+    }
+    if output_dir_or_file.exists() && !output_dir_or_file.is_dir() {
+        panic!(
+            "\"{}\" already existed but not suitable as output dir",
+            output_dir_or_file.to_string_lossy()
+        )
+    }
+    util::create_dir(&output_dir_or_file);
+    for (name, frame, _) in &frames {
+        let p = output_dir_or_file.join(name);
+        match write_art_as(&p, frame, compress, channels) {
+            Ok(_) => print!("."),
+            Err(_) => print!("S"),
+        }
+        stdout().flush().ok();
+    }
+    if let Some(p) = art::timing_path(&output_dir_or_file) {
+        let delays = frames.iter().map(|(_, _, d)| *d).collect::<Vec<_>>();
+        if delays.iter().any(|d| !d.is_zero()) {
+            try_again!(
+                art::write_timing(&p, &delays),
+                "Failed to write timing \"{}\": {:?}",
+                p.to_string_lossy(),
+            );
+        }
+    }
+    println!("\nTotally {} frames.", frames.len());
+}
+
+fn main_transform(
+    ParamTransform {
+        shoal_dir_or_file,
+        output_dir_or_file,
+        crop,
+        scale,
+        compress,
+        no_color,
+        gray,
+        indexed,
+    }: ParamTransform,
+) {
+    if scale.map_or(false, |s| !(s > 0.) || !s.is_finite()) {
+        panic!("Invalid scale")
+    }
+    let transform = |frame: &Frame| {
+        let cropped = crop.map(|c| frame.crop(c, Ambiguous::Narrow));
+        let frame = cropped.as_ref().unwrap_or(frame);
+        return match scale {
+            Some(s) => frame.scale(s),
+            None => frame.clone(),
+        };
+    };
+    let channels = channels(no_color, gray, indexed);
+
+    if shoal_dir_or_file.is_dir() {
+        let paths = art::art_dir_paths(&shoal_dir_or_file).unwrap();
+        if output_dir_or_file.exists() && !output_dir_or_file.is_dir() {
+            panic!(
+                "\"{}\" already existed but not suitable as output dir",
+                output_dir_or_file.to_string_lossy()
+            )
+        }
+        util::create_dir(&output_dir_or_file);
+        for p in &paths {
+            let frame = transform(&read_art(p).unwrap());
+            let dst = output_dir_or_file.join(p.file_name().unwrap());
+            match write_art_as(&dst, &frame, compress, channels) {
+                Ok(_) => print!("."),
+                Err(_) => print!("S"),
+            }
+            stdout().flush().ok();
+        }
+        let timing = (
+            art::timing_path(&shoal_dir_or_file),
+            art::timing_path(&output_dir_or_file),
+        );
+        if let (Some(src), Some(dst)) = timing {
+            if src.is_file() {
+                try_again!(
+                    std::fs::copy(&src, &dst).map(|_| ()),
+                    "Failed to copy timing to \"{}\": {:?}",
+                    dst.to_string_lossy(),
+                );
+            }
+        }
+        println!("\nTotally {} frames.", paths.len());
+    } else if art::is_anim(&shoal_dir_or_file) {
+        let mut art = read_anim(&shoal_dir_or_file).unwrap();
+        art.frames = art.frames.iter().map(transform).collect();
+        let (w, h) = art.dimensions();
+        println!("Totally {} frames, {}x{} at most.", art.frames.len(), w, h);
+        try_again!(
+            write_anim_as(&output_dir_or_file, &art, compress, channels),
+            "Failed to write animation \"{}\": {:?}",
+            output_dir_or_file.to_string_lossy(),
+        );
+    } else {
+        let frame = transform(&read_art(&shoal_dir_or_file).unwrap());
+        let (w, h) = frame.dimensions();
+        println!("{}x{}.", w, h);
+        try_again!(
+            write_art_as(&output_dir_or_file, &frame, compress, channels),
+            "Failed to write art \"{}\": {:?}",
+            output_dir_or_file.to_string_lossy(),
+        );
+    }
+}
+
+/// Background of differing cells in `shoal diff --show`.
+const DIFF_HIGHLIGHT: [u8; 3] = [192, 0, 64];
+
+fn main_diff(
+    ParamDiff {
+        old_file,
+        new_file,
+        tolerance,
+        limit,
+        show,
+        colors,
+    }: ParamDiff,
+) {
+    let (old, mut new) = (read_art(&old_file).unwrap(), read_art(&new_file).unwrap());
+    let same = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= tolerance);
+    let hex = |[r, g, b]: [u8; 3]| format!("#{:02x}{:02x}{:02x}", r, g, b);
+    let mut counts = [0usize; 4]; // Glyphs, colors, backgrounds, attributes.
+    let (mut total, mut differing, mut highlights) = (0, 0, Vec::new());
+    let rows = old.lines.len().max(new.lines.len());
+    for y in 0..rows {
+        let (a, b) = (old.lines.get(y), new.lines.get(y));
+        let cols = a.map_or(0, Vec::len).max(b.map_or(0, Vec::len));
+        for x in 0..cols {
+            total += 1;
+            let (a, b) = (a.and_then(|l| l.get(x)), b.and_then(|l| l.get(x)));
+            let mut notes = Vec::new();
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    if a.glyph != b.glyph {
+                        counts[0] += 1;
+                        notes.push(format!("{:?} -> {:?}", a.glyph, b.glyph));
+                    }
+                    if !same(a.color, b.color) {
+                        counts[1] += 1;
+                        notes.push(format!("color {} -> {}", hex(a.color), hex(b.color)));
+                    }
+                    let bg = |c: Option<[u8; 3]>| c.map_or(format!("none"), hex);
+                    if !match (a.bg, b.bg) {
+                        (Some(a), Some(b)) => same(a, b),
+                        (a, b) => a == b,
+                    } {
+                        counts[2] += 1;
+                        notes.push(format!("background {} -> {}", bg(a.bg), bg(b.bg)));
+                    }
+                    if a.attrs != b.attrs {
+                        counts[3] += 1;
+                        notes.push(format!("attributes {} -> {}", a.attrs, b.attrs));
+                    }
+                }
+                (Some(a), None) => notes.push(format!("{:?} -> missing", a.glyph)),
+                (None, Some(b)) => notes.push(format!("missing -> {:?}", b.glyph)),
+                (None, None) => (),
+            }
+            if notes.is_empty() {
+                continue;
+            }
+            if differing < limit {
+                println!("Line {}, cell {}: {}", y + 1, x + 1, notes.join("; "));
+            }
+            differing += 1;
+            highlights.push((y, x));
+        }
+    }
+    if differing > limit {
+        println!("... and {} more.", differing - limit);
+    }
+    if show {
+        for (y, x) in highlights {
+            if let Some(cell) = new.lines.get_mut(y).and_then(|l| l.get_mut(x)) {
+                cell.bg = Some(DIFF_HIGHLIGHT);
+            }
+        }
+        print_art(&mut stdout(), &new, Some(colors.detect())).unwrap();
+        stdout().flush().ok();
+    }
+    println!(
+        "Totally {} of {} cells differ: {} glyphs, {} colors, {} backgrounds, {} attributes.",
+        differing, total, counts[0], counts[1], counts[2], counts[3]
+    );
+    if differing > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn main_export(
+    ParamExport {
+        shoal_dir_or_file,
+        output_file,
+        format,
+    }: ParamExport,
+) {
+    let art = art::open_art(&shoal_dir_or_file).unwrap();
+    let exported = match output_file.to_str() {
+        Some("-") => interchange::export(stdout().lock(), &art, format),
+        _ => match std::fs::File::create(&output_file) {
+            Ok(f) => interchange::export(BufWriter::new(f), &art, format),
+            Err(e) => Err(format!("Failed to create output: {:?}", e)),
+        },
+    };
+    exported.unwrap();
+}
+
+fn main_import(
+    ParamImport {
+        input_file,
+        output_file,
+        format,
+        compress,
+    }: ParamImport,
+) {
+    let art = match input_file.to_str() {
+        Some("-") => interchange::import(stdin().lock(), format),
+        _ => match std::fs::File::open(&input_file) {
+            Ok(f) => interchange::import(BufReader::new(f), format),
+            Err(e) => Err(format!("Failed to open input: {:?}", e)),
+        },
+    };
+    let art = art.unwrap();
+    if art.frames.is_empty() {
+        panic!("No frames")
+    }
+    let (w, h) = art.dimensions();
+    println!("Totally {} frames, {}x{} at most.", art.frames.len(), w, h);
+    match art::is_anim(&output_file) {
+        true => try_again!(
+            write_anim_as(&output_file, &art, compress, Channels::Rgb),
+            "Failed to write animation \"{}\": {:?}",
+            output_file.to_string_lossy(),
+        ),
+        false => try_again!(
+            write_art_as(&output_file, &art.frames[0], compress, Channels::Rgb),
+            "Failed to write art \"{}\": {:?}",
+            output_file.to_string_lossy(),
+        ),
+    }
+}
@@ -0,0 +1,81 @@
+pub use shoalart::util::*;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+pub fn purify_err<T, E: Debug>(msg: &str, r: Result<T, E>) -> T {
+    return match r {
+        Ok(obj) => obj,
+        Err(e) => panic!("{}: {:?}", msg, e),
+    };
+}
+
+pub fn purify_opt<T>(msg: &str, o: Option<T>) -> T {
+    return match o {
+        Some(o) => o,
+        None => panic!("{}", msg),
+    };
+}
+
+pub fn create_dir<P: AsRef<Path>>(p: P) {
+    let p = p.as_ref();
+    if !p.exists() {
+        purify_err(
+            &format!("Failed to create dir \"{}\"", p.to_string_lossy()),
+            std::fs::create_dir_all(p),
+        );
+    } else if !p.is_dir() {
+        panic!("\"{}\" already existed but not a dir", p.to_string_lossy())
+    }
+}
+
+pub fn whether_dump(b: bool, p: &str) -> Option<PathBuf> {
+    return match b {
+        false => None,
+        true => {
+            let p = PathBuf::from(p);
+            create_dir(&p);
+            Some(p)
+        }
+    };
+}
+
+pub fn whether_dir<P: AsRef<Path>>(
+    path: P,
+    m1: &'static str,
+    m2: &'static str,
+    verbose: bool,
+) -> Box<dyn Iterator<Item = Result<PathBuf, String>> + Send> {
+    return Box::new(match std::fs::read_dir(path) {
+        Ok(d) => d.into_iter().map(move |d| match d {
+            Ok(d) => Ok(d.path()),
+            Err(e) => Err(match verbose {
+                true => format!("Failed to access {}: {:?}", m2, e),
+                false => String::with_capacity(0),
+            }),
+        }),
+        Err(e) => panic!("Failed to access {}: {:?}", m1, e),
+    });
+}
+
+macro_rules! try_again {
+    ($func:expr , $msg:literal $(, $args:expr)* $(,)?) => {{
+        let v;
+        loop {
+            v = match $func {
+                Ok(v) => v,
+                Err(e) => {
+                    println!($msg $(, $args)* , e);
+                    println!("(press ENTER to try again or press CTRL-C to terminate)");
+                    $crate::util::pause!();
+                }
+            };
+            break;
+        }
+        v
+    }};
+}
+
+#[rustfmt::skip]
+macro_rules! pause { () => {{ std::io::Read::read(&mut std::io::stdin(), &mut [0u8]).unwrap(); }}; }
+
+pub(crate) use {pause, try_again};
@@ -0,0 +1,80 @@
+//! Streaming art to TCP/telnet clients as ANSI escape sequences, like `parrot.live`.
+
+use crate::*;
+use art::{play_art, play_diff, Ambiguous, Art, Colors};
+use crossterm::{
+    cursor::{Hide as HideCursor, MoveTo},
+    queue,
+    terminal::{Clear, ClearType},
+};
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+const IAC: u8 = 255;
+const DO: u8 = 253;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const TERMINAL_TYPE: u8 = 24;
+const IS: u8 = 0;
+const SEND: u8 = 1;
+
+/// Ask the client its terminal type by telnet (RFC 1091).
+///
+/// `None` if the client does not answer in time, e.g. raw TCP clients like `nc`.
+pub fn terminal_type(stream: &mut TcpStream, timeout: Duration) -> Option<String> {
+    #[rustfmt::skip]
+    let asked = [IAC, DO, TERMINAL_TYPE, IAC, SB, TERMINAL_TYPE, SEND, IAC, SE];
+    stream.write_all(&asked).ok()?;
+    let start = Instant::now();
+    let mut got = Vec::<u8>::with_capacity(64);
+    let mut buf = [0u8; 64];
+    let head = [IAC, SB, TERMINAL_TYPE, IS];
+    let term = loop {
+        let left = timeout.checked_sub(start.elapsed())?;
+        stream
+            .set_read_timeout(Some(left.max(Duration::from_millis(1))))
+            .ok()?;
+        match stream.read(&mut buf) {
+            Ok(0) => return None,
+            Ok(n) => got.extend_from_slice(&buf[..n]),
+            Err(_) => return None,
+        }
+        if let Some(p) = got.windows(head.len()).position(|w| w == head) {
+            let rest = &got[p + head.len()..];
+            if let Some(q) = rest.windows(2).position(|w| w == [IAC, SE]) {
+                break String::from_utf8_lossy(&rest[..q]).into_owned();
+            }
+        }
+    };
+    stream.set_read_timeout(None).ok();
+    return Some(term);
+}
+
+/// Play frames in loop until the client leaves, frames without delay last `avg`.
+pub fn stream_art<W: Write>(
+    out: &mut W,
+    art: &Art,
+    colors: Option<Colors>,
+    avg: Duration,
+) -> io::Result<()> {
+    queue!(out, Clear(ClearType::All), HideCursor, MoveTo(0, 0))?;
+    let mut prev = None;
+    loop {
+        for (frame, delay) in art.frames.iter().zip(&art.delays) {
+            let now = Instant::now();
+            match prev {
+                // Terminals of clients are unknown, most draw ambiguous chars narrow.
+                Some(p) => play_diff(out, p, frame, 0, 0, colors, Ambiguous::Narrow)?,
+                None => play_art(out, frame, 0, 0, colors)?,
+            }
+            out.flush()?;
+            prev = Some(frame);
+            let wait = if delay.is_zero() { avg } else { *delay };
+            thread::sleep(wait.saturating_sub(now.elapsed()));
+        }
+    }
+}
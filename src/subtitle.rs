@@ -0,0 +1,104 @@
+//! Subtitles in SubRip (`.srt`) format, shown during play.
+
+use crate::*;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srt() {
+        let text = "\u{feff}1\r\n00:00:01,500 --> 00:00:03,000\r\n<i>Hello</i>,\r\nworld!\r\n\r\n\
+                    2\n00:01:02.250 --> 01:00:00,000 X1:10 X2:90\nBye\n\n\n";
+        let cues = parse_srt(text).unwrap();
+        assert_eq!(
+            cues,
+            [
+                Cue {
+                    start: Duration::from_millis(1_500),
+                    end: Duration::from_millis(3_000),
+                    text: "Hello, world!".to_string(),
+                },
+                Cue {
+                    start: Duration::from_millis(62_250),
+                    end: Duration::from_secs(3_600),
+                    text: "Bye".to_string(),
+                },
+            ]
+        );
+        let at = |ms| active(&cues, Duration::from_millis(ms)).count();
+        assert_eq!([at(1_000), at(1_500), at(2_999), at(3_000)], [0, 1, 1, 0]);
+        assert!(parse_srt("1\n00:00:01 -> 00:00:02\nOops\n").is_err());
+    }
+}
+
+/// A subtitle shown in `start..end`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    /// Lines are joined by spaces, and tags like `<i>` are stripped.
+    pub text: String,
+}
+
+pub fn read_srt<P: AsRef<Path>>(p: P) -> Result<Vec<Cue>, String> {
+    return match std::fs::read_to_string(p.as_ref()) {
+        Ok(text) => parse_srt(&text),
+        Err(e) => Err(format!("Failed to read subtitles: {:?}", e)),
+    };
+}
+
+/// Cues are separated by empty lines, each is an optional counter,
+/// a timing line like `00:01:02,500 --> 00:01:04,000`, then text lines.
+pub fn parse_srt(text: &str) -> Result<Vec<Cue>, String> {
+    let mut cues = Vec::new();
+    let mut lines = text.trim_start_matches('\u{feff}').lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        // Empty, or the counter.
+        if line.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let (start, end) = match line.split_once("-->") {
+            Some((s, e)) => (parse_time(s), parse_time(e)),
+            None => (None, None),
+        };
+        let (start, end) = match (start, end) {
+            (Some(s), Some(e)) => (s, e),
+            _ => Err(format!("Invalid timing \"{}\" in subtitles", line))?,
+        };
+        let mut text = String::new();
+        for line in lines.by_ref().take_while(|l| !l.is_empty()) {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            let mut tag = false;
+            for c in line.chars() {
+                match c {
+                    '<' => tag = true,
+                    '>' if tag => tag = false,
+                    c if !tag => text.push(c),
+                    _ => (),
+                }
+            }
+        }
+        cues.push(Cue { start, end, text });
+    }
+    return Ok(cues);
+}
+
+/// `HH:MM:SS,mmm`, the comma can also be a period; Position info after it is ignored.
+fn parse_time(s: &str) -> Option<Duration> {
+    let s = s.trim().split_whitespace().next()?;
+    let (hms, ms) = s.split_once(|c| c == ',' || c == '.').unwrap_or((s, "0"));
+    let mut secs = 0u64;
+    for n in hms.split(':') {
+        secs = secs * 60 + n.parse::<u64>().ok()?;
+    }
+    return Some(Duration::from_secs(secs) + Duration::from_millis(ms.parse().ok()?));
+}
+
+/// Cues shown at the time.
+pub fn active(cues: &[Cue], t: Duration) -> impl Iterator<Item = &Cue> {
+    return cues.iter().filter(move |c| c.start <= t && t < c.end);
+}
@@ -1,61 +1,56 @@
-use crate::*;
-use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage,
+};
 use lz4_flex::frame as lz4;
-use std::{fmt::Debug, io};
+use std::{
+    fmt::Debug,
+    io::{self, Read, Write},
+    str::FromStr,
+};
 
-pub fn purify_err<T, E: Debug>(msg: &str, r: Result<T, E>) -> T {
-    return match r {
-        Ok(obj) => obj,
-        Err(e) => panic!("{}: {:?}", msg, e),
-    };
-}
-
-pub fn purify_opt<T>(msg: &str, o: Option<T>) -> T {
-    return match o {
-        Some(o) => o,
-        None => panic!("{}", msg),
-    };
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn create_dir<P: AsRef<Path>>(p: P) {
-    let p = p.as_ref();
-    if !p.exists() {
-        purify_err(
-            &format!("Failed to create dir \"{}\"", p.to_string_lossy()),
-            std::fs::create_dir_all(p),
-        );
-    } else if !p.is_dir() {
-        panic!("\"{}\" already existed but not a dir", p.to_string_lossy())
+    fn inflate(buf: Vec<u8>) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut r = decompress(io::Cursor::new(buf)).unwrap();
+        r.read_to_end(&mut out).unwrap();
+        return out;
     }
-}
 
-pub fn whether_dump(b: bool, p: &str) -> Option<PathBuf> {
-    return match b {
-        false => None,
-        true => {
-            let p = PathBuf::from(p);
-            create_dir(&p);
-            Some(p)
+    #[test]
+    fn test_codecs() {
+        let data = b"Shoalart ".repeat(100);
+        for codec in [Codec::None, Codec::Lz4, Codec::Zstd] {
+            let mut w = compress(Vec::new(), codec).unwrap();
+            w.write_all(&data).unwrap();
+            let buf = w.finish().unwrap();
+            assert_eq!(Codec::from_byte(buf[0]), Some(codec));
+            assert_eq!(inflate(buf), data);
         }
-    };
-}
+    }
+    #[test]
+    fn test_codec_legacy() {
+        let data = b"Shoalart ".repeat(100);
+        let mut w = lz4write(Vec::new());
+        w.write_all(&data).unwrap();
+        let buf = w.finish().unwrap();
+        assert_eq!(buf[0], LZ4_MAGIC);
+        assert_eq!(inflate(buf), data);
+    }
 
-pub fn whether_dir<P: AsRef<Path>>(
-    path: P,
-    m1: &'static str,
-    m2: &'static str,
-    verbose: bool,
-) -> Box<dyn Iterator<Item = Result<PathBuf, String>>> {
-    return Box::new(match std::fs::read_dir(path) {
-        Ok(d) => d.into_iter().map(move |d| match d {
-            Ok(d) => Ok(d.path()),
-            Err(e) => Err(match verbose {
-                true => format!("Failed to access {}: {:?}", m2, e),
-                false => String::with_capacity(0),
-            }),
-        }),
-        Err(e) => panic!("Failed to access {}: {:?}", m1, e),
-    });
+    #[test]
+    fn test_crc32() {
+        let mut w = Crc32::new(Vec::new());
+        w.write_all(b"12345").unwrap();
+        w.write_all(b"6789").unwrap();
+        assert_eq!((w.crc(), w.count), (0xcbf43926, 9));
+        let mut r = Crc32::new(&b"123456789"[..]);
+        r.read_to_end(&mut Vec::new()).unwrap();
+        assert_eq!(r.crc(), 0xcbf43926);
+    }
 }
 
 pub fn lz4read<R: io::Read>(r: R) -> lz4::FrameDecoder<R> {
@@ -73,6 +68,201 @@ pub fn lz4cfg() -> lz4::FrameInfo {
     return cfg;
 }
 
+/// Compression of `.shoal`, `.shoala` and charset payloads, as a byte right after the header.
+///
+/// Files without it have an LZ4 frame there instead, whose magic never starts with a codec byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    None = 0,
+    Lz4 = 1,
+    Zstd = 2,
+}
+
+impl Codec {
+    pub const NAMES: &'static [&'static str] = &["lz4", "zstd", "none"];
+
+    /// Of the byte right after the header; Files without it are LZ4.
+    pub fn from_byte(b: u8) -> Option<Codec> {
+        return match b {
+            0 => Some(Codec::None),
+            1 => Some(Codec::Lz4),
+            2 => Some(Codec::Zstd),
+            _ => None,
+        };
+    }
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        return Codec::Lz4;
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Codec::None),
+            "lz4" => Ok(Codec::Lz4),
+            "zstd" => Ok(Codec::Zstd),
+            _ => Err(format!("Unknown codec \"{}\"", s)),
+        };
+    }
+}
+
+/// Read the codec byte, then decompress the rest.
+pub fn decompress<R: Read + 'static>(mut r: R) -> io::Result<Box<dyn Read>> {
+    let mut buf = [0u8];
+    r.read_exact(&mut buf)?;
+    return Ok(match buf[0] {
+        0 => Box::new(r),
+        1 => Box::new(lz4read(r)),
+        2 => Box::new(zstd::Decoder::new(r)?),
+        LZ4_MAGIC => Box::new(lz4read(io::Cursor::new(buf).chain(r))),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown codec"))?,
+    });
+}
+
+/// The first byte of LZ4 frames, which files without codec byte start with.
+const LZ4_MAGIC: u8 = 0x04;
+
+/// Write the codec byte, then compress the rest; `finish` is required.
+pub fn compress<W: Write>(mut w: W, codec: Codec) -> io::Result<Encoder<W>> {
+    w.write_all(&[codec as u8])?;
+    return Ok(match codec {
+        Codec::None => Encoder::None(w),
+        Codec::Lz4 => Encoder::Lz4(lz4write(w)),
+        Codec::Zstd => Encoder::Zstd(zstd::Encoder::new(w, 0)?),
+    });
+}
+
+pub enum Encoder<W: Write> {
+    None(W),
+    Lz4(lz4::FrameEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn finish(self) -> io::Result<W> {
+        return match self {
+            Encoder::None(mut w) => w.flush().map(|_| w),
+            Encoder::Lz4(e) => Ok(e.finish()?),
+            Encoder::Zstd(e) => e.finish(),
+        };
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        return match self {
+            Encoder::None(w) => w.write(buf),
+            Encoder::Lz4(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+        };
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return match self {
+            Encoder::None(w) => w.flush(),
+            Encoder::Lz4(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+        };
+    }
+}
+
+/// Pass-through reader or writer, keeping the CRC-32 (IEEE) and the count of bytes passed.
+pub struct Crc32<T> {
+    pub inner: T,
+    crc: u32,
+    pub count: u64,
+}
+
+impl<T> Crc32<T> {
+    pub fn new(inner: T) -> Self {
+        return Crc32 {
+            inner,
+            crc: !0,
+            count: 0,
+        };
+    }
+
+    /// Of bytes passed so far.
+    pub fn crc(&self) -> u32 {
+        return !self.crc;
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        for b in buf {
+            self.crc = CRC32_TABLE[((self.crc ^ *b as u32) & 0xff) as usize] ^ (self.crc >> 8);
+        }
+        self.count += buf.len() as u64;
+    }
+}
+
+impl<R: Read> Read for Crc32<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        return Ok(n);
+    }
+}
+
+impl<W: Write> Write for Crc32<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        return Ok(n);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// CRC-32 of the whole bytes.
+pub fn crc32(buf: &[u8]) -> u32 {
+    let mut c = Crc32::new(());
+    c.update(buf);
+    return c.crc();
+}
+
+/// `YYYY-MM-DD` in UTC of seconds since the UNIX epoch.
+pub fn format_date(secs: u64) -> String {
+    // Days to the civil date, by Howard Hinnant's algorithm.
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    return format!("{:04}-{:02}-{:02}", y, m, d);
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = match c & 1 {
+                0 => c >> 1,
+                _ => 0xedb88320 ^ (c >> 1),
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    return table;
+}
+
 pub fn img3(
     mut img: DynamicImage,
     crop: Option<(u32, u32, u32, u32)>,
@@ -95,29 +285,140 @@ pub fn img3(
     return img;
 }
 
-#[macro_export]
-#[rustfmt::skip]
-macro_rules! unsafe_init { () => {{ unsafe { std::mem::MaybeUninit::uninit().assume_init() } }}; }
-
-#[macro_export]
-macro_rules! try_again {
-    ($func:expr , $msg:literal $(, $args:expr)* $(,)?) => {{
-        let v;
-        loop {
-            v = match $func {
-                Ok(v) => v,
-                Err(e) => {
-                    println!($msg $(, $args)* , e);
-                    println!("(press ENTER to try again or press CTRL-C to terminate)");
-                    pause!();
+/// Scale the height, so that what each `4x8` block covers looks in proportion in cells of `w:h`.
+pub fn squash(img: DynamicImage, (w, h): (f32, f32), filter: FilterType) -> DynamicImage {
+    let nh = (img.height() as f32 * 2. * w / h).round().max(1.) as u32;
+    return img.resize_exact(img.width(), nh, filter);
+}
+
+/// Extend the right and bottom of the image with the color, to multiples of `(w, h)`.
+pub fn pad(img: DynamicImage, (w, h): (u32, u32), [r, g, b]: [u8; 3]) -> DynamicImage {
+    let (iw, ih) = img.dimensions();
+    let (nw, nh) = ((iw + w - 1) / w * w, (ih + h - 1) / h * h);
+    if (nw, nh) == (iw, ih) {
+        return img;
+    }
+    let mut canvas = RgbaImage::from_pixel(nw, nh, Rgba([r, g, b, 255]));
+    imageops::replace(&mut canvas, &img.to_rgba8(), 0, 0);
+    return DynamicImage::ImageRgba8(canvas);
+}
+
+/// Adjust luma by `gamma` (`1` unchanged, larger is brighter), then `contrast` around the middle gray
+/// (`1` unchanged), then add `brightness` (within `-1..=1`).
+pub fn adjust(img: &mut GrayImage, brightness: f32, contrast: f32, gamma: f32) {
+    if (brightness, contrast, gamma) == (0., 1., 1.) {
+        return;
+    }
+    let lut = (0..=255u8)
+        .map(|n| {
+            let v = (n as f32 / 255.).powf(1. / gamma);
+            let v = (v - 0.5) * contrast + 0.5 + brightness;
+            (v * 255.).round().clamp(0., 255.) as u8
+        })
+        .collect::<Vec<_>>();
+    img.pixels_mut().for_each(|Luma([n])| *n = lut[*n as usize]);
+}
+
+/// Luma at the fraction `cut` from the darkest and the brightest ends, within `0..=1`.
+pub fn levels(img: &GrayImage, cut: f32) -> (f32, f32) {
+    let mut hist = [0usize; 256];
+    img.pixels().for_each(|Luma([n])| hist[*n as usize] += 1);
+    let skip = ((img.width() * img.height()) as f32 * cut) as usize;
+    let find = |bins: &mut dyn Iterator<Item = usize>| {
+        let mut acc = 0;
+        return bins
+            .enumerate()
+            .find(|(_, n)| {
+                acc += hist[*n];
+                acc > skip
+            })
+            .map_or(0, |(i, _)| i);
+    };
+    let lo = find(&mut (0..256));
+    let hi = 255 - find(&mut (0..256).rev());
+    return (lo as f32 / 255., hi.max(lo) as f32 / 255.);
+}
+
+/// Stretch luma linearly so that `lo` becomes black and `hi` white.
+pub fn stretch(img: &mut GrayImage, (lo, hi): (f32, f32)) {
+    let range = (hi - lo).max(1. / 255.);
+    let lut = (0..=255u8)
+        .map(|n| {
+            ((n as f32 / 255. - lo) / range * 255.)
+                .round()
+                .clamp(0., 255.) as u8
+        })
+        .collect::<Vec<_>>();
+    img.pixels_mut().for_each(|Luma([n])| *n = lut[*n as usize]);
+}
+
+/// Global histogram equalization.
+pub fn equalize(img: &mut GrayImage) {
+    let mut hist = [0f32; 256];
+    img.pixels().for_each(|Luma([n])| hist[*n as usize] += 1.);
+    let lut = cdf_lut(&hist);
+    img.pixels_mut().for_each(|Luma([n])| *n = lut[*n as usize]);
+}
+
+/// Contrast limited adaptive histogram equalization: Each of `tiles x tiles` is equalized
+/// with bins clipped at `clip` times the mean, then mappings of tiles are interpolated bilinearly.
+pub fn clahe(img: &mut GrayImage, tiles: u32, clip: f32) {
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 {
+        return;
+    }
+    let (tw, th) = ((w + tiles - 1) / tiles, (h + tiles - 1) / tiles);
+    let (nx, ny) = (((w + tw - 1) / tw) as usize, ((h + th - 1) / th) as usize);
+    let mut luts = Vec::with_capacity(nx * ny);
+    for ty in 0..ny as u32 {
+        for tx in 0..nx as u32 {
+            let mut hist = [0f32; 256];
+            for y in ty * th..((ty + 1) * th).min(h) {
+                for x in tx * tw..((tx + 1) * tw).min(w) {
+                    hist[img.get_pixel(x, y)[0] as usize] += 1.;
                 }
-            };
-            break;
+            }
+            // Redistribute the excess evenly.
+            let limit = clip * hist.iter().sum::<f32>() / 256.;
+            let excess = hist
+                .iter_mut()
+                .map(|b| {
+                    let e = (*b - limit).max(0.);
+                    *b -= e;
+                    e
+                })
+                .sum::<f32>();
+            hist.iter_mut().for_each(|b| *b += excess / 256.);
+            luts.push(cdf_lut(&hist));
         }
-        v
-    }};
+    }
+    // Tile centers are where the mappings are exact.
+    let pos = |p: u32, size: u32, n: usize| {
+        let f = ((p as f32 + 0.5) / size as f32 - 0.5).clamp(0., (n - 1) as f32);
+        let i = f.floor() as usize;
+        return (i, (i + 1).min(n - 1), f - i as f32);
+    };
+    for y in 0..h {
+        let (y0, y1, ay) = pos(y, th, ny);
+        for x in 0..w {
+            let (x0, x1, ax) = pos(x, tw, nx);
+            let Luma([n]) = *img.get_pixel(x, y);
+            let at = |tx: usize, ty: usize| luts[ty * nx + tx][n as usize] as f32;
+            let v = (at(x0, y0) * (1. - ax) + at(x1, y0) * ax) * (1. - ay)
+                + (at(x0, y1) * (1. - ax) + at(x1, y1) * ax) * ay;
+            img.put_pixel(x, y, Luma([v.round() as u8]));
+        }
+    }
 }
 
-#[macro_export]
-#[rustfmt::skip]
-macro_rules! pause { () => {{ std::io::stdin().read(&mut [0u8]).unwrap(); }}; }
+/// Map each level by the cumulative distribution of the histogram.
+fn cdf_lut(hist: &[f32; 256]) -> [u8; 256] {
+    let total = hist.iter().sum::<f32>().max(1.);
+    let mut lut = [0u8; 256];
+    let mut acc = 0.;
+    for (l, h) in lut.iter_mut().zip(hist) {
+        acc += h;
+        *l = (acc / total * 255.).round().min(255.) as u8;
+    }
+    return lut;
+}
@@ -0,0 +1,170 @@
+//! Decoding videos and animated images into frames.
+//!
+//! Videos are decoded by spawning `ffmpeg`, which should be found in `PATH`.
+
+use crate::*;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    AnimationDecoder, DynamicImage, RgbImage,
+};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+pub const FFMPEG: &str = "ffmpeg";
+pub const VIDEO_SUFFIXES: &[&str] = &["mp4", "mkv", "webm", "mov", "avi", "flv"];
+
+pub fn is_video<P: AsRef<Path>>(p: P) -> bool {
+    return p.as_ref().extension().map_or(false, |e| {
+        VIDEO_SUFFIXES.iter().any(|s| e.eq_ignore_ascii_case(s))
+    });
+}
+
+/// Decoded frames in order; `ffmpeg` is killed once dropped.
+pub struct Frames {
+    child: Child,
+    out: BufReader<ChildStdout>,
+    done: bool,
+}
+
+/// Resample to `fps` if provided, otherwise keep the original frame rate.
+pub fn decode_video<P: AsRef<Path>>(p: P, fps: Option<f32>) -> Result<Frames, String> {
+    let mut cmd = Command::new(FFMPEG);
+    cmd.args(["-nostdin", "-loglevel", "error", "-i"])
+        .arg(p.as_ref())
+        .arg("-an");
+    if let Some(fps) = fps {
+        cmd.arg("-vf").arg(format!("fps={}", fps));
+    }
+    cmd.args(["-pix_fmt", "rgb24", "-f", "image2pipe", "-c:v", "ppm", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    let mut child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(e) => Err(format!("Failed to spawn `{}`: {:?}", FFMPEG, e))?,
+    };
+    let out = BufReader::new(child.stdout.take().unwrap());
+    return Ok(Frames {
+        child,
+        out,
+        done: false,
+    });
+}
+
+impl Frames {
+    /// `Ok(None)` at the end of stream.
+    fn read_frame(&mut self) -> io::Result<Option<RgbImage>> {
+        if self.out.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let mut head = [0u32; 3];
+        if self.token()? != "P6" {
+            Err(invalid("Not a PPM frame"))?;
+        }
+        for n in &mut head {
+            *n = match self.token()?.parse() {
+                Ok(n) => n,
+                Err(_) => Err(invalid("Invalid PPM header"))?,
+            };
+        }
+        let [w, h, max] = head;
+        if max != 255 {
+            Err(invalid("Unsupported PPM depth"))?;
+        }
+        let mut buf = vec![0u8; w as usize * h as usize * 3];
+        self.out.read_exact(&mut buf)?;
+        return Ok(RgbImage::from_raw(w, h, buf));
+    }
+
+    /// Next whitespace-separated token of a PPM header, consuming one trailing whitespace.
+    fn token(&mut self) -> io::Result<String> {
+        let mut s = String::with_capacity(8);
+        let mut b = [0u8];
+        loop {
+            self.out.read_exact(&mut b)?;
+            match b[0] {
+                c if c.is_ascii_whitespace() && s.is_empty() => continue,
+                c if c.is_ascii_whitespace() => break,
+                c => s.push(c as char),
+            }
+        }
+        return Ok(s);
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, msg);
+}
+
+impl Iterator for Frames {
+    type Item = Result<RgbImage, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let r = match self.read_frame() {
+            Ok(Some(f)) => return Some(Ok(f)),
+            Ok(None) => match self.child.wait() {
+                Ok(s) if s.success() => None,
+                Ok(s) => Some(Err(format!("`{}` exited with {}", FFMPEG, s))),
+                Err(e) => Some(Err(format!("Failed to wait `{}`: {:?}", FFMPEG, e))),
+            },
+            Err(e) => Some(Err(format!("Failed to decode video: {:?}", e))),
+        };
+        self.done = true;
+        return r;
+    }
+}
+
+impl Drop for Frames {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+////////////////////////////////////////
+
+/// Frames of an animated GIF or APNG, with their delays.
+///
+/// `Ok(None)` if the file is not animated, i.e. has only one frame or is not a GIF/PNG.
+pub fn decode_animation<P: AsRef<Path>>(p: P) -> Result<Option<Vec<(RgbImage, Duration)>>, String> {
+    let p = p.as_ref();
+    let ext = match p.extension().and_then(|e| e.to_str()) {
+        Some(e) => e.to_ascii_lowercase(),
+        None => return Ok(None),
+    };
+    if !["gif", "png", "apng"].contains(&ext.as_str()) {
+        return Ok(None);
+    }
+    let file = match File::open(p) {
+        Ok(f) => BufReader::new(f),
+        Err(e) => Err(format!("Failed to open animation: {:?}", e))?,
+    };
+    let err = |e| format!("Failed to decode animation: {:?}", e);
+    let frames = if ext == "gif" {
+        GifDecoder::new(file).map_err(err)?.into_frames()
+    } else {
+        let dec = PngDecoder::new(file).map_err(err)?;
+        if !dec.is_apng() {
+            return Ok(None);
+        }
+        dec.apng().into_frames()
+    };
+    let frames = frames
+        .map(|f| {
+            f.map(|f| {
+                let (n, d) = f.delay().numer_denom_ms();
+                let delay = Duration::from_micros(n as u64 * 1000 / d.max(1) as u64);
+                (DynamicImage::ImageRgba8(f.into_buffer()).to_rgb8(), delay)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(err)?;
+    return Ok(if frames.len() > 1 { Some(frames) } else { None });
+}